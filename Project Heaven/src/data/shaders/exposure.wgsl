@@ -0,0 +1,62 @@
+// Average luminance of the HDR scene for auto exposure, eased towards over several frames
+
+struct Params {
+    operator: u32;
+    exposure: f32;
+    gamma: f32;
+    auto_exposure: u32;
+    key: f32;
+    // Fraction of the way to the new average this frame moves
+    adaptation: f32;
+};
+
+struct Exposure {
+    luminance: f32;
+};
+
+[[group(0), binding(0)]]
+var hdr: texture_2d<f32>;
+[[group(0), binding(1)]]
+var<uniform> params: Params;
+[[group(0), binding(2)]]
+var<storage, read_write> exposure: Exposure;
+
+var<workgroup> sums: array<f32, 256>;
+
+// A single workgroup looks at a 64x64 grid spread over the frame, 4x4 samples per invocation
+[[stage(compute), workgroup_size(16, 16)]]
+fn main(
+    [[builtin(local_invocation_id)]] id: vec3<u32>,
+    [[builtin(local_invocation_index)]] index: u32,
+) {
+    let size = vec2<f32>(textureDimensions(hdr));
+    var sum = 0.0;
+    for (var y = 0u; y < 4u; y = y + 1u) {
+        for (var x = 0u; x < 4u; x = x + 1u) {
+            let cell = vec2<f32>(id.xy * 4u + vec2<u32>(x, y)) + 0.5;
+            let color = textureLoad(hdr, vec2<i32>(cell / 64.0 * size), 0).rgb;
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            // The log average keeps a few very bright pixels from darkening everything
+            sum = sum + log(max(luminance, 0.0) + 0.0001);
+        }
+    }
+    sums[index] = sum;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride = stride >> 1u) {
+        if (index < stride) {
+            sums[index] = sums[index] + sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let average = exp(sums[0] / 4096.0);
+        // Zero until the first frame was measured, that one is taken as is
+        if (exposure.luminance > 0.0) {
+            exposure.luminance = exposure.luminance + (average - exposure.luminance) * params.adaptation;
+        } else {
+            exposure.luminance = average;
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Bloom and vignette, drawn into the HDR scene before it is tonemapped
+
+struct Params {
+    threshold: f32;
+    bloom_intensity: f32;
+    vignette_intensity: f32;
+    // Distance from the center, with the corners at one, where the darkening starts
+    vignette_radius: f32;
+};
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: Params;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// One triangle covering the whole target
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Keeps what is brighter than the threshold, with a soft knee so it doesn't pop in
+[[stage(fragment)]]
+fn fs_threshold(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Half resolution, the bilinear sample averages the 2x2 pixels underneath
+    let color = textureSample(source, source_sampler, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = params.threshold * 0.5;
+    var soft = clamp(brightness - params.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// Nine tap gaussian in five samples, leaning on bilinear filtering for the ones in between
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let texel = direction / vec2<f32>(textureDimensions(source));
+    var color = textureSample(source, source_sampler, uv).rgb * 0.2270270270;
+    color = color + textureSample(source, source_sampler, uv + texel * 1.3846153846).rgb * 0.3162162162;
+    color = color + textureSample(source, source_sampler, uv - texel * 1.3846153846).rgb * 0.3162162162;
+    color = color + textureSample(source, source_sampler, uv + texel * 3.2307692308).rgb * 0.0702702703;
+    color = color + textureSample(source, source_sampler, uv - texel * 3.2307692308).rgb * 0.0702702703;
+    return vec4<f32>(color, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_blur_x(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0, 0.0));
+}
+
+[[stage(fragment)]]
+fn fs_blur_y(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0));
+}
+
+// Added onto the scene by the pipeline's blending
+[[stage(fragment)]]
+fn fs_composite(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let bloom = textureSample(source, source_sampler, in.uv).rgb;
+    return vec4<f32>(bloom * params.bloom_intensity, 0.0);
+}
+
+// Multiplied into the scene by the pipeline's blending
+[[stage(fragment)]]
+fn fs_vignette(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let distance = length(in.uv - 0.5) * 1.4142135;
+    let darkening = smoothstep(params.vignette_radius, 1.0, distance) * params.vignette_intensity;
+    return vec4<f32>(vec3<f32>(1.0 - darkening), 1.0);
+}
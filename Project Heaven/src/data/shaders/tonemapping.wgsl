@@ -0,0 +1,77 @@
+// Takes the HDR scene to the output, after exposure and the chosen curve
+
+struct Params {
+    operator: u32;
+    // Linear multiplier, the UI works in stops
+    exposure: f32;
+    // One over the UI's gamma, applied on top of the output's own sRGB encoding
+    gamma: f32;
+    auto_exposure: u32;
+    // Average luminance auto exposure aims for
+    key: f32;
+    adaptation: f32;
+};
+
+struct Exposure {
+    luminance: f32;
+};
+
+[[group(0), binding(0)]]
+var hdr: texture_2d<f32>;
+[[group(0), binding(1)]]
+var<uniform> params: Params;
+[[group(0), binding(2)]]
+var<storage, read> exposure: Exposure;
+
+// One triangle covering the whole output
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Narkowicz's fit of the ACES curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let mapped = (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn hable(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// Uncharted 2's filmic curve, white at 11.2
+fn filmic(color: vec3<f32>) -> vec3<f32> {
+    return hable(color * 2.0) / hable(vec3<f32>(11.2));
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    var color = textureLoad(hdr, vec2<i32>(position.xy), 0).rgb * params.exposure;
+    if (params.auto_exposure != 0u) {
+        color = color * params.key / max(exposure.luminance, 0.0001);
+    }
+    color = max(color, vec3<f32>(0.0));
+
+    if (params.operator == 1u) {
+        color = reinhard(color);
+    } else if (params.operator == 2u) {
+        color = aces(color);
+    } else if (params.operator == 3u) {
+        color = filmic(color);
+    } else {
+        color = min(color, vec3<f32>(1.0));
+    }
+
+    return vec4<f32>(pow(color, vec3<f32>(params.gamma)), 1.0);
+}
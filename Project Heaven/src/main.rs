@@ -1,26 +1,77 @@
 mod rend3_impl;
-use rend3_impl::Rendering;
+#[cfg(not(target_arch = "wasm32"))]
+use rend3_impl::HeadlessArgs;
+use rend3_impl::{Cli, Rendering};
 
+use clap::Parser;
 use winit::window::Fullscreen;
 
+// In the browser this is the wasm-bindgen start function. The page has no command line,
+// so everything starts at its defaults there.
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    let cli = Cli::parse();
+    #[cfg(target_arch = "wasm32")]
+    let cli = {
+        console_error_panic_hook::set_once();
+        Cli::parse_from([env!("CARGO_PKG_NAME")])
+    };
+
+    // Renders a single frame to a PNG and exits without ever opening a window
+    #[cfg(not(target_arch = "wasm32"))]
+    if cli.headless {
+        let result = HeadlessArgs::from_cli(&cli).and_then(|args| {
+            rend3_impl::render_headless(&args)?;
+            Ok(args.output)
+        });
+        match result {
+            Ok(output) => println!("Saved {}", output.display()),
+            Err(ex) => {
+                println!("ERROR -> {}", ex);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let image_data_icon = include_bytes!("data/images/icon_round.png");
     let image_icon = image::load_from_memory(image_data_icon).expect("Failed to load image");
     let image_buffer_icon = image_icon.to_rgba8();
     let pixels_icon = image_buffer_icon.into_vec();
 
-    let app = Rendering::default();
-    rend3_framework::start(
-        app,
-        winit::window::WindowBuilder::new()
-            .with_title("Project Heaven")
+    let builder = winit::window::WindowBuilder::new()
+        .with_title("Project Heaven")
+        .with_decorations(false)
+        .with_window_icon(Some(
+            winit::window::Icon::from_rgba(pixels_icon, 256, 256).unwrap(),
+        ));
+    // A size given on the command line starts windowed, otherwise the app covers the monitor
+    let builder = match (cli.width, cli.height) {
+        (None, None) => builder
             .with_maximized(true)
-            .with_fullscreen(Some(Fullscreen::Borderless(None)))
-            .with_decorations(false)
-            .with_window_icon(Some(
-                winit::window::Icon::from_rgba(pixels_icon, 256, 256).unwrap(),
-            )),
-    )
+            .with_fullscreen(Some(Fullscreen::Borderless(None))),
+        (width, height) => builder.with_inner_size(winit::dpi::PhysicalSize::new(
+            width.unwrap_or(1280),
+            height.unwrap_or(720),
+        )),
+    };
+    // Draws into the page's own canvas when it has one, otherwise one is added to the body
+    #[cfg(target_arch = "wasm32")]
+    let builder = {
+        use wasm_bindgen::JsCast;
+        use winit::platform::web::WindowBuilderExtWebSys;
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id("project-heaven"))
+            .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok());
+        builder
+            .with_fullscreen(None)
+            .with_maximized(false)
+            .with_canvas(canvas)
+    };
+
+    let app = Rendering::new(cli);
+    rend3_framework::start(app, builder)
 }
 
 //Ignore this stuff ;) it's from a few months ago when the project only had 2D rendering
@@ -1,28 +1,1184 @@
 use egui::{FontDefinitions, FontFamily};
-use glam::{DVec2, Mat4, Quat, Vec3, Vec3A};
+use glam::{DVec2, Mat4, Quat, Vec2, Vec3, Vec3A};
 use histogram::Histogram;
 use instant::Instant;
-use rend3::util::typedefs::FastHashMap;
 use serde::Deserialize;
 use std::borrow::Cow;
-use std::{collections::HashMap, hash::BuildHasher, sync::Arc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use winit::event::{ElementState, KeyboardInput, MouseButton};
 
 mod physics;
 
+mod asset_loader;
+
+mod audio;
+
+mod bookmarks;
+
+mod cli;
+pub use cli::Cli;
+
 mod controls;
 use controls::ship_cam;
 use controls::space_cam;
 use controls::ShipCam;
 use controls::SpaceCam;
 
-use controls::platform;
+mod debug_draw;
+
+mod error;
+
+mod file_dialog;
+
+mod frame_pacing;
+
+mod frame_stats;
+
+mod gizmo;
+
+mod gltf_animation;
+
+mod gltf_export;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::{render as render_headless, HeadlessArgs};
+
+mod hierarchy;
+
+mod history;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+#[cfg(target_arch = "wasm32")]
+use web as hot_reload;
+
+mod input;
+
+mod lighting;
+
+mod log_console;
+
+mod lod;
+
+mod material_animation;
+
+mod mesh_diagnostics;
+mod mesh_generator;
+
+mod mesh_import;
 
 mod mesh_importer;
-use mesh_importer::load_gltf;
+use mesh_importer::{GltfLoadError, Imported, NormalsMode};
+
+mod particles;
+
+mod picking;
+
+mod post_fx;
+
+mod projection;
+
+mod reference_image;
+
+mod render_mode;
+
+mod scale_bar;
+
+mod scene;
+
+mod scene_state;
+
+mod screenshot;
+
+mod script;
+
+mod settings;
+
+mod skybox;
+
+mod stress_test;
+
+mod terrain;
+
+mod texture;
+
+mod tonemapping;
+
+mod viewport;
+
+#[cfg(target_arch = "wasm32")]
+mod web;
+use scene::SceneObject;
+
+const MAX_ORBIT_DISTANCE: f32 = 10_000.;
+
+// Walking speed range of the free camera, in units per second
+const MIN_FLY_SPEED: f32 = 0.1;
+const MAX_FLY_SPEED: f32 = 100_000.;
+
+#[cfg(not(target_arch = "wasm32"))]
+const PLAYER_MODEL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/3d/Heaven1_2.glb");
+#[cfg(not(target_arch = "wasm32"))]
+const STATION_MODEL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/3d/Stardrifter.glb");
+// Trunk copies the models next to index.html, the URLs are relative to the page
+#[cfg(target_arch = "wasm32")]
+const PLAYER_MODEL: &str = "3d/Heaven1_2.glb";
+#[cfg(target_arch = "wasm32")]
+const STATION_MODEL: &str = "3d/Stardrifter.glb";
+
+// The ship keeps its own material, only what the file brings beside it is swapped in
+fn replace_ship(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    model: mesh_importer::GltfModel,
+) {
+    data.player.mesh = model.mesh;
+    data.player.mesh_data = Some(model.mesh_data);
+    data.player.report = None;
+    data.player.extras = model.extras;
+    if data
+        .gltf_camera
+        .map_or(false, |index| index >= model.cameras.len())
+    {
+        data.gltf_camera = None;
+    }
+    data.player_cameras = model.cameras;
+
+    let clay = data.clay_mode.then(|| &data.clay_material_handle);
+    data.player.refresh(renderer, clay);
+}
+
+// Swaps an opened file in for the station, keeping its transform. `files` are the ones it
+// was read from.
+fn replace_station(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    model: mesh_importer::GltfModel,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+) {
+    data.station.mesh = model.mesh;
+    data.station.material = model.material.clone();
+    data.station.mesh_data = Some(model.mesh_data);
+    data.station.report = None;
+    data.station.extras = model.extras;
+    data._station_material_handle = model.material;
+    data.station_variants = model.variants;
+    data.station_variant = None;
+    data.station.surface = None;
+    data.station_color = model.base_color;
+    data.station_path = path;
+    data.station_files = files;
+    data.station_primitive = None;
+
+    let clay = data.clay_mode.then(|| &data.clay_material_handle);
+    data.station.refresh(renderer, clay);
+}
+
+fn replace_scene(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    scene: mesh_importer::GltfScene,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+) {
+    data.scene_path = Some(path);
+    data.scene_files = files;
+    data.history.clear();
+    // Separate objects for the levels of one would all be drawn on top of each other
+    let (objects, object_nodes) = lod::group_named_levels(scene.objects, scene.object_nodes);
+    data.scene.objects = objects;
+    data.scene.object_nodes = object_nodes;
+    data.scene_animation = (!scene.animations.is_empty() || !scene.nodes.skins.is_empty())
+        .then(|| gltf_animation::AnimationPlayer::new(scene.animations, scene.nodes));
+    for object in &mut data.scene.objects {
+        object.set_wireframe(renderer, data.wireframe);
+    }
+}
+
+// A changed scene file swaps its meshes and materials into the objects from the same node
+// with the same name, which keep their transforms, selection and edits. The history only
+// survives while the objects stay the same ones, or its numbers would point at others.
+fn reload_scene(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    scene: mesh_importer::GltfScene,
+    files: Vec<PathBuf>,
+) {
+    data.scene_files = files;
+    let (reloaded, reloaded_nodes) = lod::group_named_levels(scene.objects, scene.object_nodes);
+    let mut reloaded: Vec<Option<SceneObject>> = reloaded.into_iter().map(Some).collect();
+    let clay = data.clay_mode.then(|| data.clay_material_handle.clone());
+
+    let old_objects = std::mem::take(&mut data.scene.objects);
+    let old_nodes = std::mem::take(&mut data.scene.object_nodes);
+    let old_count = old_objects.len();
+    // Where each old object ended up, None for the ones that aren't in the file any more
+    let mut moved_to = Vec::with_capacity(old_count);
+    for (mut object, node) in old_objects.into_iter().zip(old_nodes) {
+        let found = reloaded
+            .iter()
+            .zip(&reloaded_nodes)
+            .position(|(new, &new_node)| {
+                new_node == node && new.as_ref().map_or(false, |new| new.name == object.name)
+            });
+        moved_to.push(found.map(|_| data.scene.objects.len()));
+        if let Some(index) = found {
+            object.reload_from(renderer, reloaded[index].take().unwrap(), clay.as_ref());
+            data.scene.objects.push(object);
+            data.scene.object_nodes.push(node);
+        }
+    }
+    let same_objects = reloaded.iter().all(Option::is_none) && moved_to.iter().all(Option::is_some);
+    // New ones go after the others, so the old ones keep their places
+    for (object, node) in reloaded.into_iter().zip(reloaded_nodes) {
+        if let Some(mut object) = object {
+            object.set_wireframe(renderer, data.wireframe);
+            data.scene.objects.push(object);
+            data.scene.object_nodes.push(node);
+        }
+    }
+
+    // The station comes first in the scene panel and the spawned objects after the scene
+    let count = data.scene.objects.len();
+    data.selected_object = data.selected_object.and_then(|index| match index {
+        0 => Some(0),
+        index if index <= old_count => moved_to[index - 1].map(|to| to + 1),
+        index => Some(index - old_count + count),
+    });
+    if !same_objects {
+        data.history.clear();
+    }
+    data.scene_animation = (!scene.animations.is_empty() || !scene.nodes.skins.is_empty())
+        .then(|| gltf_animation::AnimationPlayer::new(scene.animations, scene.nodes));
+}
+
+// Uploads the loads the worker threads have finished reading
+fn finish_pending_loads(data: &mut RenderingData, renderer: &rend3::Renderer, now: Instant) {
+    let mut index = 0;
+    while index < data.pending_loads.len() {
+        match data.pending_loads[index].poll() {
+            Some((result, files)) => {
+                let pending = data.pending_loads.remove(index);
+                finish_load(data, renderer, pending, result, files, now);
+            }
+            None => index += 1,
+        }
+    }
+}
+
+fn finish_load(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    mut pending: asset_loader::PendingLoad,
+    result: Result<Imported, GltfLoadError>,
+    files: Vec<PathBuf>,
+    now: Instant,
+) {
+    let restore = std::mem::take(&mut pending.restore);
+    let result = result.and_then(|imported| match pending.target {
+        asset_loader::LoadTarget::Ship if pending.mesh_only => {
+            let mesh =
+                mesh_importer::imported_mesh_data(&pending.path, imported, data.normals_mode)?;
+            let clay = data.clay_mode.then(|| &data.clay_material_handle);
+            data.player.set_mesh_data(renderer, mesh, clay);
+            Ok(())
+        }
+        asset_loader::LoadTarget::Ship => {
+            let model =
+                mesh_importer::finish_gltf(renderer, &pending.path, imported, data.normals_mode)?;
+            replace_ship(data, renderer, model);
+            Ok(())
+        }
+        asset_loader::LoadTarget::Station if pending.mesh_only => {
+            let mesh =
+                mesh_importer::imported_mesh_data(&pending.path, imported, data.normals_mode)?;
+            // A shape picked while it was loading wins
+            if data.station_primitive.is_none() {
+                let clay = data.clay_mode.then(|| &data.clay_material_handle);
+                data.station.set_mesh_data(renderer, mesh, clay);
+            }
+            Ok(())
+        }
+        asset_loader::LoadTarget::Station => {
+            let model =
+                mesh_importer::finish_gltf(renderer, &pending.path, imported, data.normals_mode)?;
+            replace_station(data, renderer, model, pending.path.clone(), files);
+            if restore.primitive.is_some() {
+                set_station_primitive(data, renderer, restore.primitive);
+            }
+            restore_objects(renderer, std::slice::from_mut(&mut data.station), restore);
+            Ok(())
+        }
+        asset_loader::LoadTarget::Scene => {
+            let scene = mesh_importer::finish_gltf_scene(
+                renderer,
+                &pending.path,
+                imported,
+                data.normals_mode,
+                gltf_scene_root(),
+            )?;
+            if pending.reload {
+                reload_scene(data, renderer, scene, files);
+            } else {
+                replace_scene(data, renderer, scene, pending.path.clone(), files);
+            }
+            restore_objects(renderer, &mut data.scene.objects, restore);
+            Ok(())
+        }
+    });
+    match result {
+        Ok(()) => {
+            data.drop_error = None;
+            if pending.frame {
+                frame_scene(data);
+            }
+            let recent = match pending.target {
+                asset_loader::LoadTarget::Ship => None,
+                asset_loader::LoadTarget::Station => Some(&mut data.settings.recent_models),
+                asset_loader::LoadTarget::Scene => Some(&mut data.settings.recent_scenes),
+            };
+            if let Some(recent) = recent.filter(|_| pending.remember) {
+                settings::remember(recent, &pending.path);
+                settings::save(&data.settings);
+            }
+        }
+        Err(ex) if pending.dropped => data.drop_error = Some((ex.to_string(), now)),
+        Err(ex) => data.errors.push(ex),
+    }
+}
 
-fn button_pressed<Hash: BuildHasher>(map: &HashMap<u32, bool, Hash>, key: u32) -> bool {
-    map.get(&key).map_or(false, |b| *b)
+// Same handedness flip the ship and station get
+fn gltf_scene_root() -> Mat4 {
+    Mat4::from_scale(Vec3::new(1., 1., -1.))
+}
+
+// See asset_loader::Restore
+fn restore_objects(
+    renderer: &rend3::Renderer,
+    objects: &mut [SceneObject],
+    restore: asset_loader::Restore,
+) {
+    if restore.transforms.len() == objects.len() {
+        for (object, transform) in objects.iter_mut().zip(restore.transforms) {
+            object.set_transform(renderer, transform);
+        }
+    }
+    if restore.extras.len() == objects.len() {
+        for (object, extras) in objects.iter_mut().zip(restore.extras) {
+            object.extras = extras;
+        }
+    }
+}
+
+// The station keeps its material, only the mesh is swapped. Going back to its own model
+// reads the file again on a worker thread.
+fn set_station_primitive(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    primitive: Option<mesh_generator::Primitive>,
+) {
+    data.station_primitive = primitive;
+    match primitive {
+        Some(kind) => {
+            let clay = data.clay_mode.then(|| &data.clay_material_handle);
+            data.station.set_mesh_data(renderer, kind.mesh_data(), clay);
+        }
+        None => asset_loader::queue(
+            &mut data.pending_loads,
+            asset_loader::PendingLoad::geometry(
+                data.station_path.clone(),
+                asset_loader::LoadTarget::Station,
+            ),
+        ),
+    }
+}
+
+// The bundled models are loaded at startup, a broken one is swapped for a shape instead of
+// stopping the app and the error is kept for the UI
+fn load_or_placeholder(
+    renderer: &rend3::Renderer,
+    path: &Path,
+    normals: NormalsMode,
+    shape: mesh_generator::Primitive,
+    errors: &mut error::ErrorQueue,
+) -> mesh_importer::GltfModel {
+    // The browser build starts with the shape, the model is fetched once the app is up
+    #[cfg(target_arch = "wasm32")]
+    let result = {
+        let _ = (path, normals);
+        Ok(mesh_importer::placeholder_model(renderer, shape))
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let result = mesh_importer::load_gltf(renderer, path, normals);
+    match result {
+        Ok(model) => model,
+        Err(ex) => {
+            errors.push(ex);
+            mesh_importer::placeholder_model(renderer, shape)
+        }
+    }
+}
+
+fn refresh_ship_material(data: &RenderingData, renderer: &rend3::Renderer) {
+    renderer.update_material(
+        &data.player.material,
+        material_animation::ship_material(
+            data.color,
+            data.roughness,
+            data.metallic,
+            &data.surface,
+            data.albedo_texture.as_ref(),
+        ),
+    );
+}
+
+// Objects in the order the scene panel lists them
+fn hierarchy_objects(data: &RenderingData) -> impl Iterator<Item = &SceneObject> {
+    std::iter::once(&data.station)
+        .chain(data.scene.objects.iter())
+        .chain(data.scene.spawned.iter())
+}
+
+fn hierarchy_object(data: &mut RenderingData, index: usize) -> Option<&mut SceneObject> {
+    std::iter::once(&mut data.station)
+        .chain(data.scene.objects.iter_mut())
+        .chain(data.scene.spawned.iter_mut())
+        .nth(index)
+}
+
+// What the material editor edits, the selected object or the ship when there's none. Light
+// meshes keep the material their light's colour is set through.
+fn material_target(data: &RenderingData) -> Option<usize> {
+    data.selected_object.filter(|&index| {
+        hierarchy_objects(data)
+            .nth(index)
+            .map_or(false, |object| object.light.is_none())
+    })
+}
+
+// Bones of the selected object in world space, when it's a skinned part of the glTF scene.
+// They follow the object, wherever the gizmo has put it.
+fn selected_bones(data: &RenderingData) -> Option<Vec<(Vec3, Vec3)>> {
+    // The station comes first in the scene panel
+    let index = data.selected_object?.checked_sub(1)?;
+    let object = data.scene.objects.get(index)?;
+    object.skeleton.as_ref()?;
+    let node = data.scene.object_nodes[index];
+    let player = data.scene_animation.as_ref()?;
+    let skin = player.nodes.skins_of[node]?;
+    let pose = player.current_pose();
+    let to_world = object.transform * pose[node].unwrap_or(Mat4::IDENTITY).inverse();
+    let bones = player.nodes.bones(skin, &pose).into_iter();
+    Some(
+        bones
+            .map(|(from, to)| {
+                (
+                    to_world.transform_point3(from),
+                    to_world.transform_point3(to),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn spawn_primitive(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    kind: mesh_generator::Primitive,
+    name: String,
+    transform: Mat4,
+) {
+    let mesh_data = kind.mesh_data();
+    let mut object = SceneObject::new(
+        renderer,
+        renderer.add_mesh(mesh_data.build()),
+        renderer.add_material(scene::clay_material([0.8, 0.8, 0.8, 1.])),
+        transform,
+    );
+    object.name = name;
+    object.mesh_data = Some(mesh_data);
+    if data.clay_mode {
+        object.refresh(renderer, Some(&data.clay_material_handle));
+    }
+    object.set_wireframe(renderer, data.wireframe);
+    data.scene.spawned.push(object);
+    data.scene.spawned_primitives.push(kind);
+}
+
+// The ship, the station, the glTF scene, spawned primitives and terrain, with the lights.
+// Read straight back so a broken file shows up here, not later.
+fn export_scene(data: &RenderingData, path: &Path) -> Result<(), String> {
+    let ship = scene::PbrFactors {
+        base_color: data.color,
+        metallic: data.metallic,
+        roughness: data.roughness,
+        emissive: data.surface.emissive,
+    };
+    let station = scene::PbrFactors {
+        base_color: data.station_color,
+        ..data.station.factors
+    };
+    let objects: Vec<_> = [
+        (&data.player, "Ship", ship),
+        (&data.station, "Station", station),
+    ]
+    .into_iter()
+    .chain(
+        data.scene
+            .objects
+            .iter()
+            .chain(data.scene.spawned.iter())
+            .chain(
+                data.terrain
+                    .iter()
+                    .flat_map(|terrain| terrain.chunks.iter()),
+            )
+            .map(|object| (object, object.name.as_str(), object.factors)),
+    )
+    .filter_map(|(object, name, factors)| {
+        // The mirror into the app's left handed space is its own inverse
+        Some(gltf_export::ExportObject {
+            name,
+            mesh: object.mesh_data.as_ref()?,
+            transform: gltf_scene_root() * object.transform,
+            factors,
+            extras: &object.extras,
+        })
+    })
+    .collect();
+    let mut lights = vec![data.sun.clone()];
+    lights.extend(data.extra_lights.iter().map(|extra| extra.light.clone()));
+    if let Some(rig) = &data.turntable_rig {
+        lights.extend(rig.lights());
+    }
+    for light in &mut lights {
+        light.direction = gltf_scene_root().transform_vector3(light.direction);
+    }
+    gltf_export::export_gltf(path, &objects, &lights)?;
+    gltf::import(path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn current_scene_state(data: &RenderingData) -> scene_state::SceneState {
+    let light_state = |light: &rend3::types::DirectionalLight| scene_state::LightState {
+        direction: light.direction.to_array(),
+        color: light.color.to_array(),
+        intensity: light.intensity,
+        distance: light.distance,
+    };
+    scene_state::SceneState {
+        color: data.color,
+        roughness: data.roughness,
+        metallic: data.metallic,
+        surface: data.surface.clone(),
+        albedo_texture: data.albedo_path.clone(),
+        sun_direction: data.sun.direction.to_array(),
+        sun_intensity: data.sun.intensity,
+        sun_color: data.sun.color.to_array(),
+        sun_distance: data.sun.distance,
+        extra_lights: data
+            .extra_lights
+            .iter()
+            .map(|extra| light_state(&extra.light))
+            .collect(),
+        orbit_camera: data.orbit_camera,
+        orbit_pitch: data.orbit_pitch,
+        orbit_yaw: data.orbit_yaw,
+        orbit_distance: data.orbit_distance,
+        station_primitive: data.station_primitive,
+        station_model: Some(data.station_path.clone()),
+        station_transform: Some(data.station.transform.to_cols_array()),
+        scene: data.scene_path.clone(),
+        station_extras: data.station.extras.clone(),
+        scene_transforms: data
+            .scene
+            .objects
+            .iter()
+            .map(|object| object.transform.to_cols_array())
+            .collect(),
+        scene_extras: data
+            .scene
+            .objects
+            .iter()
+            .map(|object| object.extras.clone())
+            .collect(),
+        spawned: data
+            .scene
+            .spawned
+            .iter()
+            .zip(&data.scene.spawned_primitives)
+            .map(|(object, &primitive)| scene_state::SpawnedState {
+                name: object.name.clone(),
+                primitive,
+                transform: object.transform.to_cols_array(),
+            })
+            .collect(),
+        terrain: data
+            .terrain
+            .as_ref()
+            .map(|terrain| scene_state::TerrainState {
+                params: terrain.params.clone(),
+                heightmap: terrain.heightmap.clone(),
+            }),
+        free_camera: data.camtype,
+        camera_location: Some(Vec3::from(data.camera_location).to_array()),
+        camera_rotation: Some(data.camera_rotation.to_array()),
+        ship_location: Some(Vec3::from(data.ship_location).to_array()),
+        ship_rotation: Some(data.ship_rotation.to_array()),
+        bookmarks: data.bookmarks.clone(),
+        orthographic: data.orthographic,
+        vfov: data.vfov,
+        near: data.near,
+        ortho_size: data.ortho_size,
+        projection_override: data
+            .projection_override
+            .map(|matrix| matrix.to_cols_array()),
+    }
+}
+
+// A saved rotation that can't be normalized keeps the current one
+fn saved_rotation(rotation: [f32; 4], current: Quat) -> Quat {
+    let rotation = Quat::from_array(rotation);
+    if rotation.is_finite() && rotation.length_squared() > f32::EPSILON {
+        rotation.normalize()
+    } else {
+        current
+    }
+}
+
+// Goes through the same renderer updates the menu uses. Assets are only loaded again
+// when the state points somewhere else than what is loaded now.
+// The camera picks the orbit up on the next frame.
+fn apply_scene_state(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    state: scene_state::SceneState,
+) {
+    data.color = state.color;
+    data.roughness = state.roughness;
+    data.metallic = state.metallic;
+    data.surface = state.surface;
+    if state.albedo_texture != data.albedo_path {
+        data.albedo_texture = None;
+        data.albedo_error = None;
+        if let Some(path) = &state.albedo_texture {
+            match texture::load_texture_2d(renderer, path) {
+                Ok(handle) => data.albedo_texture = Some(handle),
+                Err(ex) => data.albedo_error = Some(ex),
+            }
+        }
+        data.albedo_path = data.albedo_texture.as_ref().and(state.albedo_texture);
+    }
+    refresh_ship_material(data, renderer);
+
+    let direction = Vec3::from(state.sun_direction);
+    if direction.length_squared() > f32::EPSILON {
+        data.sun.direction = direction;
+    }
+    data.sun.intensity = state.sun_intensity;
+    data.sun.color = Vec3::from(state.sun_color);
+    data.sun.distance = state.sun_distance;
+    renderer.update_directional_light(
+        &data.sun_handle,
+        rend3::types::DirectionalLightChange {
+            color: Some(data.sun.color),
+            intensity: Some(data.sun.intensity),
+            direction: Some(data.sun.direction.normalize()),
+            distance: Some(data.sun.distance),
+        },
+    );
+
+    // Dropping the old handles removes those lights
+    data.extra_lights = state
+        .extra_lights
+        .iter()
+        .map(|saved| {
+            let light = rend3::types::DirectionalLight {
+                color: Vec3::from(saved.color),
+                intensity: saved.intensity,
+                direction: Vec3::from(saved.direction).normalize_or_zero(),
+                distance: saved.distance,
+            };
+            lighting::ExtraLight {
+                handle: renderer.add_directional_light(light.clone()),
+                light,
+            }
+        })
+        .collect();
+
+    data.orbit_camera = state.orbit_camera;
+    data.orbit_pitch = state.orbit_pitch;
+    data.orbit_yaw = state.orbit_yaw;
+    data.orbit_distance = state.orbit_distance.clamp(0.5, MAX_ORBIT_DISTANCE);
+    data.orbit_center = None;
+
+    data.camtype = state.free_camera;
+    if let Some(location) = state.camera_location {
+        data.camera_location = Vec3A::from(location);
+    }
+    if let Some(rotation) = state.camera_rotation {
+        data.camera_rotation = saved_rotation(rotation, data.camera_rotation);
+    }
+    if let Some(location) = state.ship_location {
+        data.ship_location = Vec3A::from(location);
+    }
+    if let Some(rotation) = state.ship_rotation {
+        data.ship_rotation = saved_rotation(rotation, data.ship_rotation);
+    }
+    data.velocity_vec = Vec3A::ZERO;
+    data.bookmarks = state.bookmarks;
+    data.fly_to = None;
+    data.history.clear();
+
+    // Clamped to what the camera settings sliders allow
+    data.orthographic = state.orthographic;
+    data.vfov = state.vfov.clamp(10., 120.);
+    data.near = state.near.clamp(0.001, 10.);
+    data.ortho_size = state.ortho_size.clamp(1., 1000.);
+    data.projection_override = state.projection_override.and_then(|columns| {
+        match projection::validate_projection(Mat4::from_cols_array(&columns)) {
+            Ok(matrix) => Some(matrix),
+            Err(ex) => {
+                tracing::error!("Saved projection: {}", ex);
+                None
+            }
+        }
+    });
+    data.projection_error = None;
+    if let Some(matrix) = data.projection_override {
+        data.projection_input = projection::to_rows(matrix);
+    }
+
+    // Regenerated even when nothing changed, the heightmap file may have
+    let clay = data.clay_mode.then(|| &data.clay_material_handle);
+    data.terrain = state.terrain.map(|saved| {
+        let mut terrain = terrain::Terrain::new(renderer, saved.params, clay, data.wireframe);
+        if let Some(path) = saved.heightmap {
+            terrain.error = terrain.load_heightmap(&path).err();
+            if terrain.error.is_none() {
+                terrain.regenerate(renderer, clay, data.wireframe);
+            }
+        }
+        terrain
+    });
+
+    // A project decides what the station and the scene are, loads already on their way would
+    // undo that when they finish
+    data.pending_loads
+        .retain(|pending| pending.target == asset_loader::LoadTarget::Ship);
+
+    // Models are read on a worker thread, what was saved about them is put back once they
+    // are in
+    let station_restore = asset_loader::Restore {
+        transforms: state
+            .station_transform
+            .map(|transform| Mat4::from_cols_array(&transform))
+            .into_iter()
+            .collect(),
+        extras: Some(state.station_extras)
+            .filter(|extras| !extras.is_empty())
+            .into_iter()
+            .collect(),
+        primitive: state.station_primitive,
+    };
+    match state
+        .station_model
+        .filter(|path| *path != data.station_path)
+    {
+        Some(path) => {
+            let mut load =
+                asset_loader::PendingLoad::start(path, asset_loader::LoadTarget::Station, false);
+            load.frame = false;
+            load.remember = false;
+            load.restore = station_restore;
+            data.pending_loads.push(load);
+        }
+        None => {
+            if state.station_primitive != data.station_primitive {
+                set_station_primitive(data, renderer, state.station_primitive);
+            }
+            restore_objects(
+                renderer,
+                std::slice::from_mut(&mut data.station),
+                station_restore,
+            );
+        }
+    }
+
+    let scene_restore = asset_loader::Restore {
+        transforms: state
+            .scene_transforms
+            .iter()
+            .map(Mat4::from_cols_array)
+            .collect(),
+        extras: state.scene_extras,
+        primitive: None,
+    };
+    if state.scene != data.scene_path {
+        data.scene.objects.clear();
+        data.scene.object_nodes.clear();
+        data.scene_animation = None;
+        data.scene_path = None;
+        if let Some(path) = state.scene {
+            let mut load =
+                asset_loader::PendingLoad::start(path, asset_loader::LoadTarget::Scene, false);
+            load.frame = false;
+            load.remember = false;
+            load.restore = scene_restore;
+            data.pending_loads.push(load);
+        }
+    } else {
+        restore_objects(renderer, &mut data.scene.objects, scene_restore);
+    }
+
+    data.scene.spawned.clear();
+    data.scene.spawned_primitives.clear();
+    for spawned in state.spawned {
+        spawn_primitive(
+            data,
+            renderer,
+            spawned.primitive,
+            spawned.name,
+            Mat4::from_cols_array(&spawned.transform),
+        );
+    }
+}
+
+fn current_bookmark(data: &RenderingData, name: String) -> bookmarks::CameraBookmark {
+    bookmarks::CameraBookmark::from_view(
+        name,
+        data.view,
+        data.orthographic,
+        data.vfov,
+        data.near,
+        data.ortho_size,
+    )
+}
+
+// The free camera is put at the bookmark straight away and the flight only shows on the
+// view, so whatever the flight is doing the camera ends up there
+fn fly_to(data: &mut RenderingData, to: bookmarks::CameraBookmark) {
+    let from = current_bookmark(data, String::new());
+    data.camtype = true;
+    data.orbit_camera = false;
+    data.gltf_camera = None;
+    data.camera_location = to.location().into();
+    data.camera_rotation = to.rotation();
+    data.orthographic = to.orthographic;
+    data.near = to.near;
+    data.fly_to = Some(bookmarks::FlyTo::new(
+        &from,
+        to,
+        data.start_time.elapsed().as_secs_f32(),
+        data.settings.fly_duration,
+    ));
+}
+
+// Switches to the orbit camera, centred on everything in the scene and far enough back
+// to fit all of it
+fn frame_scene(data: &mut RenderingData) {
+    let bounds = [&data.player, &data.station]
+        .into_iter()
+        .chain(data.scene.objects.iter())
+        .chain(data.scene.spawned.iter())
+        .filter_map(|object| {
+            let bounds = object.mesh_data.as_ref()?.bounds()?;
+            Some(mesh_generator::transform_bounds(bounds, object.transform))
+        })
+        .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)));
+    let (min, max) = match bounds {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    // A single point or a flat model still needs some room around it
+    let radius = ((max - min).length() / 2.).max(0.5);
+    data.orbit_center = Some(((min + max) / 2.).into());
+    data.orbit_distance =
+        (radius * 1.1 / (data.vfov.to_radians() / 2.).sin()).clamp(0.5, MAX_ORBIT_DISTANCE);
+    data.ortho_size = radius * 2.2;
+    data.orbit_camera = true;
+}
+
+// glTF scene objects and added primitives become bodies that fall onto a floor at the
+// grid's height. The station and the terrain are fixed colliders where they stand, and so
+// is anything too flat to have a hull.
+fn start_physics(data: &mut RenderingData) {
+    let mut simulation = physics::simulation::Simulation::new(true);
+    simulation.add_ground(0.);
+    for object in data
+        .scene
+        .objects
+        .iter_mut()
+        .chain(data.scene.spawned.iter_mut())
+    {
+        object.body = simulation.add_dynamic(object);
+        if object.body.is_none() {
+            simulation.add_static(object);
+        }
+    }
+    for object in std::iter::once(&data.station).chain(
+        data.terrain
+            .iter()
+            .flat_map(|terrain| terrain.chunks.iter()),
+    ) {
+        simulation.add_static(object);
+    }
+    data.physics = Some(simulation);
+}
+
+// Objects keep the transform the simulation left them with
+fn stop_physics(data: &mut RenderingData) {
+    data.physics = None;
+    data.scene.falling_cubes.clear();
+    for object in data
+        .scene
+        .objects
+        .iter_mut()
+        .chain(data.scene.spawned.iter_mut())
+    {
+        object.body = None;
+    }
+}
+
+fn scene_counts(data: &RenderingData) -> frame_stats::SceneCounts {
+    let objects: Vec<&SceneObject> = [&data.player, &data.station]
+        .into_iter()
+        .chain(data.scene.objects.iter())
+        .chain(data.scene.spawned.iter())
+        .chain(
+            data.terrain
+                .iter()
+                .flat_map(|terrain| terrain.chunks.iter()),
+        )
+        .chain(data.scene.falling_cubes.iter())
+        .collect();
+    frame_stats::SceneCounts {
+        objects: objects.len() + data.scene.instances.len(),
+        triangles: objects
+            .iter()
+            .filter_map(|object| object.mesh_data.as_ref())
+            .map(|mesh| mesh.indices.len() / 3)
+            .sum::<usize>()
+            + data.scene.instance_triangles(),
+        lights: 1
+            + data.extra_lights.len()
+            + data
+                .turntable_rig
+                .as_ref()
+                .map_or(0, lighting::TurntableRig::light_count),
+    }
+}
+
+// A lost or outdated surface is reconfigured and tried once more
+fn acquire_frame(
+    renderer: &rend3::Renderer,
+    surface: &Arc<rend3::types::Surface>,
+    format: rend3::types::TextureFormat,
+    resolution: glam::UVec2,
+    present_mode: settings::PresentMode,
+) -> Result<rend3::util::output::OutputFrame, error::Error> {
+    let mut frame = rend3::util::output::OutputFrame::Surface {
+        surface: Arc::clone(surface),
+    };
+    match frame.acquire() {
+        Ok(()) => return Ok(frame),
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {}
+        Err(ex) => return Err(ex.into()),
+    }
+
+    rend3::configure_surface(
+        surface,
+        &renderer.device,
+        format,
+        resolution,
+        present_mode.wgpu(),
+    );
+    let mut frame = rend3::util::output::OutputFrame::Surface {
+        surface: Arc::clone(surface),
+    };
+    frame.acquire()?;
+    Ok(frame)
+}
+
+// Selects the object under the cursor in the scene panel, or nothing when the click
+// misses everything
+fn pick_object(
+    window: &winit::window::Window,
+    data: &mut RenderingData,
+    cursor: winit::dpi::PhysicalPosition<f64>,
+) {
+    let size = window.inner_size();
+    let resolution = Vec2::new(size.width as f32, size.height as f32);
+    let projection = projection::matrix(data.projection, resolution.x / resolution.y);
+    let cursor = Vec2::new(cursor.x as f32, cursor.y as f32);
+    let ray = match picking::cursor_ray(data.view, projection, cursor, resolution) {
+        Some(ray) => ray,
+        None => return,
+    };
+
+    // Same order as the scene panel lists them
+    let objects = std::iter::once(&data.station)
+        .chain(data.scene.objects.iter())
+        .chain(data.scene.spawned.iter());
+    data.selected_object = picking::pick(&ray, objects);
+    if data.selected_object.is_some() {
+        data.show_hierarchy = true;
+    }
+}
+
+// Borderless on the current monitor. winit doesn't always hand the old size back when
+// leaving fullscreen, so it is put back explicitly.
+fn set_fullscreen(window: &winit::window::Window, data: &mut RenderingData, fullscreen: bool) {
+    if fullscreen == data.fullscreen {
+        return;
+    }
+    if fullscreen {
+        data.windowed_size = Some(window.inner_size());
+        window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
+    } else {
+        window.set_fullscreen(None);
+        if let Some(size) = data.windowed_size.take() {
+            window.set_inner_size(size);
+        }
+    }
+    data.fullscreen = fullscreen;
+    if data.settings.graphics.fullscreen != fullscreen {
+        data.settings.graphics.fullscreen = fullscreen;
+        settings::save(&data.settings);
+    }
+}
+
+// egui panics on a file it can't read as a font, so it's parsed the way egui will first
+fn looks_like_font(bytes: &[u8]) -> bool {
+    ab_glyph::FontRef::try_from_slice_and_index(bytes, 0).is_ok()
+}
+
+// The bundled font, or the one from the settings with the bundled one behind it for the
+// glyphs it doesn't have
+fn font_definitions(custom: Option<&Path>) -> FontDefinitions {
+    let bundled = include_bytes!("data/fonts/Section9-4lXp.ttf");
+    let mut font = FontDefinitions::default();
+    font.font_data.insert(
+        "DejaVu Sans Mono".to_string(),
+        egui::FontData {
+            font: Cow::from(&bundled[..]),
+            index: 0,
+        },
+    );
+    let mut fonts = vec!["DejaVu Sans Mono".to_string()];
+
+    if let Some(path) = custom {
+        match std::fs::read(path) {
+            Ok(bytes) if looks_like_font(&bytes) => {
+                font.font_data.insert(
+                    "Custom".to_string(),
+                    egui::FontData {
+                        font: Cow::from(bytes),
+                        index: 0,
+                    },
+                );
+                fonts.insert(0, "Custom".to_string());
+            }
+            Ok(_) => tracing::error!("{}: not a TrueType or OpenType font", path.display()),
+            Err(ex) => tracing::error!("{}: {}", path.display(), ex),
+        }
+    }
+
+    font.fonts_for_family
+        .insert(FontFamily::Monospace, fonts.clone());
+    font.fonts_for_family
+        .insert(FontFamily::Proportional, fonts);
+    font
+}
+
+// egui lays out at the window's scale factor times the UI scale from the settings. The
+// platform only takes a scale factor from winit, so it's handed the event it would get if
+// the window moved to a monitor with that scale.
+fn apply_ui_scale(
+    window: &winit::window::Window,
+    data: &mut RenderingData,
+    size: winit::dpi::PhysicalSize<u32>,
+    window_scale: f64,
+) {
+    let scale_factor = window_scale * data.settings.ui_scale as f64;
+    let mut new_inner_size = size;
+    data.platform
+        .handle_event(&rend3_framework::Event::<()>::WindowEvent {
+            window_id: window.id(),
+            event: winit::event::WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size: &mut new_inner_size,
+            },
+        });
+    data.egui_routine
+        .resize(size.width, size.height, scale_factor as f32);
+}
+
+// Everything in config.toml back to its default except the recent files. The surface is
+// configured again by the caller for the present mode.
+fn reset_settings(window: &winit::window::Window, data: &mut RenderingData, ctx: &egui::CtxRef) {
+    set_fullscreen(window, data, false);
+    data.settings = settings::Settings {
+        recent_models: std::mem::take(&mut data.settings.recent_models),
+        recent_scenes: std::mem::take(&mut data.settings.recent_scenes),
+        ..settings::Settings::default()
+    };
+    data.sample_count = data.settings.graphics.msaa.sample_count();
+    data.walk_speed = data.settings.camera_speed;
+    data.run_speed = data.walk_speed * 2.;
+    ctx.set_visuals(data.settings.theme.visuals(&data.heaven_visuals));
+    ctx.set_fonts(font_definitions(None));
+    apply_ui_scale(window, data, window.inner_size(), window.scale_factor());
+    settings::save(&data.settings);
+}
+
+// Saved on exit, fullscreen covers the whole monitor and says nothing about the window
+fn remember_window(window: &winit::window::Window, data: &mut RenderingData) {
+    data.settings.camera_speed = data.walk_speed;
+    if window.fullscreen().is_none() {
+        if let Ok(position) = window.outer_position() {
+            let size = window.inner_size();
+            data.settings.window = Some(settings::WindowGeometry {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            });
+        }
+    }
+}
+
+// Actions that happen once per press. The held ones are looked up by the camera controls.
+fn run_action(
+    window: &winit::window::Window,
+    data: &mut RenderingData,
+    grabber: &mut rend3_framework::Grabber,
+    menu_toggle: &mut bool,
+    action: input::Action,
+    typing: bool,
+) {
+    match action {
+        input::Action::ToggleCamera => data.camtype = !data.camtype,
+        input::Action::SwitchFixture => data.camera_fixture = (1 + data.camera_fixture) % 2,
+        input::Action::ReleaseCursor => grabber.request_ungrab(window),
+        input::Action::ToggleGrab if !data.orbit_camera && !typing => {
+            if grabber.grabbed() {
+                grabber.request_ungrab(window);
+            } else {
+                grabber.request_grab(window);
+            }
+        }
+        input::Action::ToggleMenu if !typing => *menu_toggle = !*menu_toggle,
+        input::Action::ToggleFullscreen => set_fullscreen(window, data, !data.fullscreen),
+        input::Action::TogglePerformance => data.show_performance = !data.show_performance,
+        input::Action::CycleRenderMode => data.render_mode = data.render_mode.next(),
+        _ => {}
+    }
 }
 
 #[derive(Deserialize)]
@@ -34,19 +1190,97 @@ struct StarData {
 }
 
 struct RenderingData {
-    _station_handle: rend3::types::ObjectHandle,
+    station: SceneObject,
     _station_material_handle: rend3::types::MaterialHandle,
+    station_variants: Vec<(String, rend3::types::MaterialHandle)>,
+    station_variant: Option<usize>,
+    station_color: [f32; 4],
+    // Where the station was loaded from, so it can be reimported
+    station_path: PathBuf,
+    // That file and the ones it pulls in, for hot reload to watch
+    station_files: Vec<PathBuf>,
+    // Set while a generated shape stands in for the station's model
+    station_primitive: Option<mesh_generator::Primitive>,
+    errors: error::ErrorQueue,
+    // A file is being dragged over the window
+    file_hovered: bool,
+    // Shown for a few seconds after a dropped file couldn't be opened
+    drop_error: Option<(String, Instant)>,
+    // Starting another load for the same target drops the one before, its result is thrown
+    // away
+    pending_loads: Vec<asset_loader::PendingLoad>,
+    // None while reloading changed files is off, or when the platform can't watch files
+    asset_watcher: Option<hot_reload::AssetWatcher>,
+    // Everything but the ship and the station
+    scene: scene::Scene,
+    // Where the scene objects were loaded from
+    scene_path: Option<PathBuf>,
+    scene_files: Vec<PathBuf>,
+    terrain: Option<terrain::Terrain>,
+    stress_test: stress_test::StressTest,
+    // Built from the scene when physics is turned on, dropped when it is turned off
+    physics: Option<physics::simulation::Simulation>,
+    // Only there when the scene came with animations or skins
+    scene_animation: Option<gltf_animation::AnimationPlayer>,
 
     _object_handle: std::vec::Vec<rend3::types::ObjectHandle>,
-    _player_handle: rend3::types::ObjectHandle,
+    player: SceneObject,
+    player_cameras: Vec<mesh_importer::GltfCamera>,
+    gltf_camera: Option<usize>,
+    normals_mode: NormalsMode,
     _material_handle: std::vec::Vec<rend3::types::MaterialHandle>,
-    player_material_handle: rend3::types::MaterialHandle,
-    _directional_handle: rend3::types::DirectionalLightHandle,
+    sun: rend3::types::DirectionalLight,
+    sun_handle: rend3::types::DirectionalLightHandle,
+    extra_lights: Vec<lighting::ExtraLight>,
+    show_lighting: bool,
+    // Built on the first frame, it needs the base rendergraph's interfaces
+    tonemapper: Option<tonemapping::Tonemapper>,
+    tonemapping: tonemapping::TonemappingSettings,
+    post_fx: post_fx::PostFx,
+    post_fx_settings: post_fx::PostFxSettings,
+    show_post_processing: bool,
+
+    surface_format: rend3::types::TextureFormat,
+    // MSAA for the scene. egui draws straight to the surface and always uses one sample.
+    sample_count: rend3::types::SampleCount,
+    skybox: Option<rend3::types::TextureHandle>,
+    skybox_enabled: bool,
+    skybox_error: Option<String>,
+    // Set while the skybox comes from a panorama, so the exposure can be rebaked
+    skybox_panorama: Option<skybox::Equirectangular>,
+    // Stops
+    skybox_exposure: f32,
+    viewport: viewport::ViewportWidget,
+    turntable_rig: Option<lighting::TurntableRig>,
+    export_status: Option<String>,
+    scene_state_status: Option<String>,
+    // Taken on the next frame, then cleared
+    screenshot_requested: bool,
+    screenshot_include_ui: bool,
+    screenshot_status: Option<String>,
 
     egui_routine: rend3_egui::EguiRenderRoutine,
     platform: egui_winit_platform::Platform,
     start_time: instant::Instant,
     color: [f32; 4],
+    roughness: f32,
+    metallic: f32,
+    albedo_texture: Option<rend3::types::TextureHandle>,
+    // Where the albedo texture was loaded from, for saving the scene
+    albedo_path: Option<PathBuf>,
+    albedo_error: Option<String>,
+    ship_animation: material_animation::MaterialAnimation,
+    surface: material_animation::SurfaceParams,
+
+    clay_mode: bool,
+    wireframe: bool,
+    // Per object modes in the hierarchy override this one
+    render_mode: render_mode::RenderMode,
+    debug_materials: render_mode::DebugMaterials,
+    // Where the camera was when the depth colours were last made
+    render_mode_camera: Vec3,
+    clay_color: [f32; 4],
+    clay_material_handle: rend3::types::MaterialHandle,
 
     walk_speed: f32,
     run_speed: f32,
@@ -87,12 +1321,74 @@ struct RenderingData {
     timestamp_last_frame: Instant,
     timestamp_last_second: Instant,
     frame_times: Histogram,
+    // Rolling window for the on-screen overlay, the histogram above is reset every second
+    frame_stats: frame_stats::FrameStats,
+    show_performance: bool,
+    // Scopes are only recorded while the window is open
+    show_profiler: bool,
+    show_hierarchy: bool,
+    gizmo: gizmo::Gizmo,
+    // Index into the objects the scene panel lists
+    selected_object: Option<usize>,
+    // Wait for events instead of redrawing as fast as possible
+    redraw_on_demand: bool,
+    pacing: frame_pacing::FramePacing,
+    fullscreen: bool,
+    // Size to go back to when leaving fullscreen
+    windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
+    debug_draw: debug_draw::DebugDraw,
+    particles: particles::ParticleSystem,
+    audio: audio::Audio,
+    history: history::History,
+    show_history: bool,
+    script_console: script::ScriptConsole,
+    log_console: log_console::LogConsole,
 
     camtype: bool,
 
     camera_fixture: u32,
 
     view: Mat4,
+    bookmarks: Vec<bookmarks::CameraBookmark>,
+    bookmark_name: String,
+    fly_to: Option<bookmarks::FlyTo>,
+    // Last projection handed to the renderer
+    projection: rend3::types::CameraProjection,
+    orthographic: bool,
+    vfov: f32,
+    near: f32,
+    // Height of the view in world units, the width follows the aspect ratio
+    ortho_size: f32,
+
+    projection_input: [f32; 16],
+    projection_override: Option<Mat4>,
+    projection_error: Option<String>,
+
+    idle_orbit: bool,
+    idle_delay: f32,
+    idle_orbit_speed: f32,
+    // Camera offset from the ship and clock time when the current idle orbit started
+    idle_orbit_start: Option<(Vec3A, f32)>,
+
+    // Mouse driven orbit around the ship, left drag turns and the wheel zooms
+    orbit_camera: bool,
+    orbit_pitch: f32,
+    orbit_yaw: f32,
+    orbit_distance: f32,
+    // Set by framing the scene, otherwise the orbit follows the ship
+    orbit_center: Option<Vec3A>,
+    orbit_dragging: bool,
+    // Where the left button went down, a release close by counts as a click that picks
+    pick_start: Option<winit::dpi::PhysicalPosition<f64>>,
+    cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+
+    settings: settings::Settings,
+    // The app's own egui style, for switching back to it from another theme
+    heaven_visuals: egui::Visuals,
+
+    reference_path: String,
+    reference_image: Option<reference_image::ReferenceImage>,
+    reference_error: Option<String>,
 }
 
 const SAMPLE_COUNT: rend3::types::SampleCount = rend3::types::SampleCount::Four;
@@ -103,29 +1399,85 @@ pub struct Rendering {
     project_heaven_logo: egui::TextureId,
 
     grabber: Option<rend3_framework::Grabber>,
-    scancode_status: FastHashMap<u32, bool>,
+    input: input::Input,
+    last_input: Option<Instant>,
+    validation: bool,
+    cli: Cli,
+    log: log_console::LogBuffer,
 
     data: Option<RenderingData>,
 }
 
+impl Rendering {
+    pub fn new(cli: Cli) -> Self {
+        Self {
+            cli,
+            ..Self::default()
+        }
+    }
+}
+
 impl rend3_framework::App for Rendering {
     const HANDEDNESS: rend3::types::Handedness = rend3::types::Handedness::Left;
 
+    // Runs before the instance and device are created, so this is also where the
+    // validation switches have to be flipped
+    fn register_logger(&mut self) {
+        self.validation = std::env::var_os("HEAVEN_VALIDATION").is_some() || self.cli.validation;
+
+        let filter = if self.validation {
+            // Asks the Vulkan loader for the Khronos validation layer, other backends
+            // only get the more verbose logging
+            std::env::set_var("VK_INSTANCE_LAYERS", "VK_LAYER_KHRONOS_validation");
+            "info,wgpu_core=debug,wgpu_hal=debug"
+        } else {
+            "warn"
+        };
+        self.log = log_console::init(filter);
+    }
+
+    // --backend picks the graphics API, wgpu chooses otherwise
+    fn create_iad<'a>(
+        &'a mut self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = anyhow::Result<rend3::InstanceAdapterDevice>> + 'a>,
+    > {
+        let backend = self.cli.backend.map(cli::Backend::wgpu);
+        Box::pin(async move { Ok(rend3::create_iad(backend, None, None, None).await?) })
+    }
+
     fn sample_count(&self) -> rend3::types::SampleCount {
-        SAMPLE_COUNT
+        self.data.as_ref().map_or_else(
+            || {
+                self.cli
+                    .msaa
+                    .map_or(SAMPLE_COUNT, settings::Msaa::sample_count)
+            },
+            |data| data.sample_count,
+        )
     }
 
     fn setup(
         &mut self,
         window: &winit::window::Window,
         renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+        routines: &Arc<rend3_framework::DefaultRoutines>,
         surface_format: rend3::types::TextureFormat,
     ) {
+        puffin::profile_function!();
         self.grabber = Some(rend3_framework::Grabber::new(window));
+        self.input = input::Input::load();
+        let settings = settings::load();
 
         let window_size = window.inner_size();
 
+        // wgpu panics on validation errors by default, log them and keep going instead
+        if self.validation {
+            renderer.device.on_uncaptured_error(|error| {
+                tracing::error!(target: "wgpu", "validation: {}", error);
+            });
+        }
+
         // Create the egui render routine
         let mut egui_routine = rend3_egui::EguiRenderRoutine::new(
             renderer,
@@ -133,31 +1485,47 @@ impl rend3_framework::App for Rendering {
             rend3::types::SampleCount::One,
             window_size.width,
             window_size.height,
-            window.scale_factor() as f32,
+            window.scale_factor() as f32 * settings.ui_scale,
         );
         // Create mesh and calculate smooth normals based on vertices.
         //
         // We do not need to keep these handles alive once we make the object
-        let (sphere_mesh, _material) = load_gltf(
+        let normals_mode = NormalsMode::RecomputeIfMissing;
+        let mut errors = error::ErrorQueue::default();
+        let sphere_mesh = load_or_placeholder(
             renderer,
-            concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/3d/Sphere_low.glb"),
-        );
-
-        let (player_mesh, _material) = load_gltf(
+            Path::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/data/3d/Sphere_low.glb"
+            )),
+            normals_mode,
+            mesh_generator::Primitive::Sphere,
+            &mut errors,
+        )
+        .mesh;
+
+        let player_model = load_or_placeholder(
             renderer,
-            concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/3d/Heaven1_2.glb"),
+            Path::new(PLAYER_MODEL),
+            normals_mode,
+            mesh_generator::Primitive::Cube,
+            &mut errors,
         );
 
-        let (station_mesh, _station_material_handle) = load_gltf(
+        let station_model = load_or_placeholder(
             renderer,
-            concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/3d/Stardrifter.glb"),
+            Path::new(STATION_MODEL),
+            normals_mode,
+            mesh_generator::Primitive::Torus,
+            &mut errors,
         );
+        let _station_material_handle = station_model.material.clone();
 
         let mut star_data: std::vec::Vec<StarData> = vec![];
         match spv_rs::input_data::parse_csv("src/data/stars/edr3_10gmag.csv", true, b',', b'\n') {
             Ok(vec) => star_data = vec,
             Err(ex) => {
-                println!("ERROR -> {}", ex);
+                tracing::error!("{}", ex);
             }
         };
 
@@ -172,30 +1540,41 @@ impl rend3_framework::App for Rendering {
 
         let mut material_vec = Vec::new();
 
-        let player = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(player_mesh),
-            material: player_material_handle.clone(),
-            transform: glam::Mat4::from_scale_rotation_translation(
-                glam::Vec3::new(1., 1., -1.),
-                rend3::types::glam::Quat::IDENTITY,
-                glam::Vec3::new(0.0, 0.0, 0.0),
-            ),
-        };
+        let player_transform = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::new(1., 1., -1.),
+            rend3::types::glam::Quat::IDENTITY,
+            glam::Vec3::new(0.0, 0.0, 0.0),
+        );
 
-        let station = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(station_mesh),
-            material: _station_material_handle.clone(),
-            transform: glam::Mat4::from_scale_rotation_translation(
-                glam::Vec3::new(2., 2., -2.),
-                rend3::types::glam::Quat::IDENTITY,
-                glam::Vec3::new(0.0, 0.0, 0.0),
-            ),
-        };
+        let station_transform = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::new(2., 2., -2.),
+            rend3::types::glam::Quat::IDENTITY,
+            glam::Vec3::new(0.0, 0.0, 0.0),
+        );
 
         let mut object_vec = Vec::new();
-        let _player_handle = renderer.add_object(player);
+        let mut player = SceneObject::new(
+            renderer,
+            player_model.mesh,
+            player_material_handle,
+            player_transform,
+        );
+        player.name = "Ship".to_string();
+        player.mesh_data = Some(player_model.mesh_data);
+        player.extras = player_model.extras;
+
+        let mut station = SceneObject::new(
+            renderer,
+            station_model.mesh,
+            _station_material_handle.clone(),
+            station_transform,
+        );
+        station.name = "Station".to_string();
+        station.mesh_data = Some(station_model.mesh_data);
+        station.extras = station_model.extras;
 
-        let _station_handle = renderer.add_object(station);
+        let clay_color: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+        let clay_material_handle = renderer.add_material(scene::clay_material(clay_color));
 
         for i in star_data {
             if i.gmag < 7. {
@@ -231,13 +1610,8 @@ impl rend3_framework::App for Rendering {
         // Create a single directional light
         //
         // We need to keep the directional light handle alive.
-        let _directional_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::new(0.95, 0.9, 0.6),
-            intensity: 5.,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 4000.0,
-        });
+        let sun = lighting::sun();
+        let sun_handle = renderer.add_directional_light(sun.clone());
 
         let mut style: egui::Style = Default::default();
 
@@ -327,30 +1701,17 @@ impl rend3_framework::App for Rendering {
 
         style.visuals.widgets.open.corner_radius = 0.1;
 
-        let font_dejavusansmono = include_bytes!("data/fonts/Section9-4lXp.ttf");
-        let mut font = FontDefinitions::default();
+        let heaven_visuals = style.visuals.clone();
+        style.visuals = settings.theme.visuals(&heaven_visuals);
 
-        font.font_data.insert(
-            "DejaVu Sans Mono".to_string(),
-            egui::FontData {
-                font: Cow::from(&font_dejavusansmono[..]),
-                index: 0,
-            },
-        );
-        font.fonts_for_family
-            .insert(FontFamily::Monospace, vec!["DejaVu Sans Mono".to_string()]);
-
-        font.fonts_for_family.insert(
-            FontFamily::Proportional,
-            vec!["DejaVu Sans Mono".to_string()],
-        );
+        let font = font_definitions(settings.font.as_deref());
 
         // Create the winit/egui integration, which manages our egui context for us.
         let platform =
             egui_winit_platform::Platform::new(egui_winit_platform::PlatformDescriptor {
                 physical_width: window_size.width as u32,
                 physical_height: window_size.height as u32,
-                scale_factor: window.scale_factor(),
+                scale_factor: window.scale_factor() * settings.ui_scale as f64,
                 font_definitions: font,
                 style,
             });
@@ -374,25 +1735,106 @@ impl rend3_framework::App for Rendering {
             Some("project_heaven_logo"),
         );
 
+        // The skybox is optional, nothing ships in the default folder
+        let skybox = if Path::new(skybox::DEFAULT_SKYBOX).exists() {
+            match skybox::load_cubemap(renderer, Path::new(skybox::DEFAULT_SKYBOX)) {
+                Ok(texture) => Some(texture),
+                Err(ex) => {
+                    tracing::error!("{}", ex);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        rend3_framework::lock(&routines.skybox).set_background_texture(skybox.clone());
+
         let start_time = instant::Instant::now();
         let color: [f32; 4] = [0.0, 0.5, 0.5, 1.0];
 
+        self.last_input = Some(Instant::now());
+
         self.data = Some(RenderingData {
-            _station_handle,
+            station,
             _station_material_handle,
+            station_variants: station_model.variants,
+            station_color: station_model.base_color,
+            station_path: PathBuf::from(STATION_MODEL),
+            // The bundled model has everything in the one file
+            station_files: vec![PathBuf::from(STATION_MODEL)],
+            station_primitive: None,
+            errors,
+            file_hovered: false,
+            drop_error: None,
+            pending_loads: Vec::new(),
+            asset_watcher: hot_reload::AssetWatcher::new()
+                .map_err(|ex| tracing::error!("{}", ex))
+                .ok(),
+            scene: scene::Scene::default(),
+            scene_path: None,
+            scene_files: Vec::new(),
+            terrain: None,
+            stress_test: stress_test::StressTest::default(),
+            physics: None,
+            scene_animation: None,
+            station_variant: None,
             _object_handle: object_vec,
-            _player_handle,
+            player,
+            player_cameras: player_model.cameras,
+            gltf_camera: None,
+            normals_mode,
             _material_handle: material_vec,
-            player_material_handle,
-            _directional_handle,
+            sun,
+            sun_handle,
+            extra_lights: Vec::new(),
+            show_lighting: false,
+            tonemapper: None,
+            tonemapping: tonemapping::TonemappingSettings::default(),
+            post_fx: post_fx::PostFx::new(renderer),
+            post_fx_settings: post_fx::PostFxSettings::default(),
+            show_post_processing: false,
+
+            surface_format,
+            sample_count: self
+                .cli
+                .msaa
+                .unwrap_or(settings.graphics.msaa)
+                .sample_count(),
+            skybox_enabled: skybox.is_some(),
+            skybox,
+            skybox_error: None,
+            skybox_panorama: None,
+            skybox_exposure: 0.,
+            viewport: viewport::ViewportWidget::default(),
+            turntable_rig: None,
+            export_status: None,
+            scene_state_status: None,
+            screenshot_requested: false,
+            screenshot_include_ui: false,
+            screenshot_status: None,
 
             egui_routine,
             platform,
             start_time,
             color,
-
-            walk_speed: 10., /*000000000000*/
-            run_speed: 20.,  /*000000000000*/
+            roughness: 1.,
+            metallic: 0.,
+            albedo_texture: None,
+            albedo_path: None,
+            albedo_error: None,
+            ship_animation: material_animation::MaterialAnimation::default(),
+            surface: material_animation::SurfaceParams::default(),
+
+            clay_mode: false,
+            wireframe: false,
+            render_mode: render_mode::RenderMode::Shaded,
+            debug_materials: render_mode::DebugMaterials::new(renderer),
+            render_mode_camera: Vec3::ZERO,
+            clay_color,
+            clay_material_handle,
+
+            walk_speed: settings.camera_speed,
+            run_speed: settings.camera_speed * 2.,
 
             camera_pitch: 0.,
             camera_yaw: 0.,
@@ -430,12 +1872,113 @@ impl rend3_framework::App for Rendering {
             timestamp_last_frame: Instant::now(),
             timestamp_last_second: Instant::now(),
             frame_times: Histogram::new(),
+            frame_stats: frame_stats::FrameStats::default(),
+            show_performance: false,
+            show_profiler: false,
+            show_hierarchy: false,
+            gizmo: gizmo::Gizmo::default(),
+            selected_object: None,
+            redraw_on_demand: false,
+            pacing: frame_pacing::FramePacing::default(),
+            fullscreen: false,
+            windowed_size: None,
+            debug_draw: debug_draw::DebugDraw::new(renderer),
+            particles: particles::ParticleSystem::new(renderer),
+            audio: audio::Audio::default(),
+            history: history::History::default(),
+            show_history: false,
+            script_console: script::ScriptConsole::default(),
+            log_console: log_console::LogConsole::new(self.log.clone()),
 
             camtype: false,
             camera_fixture: 0,
 
             view: Mat4::IDENTITY,
-        })
+            bookmarks: Vec::new(),
+            bookmark_name: String::new(),
+            fly_to: None,
+            projection: rend3::types::CameraProjection::Perspective {
+                vfov: 60.0,
+                near: 0.1,
+            },
+            orthographic: false,
+            vfov: 60.,
+            near: 0.1,
+            ortho_size: 50.,
+
+            projection_input: projection::to_rows(projection::perspective(
+                60.0,
+                window_size.width as f32 / window_size.height as f32,
+                0.1,
+            )),
+            projection_override: None,
+            projection_error: None,
+
+            idle_orbit: false,
+            idle_delay: 60.,
+            idle_orbit_speed: 0.1,
+            idle_orbit_start: None,
+
+            orbit_camera: false,
+            orbit_pitch: 0.3,
+            orbit_yaw: 0.,
+            orbit_distance: 10.,
+            orbit_center: None,
+            orbit_dragging: false,
+            pick_start: None,
+            cursor_position: None,
+
+            settings,
+            heaven_visuals,
+
+            reference_path: String::new(),
+            reference_image: None,
+            reference_error: None,
+        });
+
+        let data = self.data.as_mut().unwrap();
+        // A size on the command line already made the window what it should be
+        let sized = self.cli.width.is_some() || self.cli.height.is_some();
+        if !sized {
+            if data.settings.graphics.fullscreen {
+                set_fullscreen(window, data, true);
+            } else if let Some(geometry) = data.settings.window {
+                window.set_fullscreen(None);
+                window.set_inner_size(winit::dpi::PhysicalSize::new(
+                    geometry.width,
+                    geometry.height,
+                ));
+                window
+                    .set_outer_position(winit::dpi::PhysicalPosition::new(geometry.x, geometry.y));
+            }
+        }
+
+        if let Some(pose) = self.cli.camera {
+            data.camtype = true;
+            data.orbit_camera = false;
+            data.camera_location = pose.location.into();
+            data.camera_rotation = pose.orientation().inverse();
+        }
+        #[cfg(target_arch = "wasm32")]
+        for (path, target) in [
+            (PLAYER_MODEL, asset_loader::LoadTarget::Ship),
+            (STATION_MODEL, asset_loader::LoadTarget::Station),
+        ] {
+            let mut load = asset_loader::PendingLoad::start(PathBuf::from(path), target, false);
+            load.frame = false;
+            load.remember = false;
+            asset_loader::queue(&mut data.pending_loads, load);
+        }
+        if let Some(path) = self.cli.gltf.clone() {
+            let mut load =
+                asset_loader::PendingLoad::start(path, asset_loader::LoadTarget::Station, false);
+            // Framing would move the camera away from where it was asked to start
+            load.frame = self.cli.camera.is_none();
+            asset_loader::queue(&mut data.pending_loads, load);
+        }
+        if let Some(path) = data.settings.startup_script.clone() {
+            script::run_file(data, renderer, &path);
+        }
     }
 
     fn handle_event(
@@ -449,21 +1992,45 @@ impl rend3_framework::App for Rendering {
         event: rend3_framework::Event<'_, ()>,
         control_flow: impl FnOnce(winit::event_loop::ControlFlow),
     ) {
-        let data = self.data.as_mut().unwrap();
+        puffin::profile_function!();
+        // Nothing to handle until setup has run
+        let data = match self.data.as_mut() {
+            Some(data) => data,
+            None => return,
+        };
 
         // Pass the winit events to the platform integration.
         data.platform.handle_event(&event);
+        let was_idle = data.pacing.idle(&data.settings.graphics);
+        data.pacing.event(&event);
+
+        // Anything happening to the window may change the UI, so it gets a frame. Waking up
+        // from idle restarts the frames the last one didn't ask for.
+        let idle = data.pacing.idle(&data.settings.graphics);
+        let on_demand = data.redraw_on_demand || idle;
+        if (on_demand && matches!(event, rend3_framework::Event::WindowEvent { .. }))
+            || (was_idle && !idle)
+        {
+            window.request_redraw();
+        }
+        let control_flow_mode = if on_demand {
+            winit::event_loop::ControlFlow::Wait
+        } else {
+            winit::event_loop::ControlFlow::Poll
+        };
 
         let now = Instant::now();
         let delta_time = now - data.timestamp_last_frame;
-        data.frame_times
-            .increment(delta_time.as_micros() as u64)
-            .unwrap();
+        // A frame longer than the histogram's range is only left out of the stats
+        if let Err(ex) = data.frame_times.increment(delta_time.as_micros() as u64) {
+            tracing::warn!("frame time {:?}: {}", delta_time, ex);
+        }
+        data.frame_stats.push(delta_time);
 
         let elapsed_since_second = now - data.timestamp_last_second;
         if elapsed_since_second > std::time::Duration::from_secs(1) {
             let count = data.frame_times.entries();
-            println!(
+            tracing::debug!(
                 "{:0>5} frames over {:0>5.2}s. \
                         Min: {:0>5.2}ms; \
                         Average: {:0>5.2}ms; \
@@ -486,14 +2053,6 @@ impl rend3_framework::App for Rendering {
 
         data.timestamp_last_frame = now;
 
-        if button_pressed(&self.scancode_status, platform::Scancodes::PERIOD) {
-            data.camtype = !data.camtype;
-        }
-
-        if button_pressed(&self.scancode_status, platform::Scancodes::CTRL) {
-            data.camera_fixture = (1 + data.camera_fixture) % 2;
-        }
-
         if data.camtype == true {
             let cam_data = space_cam(
                 SpaceCam {
@@ -509,7 +2068,7 @@ impl rend3_framework::App for Rendering {
                     delta_time,
                     camera_location: data.camera_location,
                 },
-                &self.scancode_status,
+                &self.input,
             );
 
             data.camera_rotation = cam_data.0;
@@ -523,6 +2082,7 @@ impl rend3_framework::App for Rendering {
                 ShipCam {
                     camera_yaw: data.camera_yaw_keyboard,
                     camera_pitch: data.camera_pitch_keyboard,
+                    look_speed: data.settings.sensitivity.keyboard_look,
 
                     ship_yaw: data.ship_yaw,
                     ship_pitch: data.ship_pitch,
@@ -545,7 +2105,7 @@ impl rend3_framework::App for Rendering {
                     ship_location: data.ship_location,
                     ship_rotation: data.ship_rotation,
                 },
-                &self.scancode_status,
+                &self.input,
             );
 
             data.acceleration = cam_data.0;
@@ -581,36 +2141,368 @@ impl rend3_framework::App for Rendering {
                     + Quat::mul_vec3a(data.ship_rotation, Vec3A::new(0., 25., -10.));
             }
 
-            rend3::Renderer::set_object_transform(
-                renderer,
-                &data._player_handle,
-                glam::Mat4::from_scale_rotation_translation(
-                    glam::Vec3::new(1., 1., -1.),
-                    data.ship_rotation,
-                    Vec3::from(data.ship_location),
-                ),
+            let player_transform = glam::Mat4::from_scale_rotation_translation(
+                glam::Vec3::new(1., 1., -1.),
+                data.ship_rotation,
+                Vec3::from(data.ship_location),
             );
 
+            data.player.set_transform(renderer, player_transform);
+
             data.camera_pitch = 0.;
             data.camera_yaw = 0.;
         }
 
-        if button_pressed(&self.scancode_status, platform::Scancodes::ESCAPE) {
-            self.grabber.as_mut().unwrap().request_ungrab(window);
+        if let Some(physics) = &mut data.physics {
+            let live = data
+                .scene
+                .objects
+                .iter()
+                .chain(data.scene.spawned.iter())
+                .chain(data.scene.falling_cubes.iter())
+                .filter_map(|object| object.body)
+                .collect();
+            physics.retain_bodies(&live);
+            if physics.update(delta_time.as_secs_f32()) {
+                for object in data
+                    .scene
+                    .objects
+                    .iter_mut()
+                    .chain(data.scene.spawned.iter_mut())
+                    .chain(data.scene.falling_cubes.iter_mut())
+                {
+                    let (scale, _, _) = object.transform.to_scale_rotation_translation();
+                    if let Some(transform) =
+                        object.body.and_then(|body| physics.transform(body, scale))
+                    {
+                        object.set_transform(renderer, transform);
+                    }
+                }
+            }
         }
 
         match event {
             rend3_framework::Event::RedrawRequested(..) => {
+                // Every redraw is one frame in the profiler
+                puffin::GlobalProfiler::lock().new_frame();
+
+                // Minimized, there is nothing to draw to. Resizing back requests a redraw.
+                if resolution.x == 0 || resolution.y == 0 {
+                    control_flow(control_flow_mode);
+                    return;
+                }
+
+                if let Some(watcher) = &mut data.asset_watcher {
+                    let station = data
+                        .station_primitive
+                        .is_none()
+                        .then(|| (data.station_path.as_path(), data.station_files.as_slice()));
+                    let scene = data
+                        .scene_path
+                        .as_deref()
+                        .map(|path| (path, data.scene_files.as_slice()));
+                    watcher.set_assets(&[
+                        (asset_loader::LoadTarget::Station, station),
+                        (asset_loader::LoadTarget::Scene, scene),
+                    ]);
+                    // Waits for whatever is loading already, the change stays queued until then
+                    if data.pending_loads.is_empty() {
+                        if let Some((target, path)) = watcher.next_change() {
+                            data.pending_loads
+                                .push(asset_loader::PendingLoad::reload(path, target));
+                        }
+                    }
+                }
+                finish_pending_loads(data, renderer, now);
+                // Nothing wakes the loop when the worker finishes, so keep drawing until then
+                if !data.pending_loads.is_empty() {
+                    window.request_redraw();
+                }
+
                 data.platform
                     .update_time(data.start_time.elapsed().as_secs_f64());
                 data.platform.begin_frame();
 
                 // Insert egui commands here
                 let ctx = data.platform.context();
+                let snapshot = history::snapshot(data);
+                let (undo, redo) = {
+                    let input = ctx.input();
+                    let shortcuts = !ctx.wants_keyboard_input() && input.modifiers.command;
+                    (
+                        shortcuts && input.key_pressed(egui::Key::Z) && !input.modifiers.shift,
+                        shortcuts
+                            && (input.key_pressed(egui::Key::Y)
+                                || input.key_pressed(egui::Key::Z) && input.modifiers.shift),
+                    )
+                };
+                if undo {
+                    history::undo(data, renderer);
+                }
+                if redo {
+                    history::redo(data, renderer);
+                }
+                if let Some(image) = &data.reference_image {
+                    reference_image::draw(&ctx, image);
+                }
+                egui::Window::new("Profiler")
+                    .open(&mut data.show_profiler)
+                    .default_size([600., 400.])
+                    .show(&ctx, |ui| puffin_egui::profiler_ui(ui));
+                puffin::set_scopes_on(data.show_profiler);
+                if data.show_performance {
+                    let counts = scene_counts(data);
+                    frame_stats::performance_window(
+                        &ctx,
+                        renderer,
+                        &data.frame_stats,
+                        &counts,
+                        &mut data.show_performance,
+                    );
+                }
+                lighting::lighting_window(
+                    &ctx,
+                    renderer,
+                    &data.sun_handle,
+                    &mut data.sun,
+                    &mut data.extra_lights,
+                    &mut data.tonemapping.exposure,
+                    &mut data.show_lighting,
+                );
+                history::history_window(&ctx, data, renderer);
+                script::console_window(&ctx, data, renderer);
+                log_console::console_window(&ctx, &mut data.log_console);
+                let camera = data.view.inverse();
+                particles::particles_window(
+                    &ctx,
+                    &mut data.particles,
+                    camera.w_axis.truncate() + camera.z_axis.truncate().normalize() * 10.,
+                );
+                // In the scene panel's order, borrowed field by field so the audio can be
+                // borrowed next to it
+                let selected = data.selected_object.and_then(|index| {
+                    std::iter::once(&data.station)
+                        .chain(data.scene.objects.iter())
+                        .chain(data.scene.spawned.iter())
+                        .nth(index)
+                });
+                audio::audio_window(
+                    &ctx,
+                    &mut data.audio,
+                    &data.player,
+                    selected,
+                    &mut data.errors,
+                );
+                error::error_modal(&ctx, &mut data.errors);
+                tonemapping::post_processing_window(
+                    &ctx,
+                    &mut data.tonemapping,
+                    &mut data.post_fx_settings,
+                    &mut data.show_post_processing,
+                );
+                if data.show_hierarchy {
+                    // The ship is left out, the flight controls set its transform every frame
+                    let mut objects: Vec<&mut SceneObject> = std::iter::once(&mut data.station)
+                        .chain(data.scene.objects.iter_mut())
+                        .chain(data.scene.spawned.iter_mut())
+                        .collect();
+                    let first_deletable = 1 + data.scene.objects.len();
+                    let deleted = hierarchy::hierarchy_ui(
+                        &ctx,
+                        renderer,
+                        &mut objects,
+                        &mut data.selected_object,
+                        &mut data.gizmo.mode,
+                        first_deletable,
+                    );
+                    // Handles wouldn't line up with the scene inside the viewport window
+                    if let (Some(index), false, None) =
+                        (data.selected_object, data.viewport.enabled, deleted)
+                    {
+                        gizmo::gizmo_ui(
+                            &ctx,
+                            renderer,
+                            &mut data.gizmo,
+                            objects[index],
+                            data.view,
+                            data.projection,
+                        );
+                    }
+                    if let Some(index) = deleted {
+                        let spawned = history::SpawnedPrimitive::of(data, index - first_deletable);
+                        history::run(data, renderer, Box::new(history::Delete(spawned)));
+                    }
+                    if let (Some(bones), false) = (selected_bones(data), data.viewport.enabled) {
+                        gltf_animation::bones_overlay(&ctx, &bones, data.view, data.projection);
+                    }
+                    if let (Some(object), false) = (
+                        data.selected_object
+                            .and_then(|index| hierarchy_objects(data).nth(index)),
+                        data.viewport.enabled,
+                    ) {
+                        lod::lod_overlay(&ctx, object, data.view, data.projection);
+                    }
+                }
+                if data.file_hovered {
+                    egui::Area::new("drop_hint")
+                        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+                        .interactable(false)
+                        .show(&ctx, |ui| {
+                            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                ui.heading("Drop a .gltf or .glb file to replace the station");
+                            });
+                        });
+                }
+                if let Some((error, shown)) = &data.drop_error {
+                    if shown.elapsed().as_secs_f32() < 4. {
+                        egui::Area::new("drop_error")
+                            .anchor(egui::Align2::CENTER_TOP, [0., 40.])
+                            .interactable(false)
+                            .show(&ctx, |ui| {
+                                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                    ui.colored_label(egui::Color32::from_rgb(255, 0, 0), error);
+                                });
+                            });
+                    } else {
+                        data.drop_error = None;
+                    }
+                }
+                if let Some(drag) = viewport::viewport_ui(&ctx, &mut data.viewport) {
+                    let sensitivity = data.settings.sensitivity;
+                    let pitch_sign = if sensitivity.invert_y { -1. } else { 1. };
+                    let drag = drag * ctx.pixels_per_point();
+                    data.camera_yaw -= drag.x * sensitivity.mouse_look;
+                    data.camera_pitch -= pitch_sign * drag.y * sensitivity.mouse_look;
+                }
+
+                // Measured at the ship, with last frame's camera
+                let camera_position = data.view.inverse().w_axis.truncate();
+                let ship_distance = camera_position.distance(data.ship_location.into());
+                if let Some(points_per_meter) = scale_bar::points_per_meter(
+                    data.projection,
+                    ship_distance,
+                    ctx.input().screen_rect().height(),
+                ) {
+                    scale_bar::draw(&ctx, data.settings.unit, points_per_meter);
+                }
                 egui::TopBottomPanel::top("Taskbar").show(&ctx, |ui| {
                     if ui.add(egui::Button::new("Menu")).clicked() {
                         self.menu_toggle = !self.menu_toggle;
                     }
+                    ui.menu_button("File", |ui| {
+                        if ui.button("Open project…").clicked() {
+                            ui.close_menu();
+                            if let Some(path) = file_dialog::open("Project", &["json"])
+                            {
+                                data.scene_state_status = Some(match scene_state::load(&path) {
+                                    Ok(state) => {
+                                        apply_scene_state(data, renderer, state);
+                                        format!("Loaded {}", path.display())
+                                    }
+                                    Err(ex) => ex,
+                                });
+                            }
+                        }
+                        if ui.button("Save project…").clicked() {
+                            ui.close_menu();
+                            if let Some(path) =
+                                file_dialog::save("Project", &["json"], "project.json")
+                            {
+                                data.scene_state_status = Some(
+                                    match scene_state::save(&path, &current_scene_state(data)) {
+                                        Ok(()) => format!("Saved {}", path.display()),
+                                        Err(ex) => ex,
+                                    },
+                                );
+                            }
+                        }
+                        if ui.button("Export…").clicked() {
+                            ui.close_menu();
+                            if let Some(path) =
+                                file_dialog::save("glTF", &["glb", "gltf"], "scene.glb")
+                            {
+                                data.export_status = Some(match export_scene(data, &path) {
+                                    Ok(()) => format!("Saved {}", path.display()),
+                                    Err(e) => e,
+                                });
+                            }
+                        }
+                        let recent = [
+                            (
+                                "Recent models",
+                                asset_loader::LoadTarget::Station,
+                                data.settings.recent_models.clone(),
+                            ),
+                            (
+                                "Recent scenes",
+                                asset_loader::LoadTarget::Scene,
+                                data.settings.recent_scenes.clone(),
+                            ),
+                        ];
+                        for (label, target, paths) in recent {
+                            if paths.is_empty() {
+                                continue;
+                            }
+                            ui.menu_button(label, |ui| {
+                                for path in paths {
+                                    if ui.button(path.display().to_string()).clicked() {
+                                        ui.close_menu();
+                                        asset_loader::queue(
+                                            &mut data.pending_loads,
+                                            asset_loader::PendingLoad::start(path, target, false),
+                                        );
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    ui.menu_button("View", |ui| {
+                        ui.checkbox(&mut data.debug_draw.grid, "Grid");
+                        ui.checkbox(&mut data.debug_draw.axes, "World axes");
+                        if data.debug_draw.grid || data.debug_draw.axes {
+                            let radius = debug_draw::GRID_RADIUS as f32;
+                            ui.add(
+                                egui::Slider::new(&mut data.debug_draw.fade_start, 0.0..=radius)
+                                    .text("Fade from (cells)"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut data.debug_draw.fade_end, 0.0..=radius)
+                                    .text("Faded out at (cells)"),
+                            );
+                        }
+                        ui.checkbox(&mut data.show_history, "History");
+                        ui.checkbox(&mut data.script_console.open, "Script console");
+                        ui.checkbox(&mut data.log_console.open, "Log");
+                        ui.checkbox(&mut data.particles.open, "Particles");
+                        ui.checkbox(&mut data.audio.open, "Audio");
+                        ui.separator();
+                        ui.checkbox(&mut data.debug_draw.bounding_boxes, "Bounding boxes");
+                        ui.checkbox(&mut data.debug_draw.shadow_frustums, "Shadow frustums");
+                        ui.checkbox(&mut data.debug_draw.debug_camera, "Debug camera")
+                            .on_hover_text("Leaves the camera frustum in place to fly around it");
+                    });
+                    if let Some(image) = &mut data.reference_image {
+                        ui.checkbox(&mut image.visible, "Reference");
+                    }
+                    ui.checkbox(&mut data.show_hierarchy, "Scene");
+                    egui::ComboBox::from_id_source("render_mode")
+                        .selected_text(data.render_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in render_mode::RenderMode::ALL {
+                                ui.selectable_value(&mut data.render_mode, mode, mode.name());
+                            }
+                        });
+                    for pending in &data.pending_loads {
+                        let name = pending
+                            .path
+                            .file_name()
+                            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                        ui.label(format!(
+                            "Loading {}… {:.1}s",
+                            name,
+                            pending.started.elapsed().as_secs_f32()
+                        ));
+                    }
                     if self.menu_toggle == true {
                         egui::Window::new("Change color")
                             .resizable(false)
@@ -619,32 +2511,870 @@ impl rend3_framework::App for Rendering {
                                 if ui.add(egui::Button::new("Camera fixture")).clicked() {
                                     data.camera_fixture = !data.camera_fixture;
                                 }
-                                if ui.add(egui::Button::new("exit")).clicked() {
+                                // A page can't close itself
+                                if !cfg!(target_arch = "wasm32")
+                                    && ui.add(egui::Button::new("exit")).clicked()
+                                {
                                     std::process::exit(1);
                                 }
-                                ui.label("Change the color of the cube");
+                                ui.label(if self.validation {
+                                    "Validation: on"
+                                } else {
+                                    "Validation: off (HEAVEN_VALIDATION or --validation)"
+                                });
+                                if let Some(index) = material_target(data) {
+                                    let clay =
+                                        data.clay_mode.then(|| data.clay_material_handle.clone());
+                                    let object = hierarchy_object(data, index).unwrap();
+                                    ui.label(format!("Material of {}", object.name));
+                                    let mut factors = object.factors;
+                                    let mut surface = object.surface();
+                                    if material_animation::material_ui(
+                                        ui,
+                                        &mut factors.base_color,
+                                        &mut factors.roughness,
+                                        &mut factors.metallic,
+                                        &mut surface,
+                                    ) {
+                                        object.factors = factors;
+                                        object.set_material(renderer, surface, clay.as_ref());
+                                    }
+                                } else {
+                                    ui.label("Change the color of the ship");
+                                    let material_changed = material_animation::material_ui(
+                                        ui,
+                                        &mut data.color,
+                                        &mut data.roughness,
+                                        &mut data.metallic,
+                                        &mut data.surface,
+                                    );
+                                    let mut texture_changed = false;
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Load albedo texture…").clicked() {
+                                            if let Some(path) = file_dialog::open(
+                                                "Image",
+                                                &["png", "jpg", "jpeg"],
+                                            ) {
+                                                match texture::load_texture_2d(renderer, &path) {
+                                                    Ok(handle) => {
+                                                        data.albedo_texture = Some(handle);
+                                                        data.albedo_path = Some(path);
+                                                        data.albedo_error = None;
+                                                        texture_changed = true;
+                                                    }
+                                                    Err(ex) => data.albedo_error = Some(ex),
+                                                }
+                                            }
+                                        }
+                                        if data.albedo_texture.is_some()
+                                            && ui.button("Clear texture").clicked()
+                                        {
+                                            data.albedo_texture = None;
+                                            data.albedo_path = None;
+                                            data.albedo_error = None;
+                                            texture_changed = true;
+                                        }
+                                    });
+                                    // Without UVs every pixel samples the same texel
+                                    if texture_changed && data.albedo_texture.is_some() {
+                                        let has_uvs =
+                                            data.player.mesh_data.as_ref().map_or(false, |mesh| {
+                                                match mesh.uv_set {
+                                                    1 => !mesh.uvs1.is_empty(),
+                                                    _ => !mesh.uvs.is_empty(),
+                                                }
+                                            });
+                                        if !has_uvs {
+                                            data.albedo_error = Some(
+                                                "The ship mesh has no UVs to map the texture with"
+                                                    .to_string(),
+                                            );
+                                        }
+                                    }
+                                    if let Some(error) = &data.albedo_error {
+                                        ui.colored_label(egui::Color32::from_rgb(255, 0, 0), error);
+                                    }
+                                    if material_changed || texture_changed {
+                                        refresh_ship_material(data, renderer);
+                                    }
+                                    if material_animation::animation_ui(
+                                        ui,
+                                        "Ship material animation",
+                                        &mut data.ship_animation,
+                                    ) {
+                                        refresh_ship_material(data, renderer);
+                                    }
+                                }
+                                if !data.station_variants.is_empty() {
+                                    let previous_variant = data.station_variant;
+                                    egui::ComboBox::from_label("Station variant")
+                                        .selected_text(match data.station_variant {
+                                            Some(index) => data.station_variants[index].0.as_str(),
+                                            None => "Default",
+                                        })
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(
+                                                &mut data.station_variant,
+                                                None,
+                                                "Default",
+                                            );
+                                            for (index, (name, _)) in
+                                                data.station_variants.iter().enumerate()
+                                            {
+                                                ui.selectable_value(
+                                                    &mut data.station_variant,
+                                                    Some(index),
+                                                    name.as_str(),
+                                                );
+                                            }
+                                        });
+                                    if data.station_variant != previous_variant {
+                                        let material = match data.station_variant {
+                                            Some(index) => data.station_variants[index].1.clone(),
+                                            None => data._station_material_handle.clone(),
+                                        };
+                                        // Reusing the mesh handle means only the object is rebuilt
+                                        data.station.material = material;
+                                        data.station.surface = None;
+                                        data.station.refresh(
+                                            renderer,
+                                            data.clay_mode.then(|| &data.clay_material_handle),
+                                        );
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.checkbox(&mut data.wireframe, "Wireframe").changed() {
+                                        for object in [&mut data.player, &mut data.station]
+                                            .into_iter()
+                                            .chain(data.scene.objects.iter_mut())
+                                            .chain(data.scene.spawned.iter_mut())
+                                            .chain(
+                                                data.terrain
+                                                    .iter_mut()
+                                                    .flat_map(|terrain| terrain.chunks.iter_mut()),
+                                            )
+                                        {
+                                            object.set_wireframe(
+                                                renderer,
+                                                data.wireframe
+                                                    || render_mode::shows_edges(object),
+                                            );
+                                        }
+                                    }
+                                    if ui.checkbox(&mut data.clay_mode, "Clay render").changed() {
+                                        let clay =
+                                            data.clay_mode.then(|| &data.clay_material_handle);
+                                        data.player.refresh(renderer, clay);
+                                        data.station.refresh(renderer, clay);
+                                        for object in data
+                                            .scene
+                                            .objects
+                                            .iter_mut()
+                                            .chain(data.scene.spawned.iter_mut())
+                                            .chain(
+                                                data.terrain
+                                                    .iter_mut()
+                                                    .flat_map(|terrain| terrain.chunks.iter_mut()),
+                                            )
+                                        {
+                                            object.refresh(renderer, clay);
+                                        }
+                                    }
+                                    if ui
+                                        .color_edit_button_rgba_unmultiplied(&mut data.clay_color)
+                                        .changed()
+                                    {
+                                        renderer.update_material(
+                                            &data.clay_material_handle,
+                                            scene::clay_material(data.clay_color),
+                                        );
+                                    }
+                                });
+                                let clay = data.clay_mode.then(|| &data.clay_material_handle);
+                                scene::light_mesh_ui(
+                                    ui,
+                                    renderer,
+                                    "Ship light mesh",
+                                    &mut data.player,
+                                    clay,
+                                );
+                                scene::light_mesh_ui(
+                                    ui,
+                                    renderer,
+                                    "Station light mesh",
+                                    &mut data.station,
+                                    clay,
+                                );
+                                let previous_normals_mode = data.normals_mode;
+                                egui::ComboBox::from_label("Normals")
+                                    .selected_text(data.normals_mode.name())
+                                    .show_ui(ui, |ui| {
+                                        for mode in NormalsMode::ALL {
+                                            ui.selectable_value(
+                                                &mut data.normals_mode,
+                                                mode,
+                                                mode.name(),
+                                            );
+                                        }
+                                    });
+                                if data.normals_mode != previous_normals_mode {
+                                    // Reimporting drops any fixes made from the diagnostics
+                                    // panel. A shape standing in for the station has nothing
+                                    // to reimport.
+                                    let mut targets = vec![(
+                                        PathBuf::from(PLAYER_MODEL),
+                                        asset_loader::LoadTarget::Ship,
+                                    )];
+                                    if data.station_primitive.is_none() {
+                                        targets.push((
+                                            data.station_path.clone(),
+                                            asset_loader::LoadTarget::Station,
+                                        ));
+                                    }
+                                    for (path, target) in targets {
+                                        asset_loader::queue(
+                                            &mut data.pending_loads,
+                                            asset_loader::PendingLoad::geometry(path, target),
+                                        );
+                                    }
+                                }
+                                let previous_primitive = data.station_primitive;
+                                egui::ComboBox::from_label("Station mesh")
+                                    .selected_text(
+                                        data.station_primitive.map_or("Model", |kind| kind.name()),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut data.station_primitive,
+                                            None,
+                                            "Model",
+                                        );
+                                        for kind in mesh_generator::Primitive::ALL {
+                                            ui.selectable_value(
+                                                &mut data.station_primitive,
+                                                Some(kind),
+                                                kind.name(),
+                                            );
+                                        }
+                                    });
+                                let primitive = data.station_primitive;
+                                if primitive != previous_primitive {
+                                    set_station_primitive(data, renderer, primitive);
+                                }
+                                let clay = data.clay_mode.then(|| &data.clay_material_handle);
+                                scene::uv_set_ui(
+                                    ui,
+                                    renderer,
+                                    "Ship UV set",
+                                    &mut data.player,
+                                    clay,
+                                );
+                                scene::uv_set_ui(
+                                    ui,
+                                    renderer,
+                                    "Station UV set",
+                                    &mut data.station,
+                                    clay,
+                                );
+                                ui.collapsing("Mesh diagnostics", |ui| {
+                                    mesh_diagnostics::diagnostics_ui(
+                                        ui,
+                                        renderer,
+                                        "Ship",
+                                        &mut data.player,
+                                        clay,
+                                    );
+                                    ui.separator();
+                                    mesh_diagnostics::diagnostics_ui(
+                                        ui,
+                                        renderer,
+                                        "Station",
+                                        &mut data.station,
+                                        clay,
+                                    );
+                                });
+                                if !data.player_cameras.is_empty() {
+                                    egui::ComboBox::from_label("View")
+                                        .selected_text(match data.gltf_camera {
+                                            Some(index) => data.player_cameras[index].name.as_str(),
+                                            None => "Free camera",
+                                        })
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(
+                                                &mut data.gltf_camera,
+                                                None,
+                                                "Free camera",
+                                            );
+                                            for (index, camera) in
+                                                data.player_cameras.iter().enumerate()
+                                            {
+                                                ui.selectable_value(
+                                                    &mut data.gltf_camera,
+                                                    Some(index),
+                                                    camera.name.as_str(),
+                                                );
+                                            }
+                                        });
+                                }
+                                // With redraw on demand a change is only picked up by the next
+                                // frame something else asks for
+                                let mut watching = data.asset_watcher.is_some();
+                                if ui.checkbox(&mut watching, "Reload on change").changed() {
+                                    data.asset_watcher = watching
+                                        .then(hot_reload::AssetWatcher::new)
+                                        .and_then(|watcher| {
+                                            watcher.map_err(|ex| tracing::error!("{}", ex)).ok()
+                                        });
+                                }
+                                if ui.button("Open model…").clicked() {
+                                    if let Some(path) = file_dialog::open(
+                                        "Model",
+                                        &["gltf", "glb", "obj", "stl"],
+                                    ) {
+                                        // On failure the current station stays as it is
+                                        asset_loader::queue(
+                                            &mut data.pending_loads,
+                                            asset_loader::PendingLoad::start(
+                                                path,
+                                                asset_loader::LoadTarget::Station,
+                                                false,
+                                            ),
+                                        );
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.button("Open scene…").clicked() {
+                                        if let Some(path) = file_dialog::open(
+                                            "Model",
+                                            &["gltf", "glb", "obj", "stl"],
+                                        ) {
+                                            asset_loader::queue(
+                                                &mut data.pending_loads,
+                                                asset_loader::PendingLoad::start(
+                                                    path,
+                                                    asset_loader::LoadTarget::Scene,
+                                                    false,
+                                                ),
+                                            );
+                                        }
+                                    }
+                                    if !data.scene.objects.is_empty() {
+                                        ui.label(format!("{} objects", data.scene.objects.len()));
+                                        if ui.button("Clear").clicked() {
+                                            data.history.clear();
+                                            data.scene.objects.clear();
+                                            data.scene.object_nodes.clear();
+                                            data.scene_animation = None;
+                                            data.scene_path = None;
+                                        }
+                                    }
+                                });
+                                if let Some(player) = data
+                                    .scene_animation
+                                    .as_mut()
+                                    .filter(|player| !player.animations.is_empty())
+                                {
+                                    gltf_animation::animation_ui(ui, player);
+                                }
+                                ui.horizontal(|ui| {
+                                    let mut spawned = None;
+                                    egui::ComboBox::from_label("Add primitive")
+                                        .selected_text("…")
+                                        .show_ui(ui, |ui| {
+                                            for kind in mesh_generator::Primitive::ALL {
+                                                if ui.selectable_label(false, kind.name()).clicked() {
+                                                    spawned = Some(kind);
+                                                }
+                                            }
+                                        });
+                                    // At the origin, the scene panel moves it from there
+                                    if let Some(kind) = spawned {
+                                        let name = format!(
+                                            "{} {}",
+                                            kind.name(),
+                                            data.scene.spawned.len() + 1
+                                        );
+                                        spawn_primitive(data, renderer, kind, name, Mat4::IDENTITY);
+                                        let spawned = history::SpawnedPrimitive::of(
+                                            data,
+                                            data.scene.spawned.len() - 1,
+                                        );
+                                        data.history.push(Box::new(history::Spawn(spawned)));
+                                    }
+                                    if !data.scene.spawned.is_empty()
+                                        && ui.button("Remove primitives").clicked()
+                                    {
+                                        data.history.clear();
+                                        data.scene.spawned.clear();
+                                        data.scene.spawned_primitives.clear();
+                                    }
+                                });
+                                ui.collapsing("Physics", |ui| {
+                                    let mut enabled = data.physics.is_some();
+                                    if ui.checkbox(&mut enabled, "Simulate").changed() {
+                                        if enabled {
+                                            start_physics(data);
+                                        } else {
+                                            stop_physics(data);
+                                        }
+                                    }
+                                    if let Some(physics) = &mut data.physics {
+                                        ui.checkbox(&mut physics.gravity, "Gravity");
+                                        if ui.button("Spawn falling cube").clicked() {
+                                            // Ten units in front of the camera
+                                            let camera = data.view.inverse();
+                                            let position = camera.w_axis.truncate()
+                                                + camera.z_axis.truncate().normalize() * 10.;
+                                            let transform = Mat4::from_scale_rotation_translation(
+                                                Vec3::splat(0.5),
+                                                Quat::IDENTITY,
+                                                position,
+                                            );
+                                            let mesh_data =
+                                                mesh_generator::Primitive::Cube.mesh_data();
+                                            let mut cube = SceneObject::new(
+                                                renderer,
+                                                renderer.add_mesh(mesh_data.build()),
+                                                renderer.add_material(scene::clay_material([
+                                                    0.9, 0.5, 0.2, 1.,
+                                                ])),
+                                                transform,
+                                            );
+                                            cube.name = format!(
+                                                "Falling cube {}",
+                                                data.scene.falling_cubes.len() + 1
+                                            );
+                                            cube.mesh_data = Some(mesh_data);
+                                            cube.body = Some(physics.add_cube(transform));
+                                            data.scene.falling_cubes.push(cube);
+                                        }
+                                    }
+                                });
+                                if ui.button("Save scene as glTF…").clicked() {
+                                    if let Some(path) =
+                                        file_dialog::save("glTF", &["glb", "gltf"], "scene.glb")
+                                    {
+                                        data.export_status =
+                                            Some(match export_scene(data, &path) {
+                                                Ok(()) => format!("Saved {}", path.display()),
+                                                Err(e) => e,
+                                            });
+                                    }
+                                }
+                                if let Some(status) = &data.export_status {
+                                    ui.label(status);
+                                }
+                                ui.horizontal(|ui| {
+                                    let path = Path::new(scene_state::SCENE_STATE_PATH);
+                                    if ui.button("Save scene state").clicked() {
+                                        data.scene_state_status = Some(
+                                            match scene_state::save(
+                                                path,
+                                                &current_scene_state(data),
+                                            ) {
+                                                Ok(()) => format!("Saved {}", path.display()),
+                                                Err(ex) => ex,
+                                            },
+                                        );
+                                    }
+                                    if ui.button("Load scene state").clicked() {
+                                        data.scene_state_status =
+                                            Some(match scene_state::load(path) {
+                                                Ok(state) => {
+                                                    apply_scene_state(data, renderer, state);
+                                                    format!("Loaded {}", path.display())
+                                                }
+                                                Err(ex) => ex,
+                                            });
+                                    }
+                                });
+                                if let Some(status) = &data.scene_state_status {
+                                    ui.label(status);
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.button("Screenshot").clicked() {
+                                        data.screenshot_requested = true;
+                                    }
+                                    // In window mode the scene is part of the UI
+                                    ui.add_enabled(
+                                        !data.viewport.enabled,
+                                        egui::Checkbox::new(
+                                            &mut data.screenshot_include_ui,
+                                            "Include UI",
+                                        ),
+                                    );
+                                });
+                                if let Some(status) = &data.screenshot_status {
+                                    ui.label(status);
+                                }
+                                ui.collapsing("glTF extras", |ui| {
+                                    scene::extras_ui(ui, "Ship", &data.player);
+                                    ui.separator();
+                                    scene::extras_ui(ui, "Station", &data.station);
+                                    // The station is first in the scene panel, and shown already
+                                    let selected = data
+                                        .selected_object
+                                        .filter(|&index| index > 0)
+                                        .and_then(|index| hierarchy_objects(data).nth(index));
+                                    if let Some(object) = selected {
+                                        ui.separator();
+                                        scene::extras_ui(ui, &object.name, object);
+                                    }
+                                });
+                                ui.checkbox(&mut data.show_lighting, "Lighting window");
+                                ui.checkbox(&mut data.show_post_processing, "Post processing");
+                                lighting::turntable_ui(ui, renderer, &mut data.turntable_rig);
+                                ui.collapsing("Reference image", |ui| {
+                                    reference_image::reference_ui(
+                                        ui,
+                                        &mut data.egui_routine,
+                                        renderer,
+                                        &mut data.reference_path,
+                                        &mut data.reference_image,
+                                        &mut data.reference_error,
+                                    );
+                                });
+                                let previous_unit = data.settings.unit;
+                                egui::ComboBox::from_label("Units")
+                                    .selected_text(data.settings.unit.name())
+                                    .show_ui(ui, |ui| {
+                                        for unit in scale_bar::Unit::ALL {
+                                            ui.selectable_value(
+                                                &mut data.settings.unit,
+                                                unit,
+                                                unit.name(),
+                                            );
+                                        }
+                                    });
+                                if data.settings.unit != previous_unit {
+                                    settings::save(&data.settings);
+                                }
+                                ui.label(format!(
+                                    "Distance to ship: {}",
+                                    scale_bar::format_length(ship_distance, data.settings.unit)
+                                ));
+                                ui.checkbox(&mut data.viewport.enabled, "Render in a window");
+                                ui.checkbox(
+                                    &mut data.show_performance,
+                                    format!(
+                                        "Performance overlay ({})",
+                                        self.input.label(input::Action::TogglePerformance)
+                                    ),
+                                );
+                                ui.checkbox(&mut data.show_profiler, "Profiler");
+                                ui.checkbox(&mut data.redraw_on_demand, "Only redraw on changes");
+                                let mut fullscreen = data.fullscreen;
+                                let label = format!(
+                                    "Fullscreen ({})",
+                                    self.input.label(input::Action::ToggleFullscreen)
+                                );
+                                if ui.checkbox(&mut fullscreen, label).changed() {
+                                    set_fullscreen(window, data, fullscreen);
+                                }
+                                ui.collapsing("Graphics", |ui| {
+                                    let msaa = data.settings.graphics.msaa;
+                                    if settings::graphics_ui(ui, &mut data.settings) {
+                                        if let Some(surface) = surface {
+                                            rend3::configure_surface(
+                                                surface,
+                                                &renderer.device,
+                                                data.surface_format,
+                                                resolution,
+                                                data.settings.graphics.present_mode.wgpu(),
+                                            );
+                                        }
+                                    }
+                                    if data.settings.graphics.msaa != msaa {
+                                        data.sample_count = data.settings.graphics.msaa.sample_count();
+                                    }
+                                });
+                                ui.collapsing("Audio", |ui| {
+                                    settings::audio_ui(ui, &mut data.settings);
+                                });
+                                ui.collapsing("Settings", |ui| {
+                                    if settings::theme_ui(ui, &mut data.settings) {
+                                        ui.ctx().set_visuals(
+                                            data.settings.theme.visuals(&data.heaven_visuals),
+                                        );
+                                    }
+                                    if settings::ui_scale_ui(ui, &mut data.settings) {
+                                        apply_ui_scale(
+                                            window,
+                                            data,
+                                            window.inner_size(),
+                                            window.scale_factor(),
+                                        );
+                                    }
+                                    if settings::font_ui(ui, &mut data.settings) {
+                                        ui.ctx().set_fonts(font_definitions(
+                                            data.settings.font.as_deref(),
+                                        ));
+                                    }
+                                    if ui.button("Reset to defaults").clicked() {
+                                        reset_settings(window, data, ui.ctx());
+                                        if let Some(surface) = surface {
+                                            rend3::configure_surface(
+                                                surface,
+                                                &renderer.device,
+                                                data.surface_format,
+                                                resolution,
+                                                data.settings.graphics.present_mode.wgpu(),
+                                            );
+                                        }
+                                    }
+                                });
+                                ui.collapsing("Terrain", |ui| {
+                                    terrain::terrain_ui(
+                                        ui,
+                                        renderer,
+                                        &mut data.terrain,
+                                        data.clay_mode.then(|| &data.clay_material_handle),
+                                        data.wireframe,
+                                    );
+                                });
+                                ui.collapsing("Stress test", |ui| {
+                                    stress_test::stress_test_ui(
+                                        ui,
+                                        renderer,
+                                        &mut data.stress_test,
+                                        &mut data.scene,
+                                        &data.frame_stats,
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.add_enabled(
+                                        data.skybox.is_some(),
+                                        egui::Checkbox::new(&mut data.skybox_enabled, "Skybox"),
+                                    );
+                                    if ui.button("Open skybox folder…").clicked() {
+                                        if let Some(folder) = file_dialog::folder() {
+                                            match skybox::load_cubemap(renderer, &folder) {
+                                                Ok(texture) => {
+                                                    rend3_framework::lock(&routines.skybox)
+                                                        .set_background_texture(Some(
+                                                            texture.clone(),
+                                                        ));
+                                                    data.skybox = Some(texture);
+                                                    data.skybox_enabled = true;
+                                                    data.skybox_error = None;
+                                                    data.skybox_panorama = None;
+                                                }
+                                                Err(ex) => data.skybox_error = Some(ex),
+                                            }
+                                        }
+                                    }
+                                    if ui.button("Open HDR panorama…").clicked() {
+                                        if let Some(path) =
+                                            file_dialog::open("Panorama", &["hdr", "exr"])
+                                        {
+                                            match skybox::Equirectangular::open(&path) {
+                                                Ok(panorama) => {
+                                                    let texture = panorama
+                                                        .to_cubemap(renderer, data.skybox_exposure);
+                                                    rend3_framework::lock(&routines.skybox)
+                                                        .set_background_texture(Some(
+                                                            texture.clone(),
+                                                        ));
+                                                    data.skybox = Some(texture);
+                                                    data.skybox_enabled = true;
+                                                    data.skybox_error = None;
+                                                    data.skybox_panorama = Some(panorama);
+                                                }
+                                                Err(ex) => data.skybox_error = Some(ex),
+                                            }
+                                        }
+                                    }
+                                });
+                                if let Some(panorama) = &data.skybox_panorama {
+                                    let response = ui.add(
+                                        egui::Slider::new(&mut data.skybox_exposure, -5.0..=5.0)
+                                            .text("Sky exposure (stops)"),
+                                    );
+                                    // Baking takes a moment, so not on every step of a drag
+                                    if response.drag_released()
+                                        || (response.changed() && !response.dragged())
+                                    {
+                                        let texture =
+                                            panorama.to_cubemap(renderer, data.skybox_exposure);
+                                        rend3_framework::lock(&routines.skybox)
+                                            .set_background_texture(Some(texture.clone()));
+                                        data.skybox = Some(texture);
+                                    }
+                                }
+                                if let Some(error) = &data.skybox_error {
+                                    ui.colored_label(egui::Color32::from_rgb(255, 0, 0), error);
+                                }
+                                ui.checkbox(&mut data.camtype, "Free camera (.)");
+                                if data.camtype {
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut data.walk_speed,
+                                                MIN_FLY_SPEED..=MAX_FLY_SPEED,
+                                            )
+                                            .logarithmic(true)
+                                            .text("Fly speed"),
+                                        )
+                                        .changed()
+                                    {
+                                        data.run_speed = data.walk_speed * 2.;
+                                    }
+                                    ui.label(format!(
+                                        "{} runs, {} creeps, scroll changes speed, {} grabs the cursor",
+                                        self.input.label(input::Action::Run),
+                                        self.input.label(input::Action::Creep),
+                                        self.input.label(input::Action::ToggleGrab)
+                                    ));
+                                }
+                                ui.collapsing("Camera sensitivity", |ui| {
+                                    settings::sensitivity_ui(ui, &mut data.settings);
+                                });
+                                ui.collapsing("Controls", |ui| {
+                                    input::controls_ui(ui, &mut self.input);
+                                });
                                 if ui
-                                    .color_edit_button_rgba_unmultiplied(&mut data.color)
+                                    .checkbox(&mut data.orbit_camera, "Orbit camera")
                                     .changed()
+                                    && data.orbit_camera
                                 {
-                                    renderer.update_material(
-                                        &data.player_material_handle.clone(),
-                                        rend3_routine::pbr::PbrMaterial {
-                                            albedo: rend3_routine::pbr::AlbedoComponent::Value(
-                                                glam::Vec4::from(data.color),
-                                            ),
-                                            transparency: rend3_routine::pbr::Transparency::Blend,
-                                            ..rend3_routine::pbr::PbrMaterial::default()
-                                        },
-                                    );
+                                    // Start the orbit from wherever the camera is now
+                                    data.orbit_distance = (data.camera_location
+                                        - data.ship_location)
+                                        .length()
+                                        .clamp(0.5, MAX_ORBIT_DISTANCE);
+                                    data.orbit_center = None;
+                                    self.grabber.as_mut().unwrap().request_ungrab(window);
+                                }
+                                if ui.button("Frame selection").clicked() {
+                                    frame_scene(data);
+                                    self.grabber.as_mut().unwrap().request_ungrab(window);
                                 }
+                                ui.collapsing("Idle orbit", |ui| {
+                                    ui.checkbox(&mut data.idle_orbit, "Orbit the ship when idle");
+                                    ui.add(
+                                        egui::Slider::new(&mut data.idle_delay, 5.0..=300.0)
+                                            .text("Idle delay (s)"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut data.idle_orbit_speed, 0.01..=1.0)
+                                            .text("Orbit speed (rad/s)"),
+                                    );
+                                });
+                                ui.collapsing("Camera settings", |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(
+                                            &mut data.orthographic,
+                                            false,
+                                            "Perspective",
+                                        );
+                                        ui.radio_value(
+                                            &mut data.orthographic,
+                                            true,
+                                            "Orthographic",
+                                        );
+                                    });
+                                    if data.orthographic {
+                                        ui.add(
+                                            egui::Slider::new(&mut data.ortho_size, 1.0..=1000.0)
+                                                .logarithmic(true)
+                                                .text("Ortho size"),
+                                        );
+                                    } else {
+                                        ui.add(
+                                            egui::Slider::new(&mut data.vfov, 10.0..=120.0)
+                                                .text("Vertical FOV"),
+                                        );
+                                        ui.add(
+                                            egui::Slider::new(&mut data.near, 0.001..=10.0)
+                                                .logarithmic(true)
+                                                .text("Near plane"),
+                                        );
+                                    }
+                                    ui.horizontal_wrapped(|ui| {
+                                        for preset in controls::ViewPreset::ALL {
+                                            if ui.button(preset.name()).clicked() {
+                                                let (yaw, pitch) = preset.orbit_angles();
+                                                data.orbit_yaw = yaw;
+                                                data.orbit_pitch = pitch;
+                                                data.orbit_camera = true;
+                                                self.grabber
+                                                    .as_mut()
+                                                    .unwrap()
+                                                    .request_ungrab(window);
+                                            }
+                                        }
+                                    });
+                                    ui.collapsing("Custom projection", |ui| {
+                                        egui::Grid::new("projection_matrix").show(ui, |ui| {
+                                            for row in data.projection_input.chunks_mut(4) {
+                                                for value in row {
+                                                    ui.add(
+                                                        egui::DragValue::new(value).speed(0.01),
+                                                    );
+                                                }
+                                                ui.end_row();
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.add(egui::Button::new("Apply")).clicked() {
+                                                match projection::validate_projection(
+                                                    projection::from_rows(&data.projection_input),
+                                                ) {
+                                                    Ok(matrix) => {
+                                                        data.projection_override = Some(matrix);
+                                                        data.projection_error = None;
+                                                    }
+                                                    Err(error) => {
+                                                        data.projection_error = Some(error)
+                                                    }
+                                                }
+                                            }
+                                            if ui.add(egui::Button::new("Reset")).clicked() {
+                                                data.projection_override = None;
+                                                data.projection_error = None;
+                                            }
+                                        });
+                                        if let Some(error) = &data.projection_error {
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(255, 0, 0),
+                                                error,
+                                            );
+                                        }
+                                    });
+                                });
+                                ui.collapsing("Camera bookmarks", |ui| {
+                                    let fly_time = ui.add(
+                                        egui::Slider::new(
+                                            &mut data.settings.fly_duration,
+                                            0.0..=5.0,
+                                        )
+                                        .text("Fly time (s)"),
+                                    );
+                                    if settings::settled(fly_time.changed(), &fly_time) {
+                                        settings::save(&data.settings);
+                                    }
+                                    let current = current_bookmark(data, String::new());
+                                    let picked = bookmarks::bookmarks_ui(
+                                        ui,
+                                        &mut data.bookmarks,
+                                        &mut data.bookmark_name,
+                                        current,
+                                    );
+                                    if let Some(bookmark) = picked {
+                                        fly_to(data, bookmark);
+                                        self.grabber.as_mut().unwrap().request_ungrab(window);
+                                    }
+                                });
                             });
                     }
                 });
 
+                let dragging = ctx.input().pointer.any_down();
+                history::track(data, snapshot, dragging);
+
                 // End the UI frame. Now let's draw the UI with our Backend, we could also
                 // handle the output here
-                let (_output, paint_commands) = data.platform.end_frame(Some(window));
+                let (output, paint_commands) = data.platform.end_frame(Some(window));
                 let paint_jobs = data.platform.context().tessellate(paint_commands);
 
                 let input = rend3_egui::Input {
@@ -659,50 +3389,338 @@ impl rend3_framework::App for Rendering {
                 }
                 data.view = data.view * Mat4::from_translation((-data.camera_location).into());
 
+                let idle = data.idle_orbit
+                    && self.last_input.map_or(false, |last_input| {
+                        (now - last_input).as_secs_f32() > data.idle_delay
+                    });
+                if data.orbit_camera {
+                    let center = data.orbit_center.unwrap_or(data.ship_location);
+                    let eye = center
+                        + Quat::from_euler(
+                            glam::EulerRot::YXZ,
+                            data.orbit_yaw,
+                            data.orbit_pitch,
+                            0.,
+                        )
+                        .mul_vec3a(Vec3A::new(
+                            0.,
+                            0.,
+                            -data.orbit_distance,
+                        ));
+                    data.view = Mat4::look_at_lh(eye.into(), center.into(), Vec3::Y);
+                }
+
+                if idle {
+                    let elapsed = data.start_time.elapsed().as_secs_f32();
+                    let (offset, started) = *data
+                        .idle_orbit_start
+                        .get_or_insert((data.camera_location - data.ship_location, elapsed));
+                    let eye = data.ship_location
+                        + Quat::from_rotation_y(data.idle_orbit_speed * (elapsed - started))
+                            .mul_vec3a(offset);
+                    data.view = Mat4::look_at_lh(eye.into(), data.ship_location.into(), Vec3::Y);
+                } else {
+                    // Leaving the orbit falls back to wherever the user left the camera
+                    data.idle_orbit_start = None;
+                }
+
+                if let Some(flight) = &data.fly_to {
+                    match flight.sample(data.start_time.elapsed().as_secs_f32()) {
+                        Some((view, vfov, ortho_size)) => {
+                            data.view = view;
+                            data.vfov = vfov;
+                            data.ortho_size = ortho_size;
+                        }
+                        None => {
+                            data.vfov = flight.to.vfov;
+                            data.ortho_size = flight.to.ortho_size;
+                            data.fly_to = None;
+                        }
+                    }
+                }
+
+                let viewport_target =
+                    data.viewport
+                        .prepare(renderer, &mut data.egui_routine, data.surface_format);
+                let scene_resolution = viewport_target.map_or(resolution, |target| target.size);
+                let aspect = scene_resolution.x as f32 / scene_resolution.y as f32;
+                renderer.set_aspect_ratio(aspect);
+
+                // Both share the view matrix, so switching doesn't move the camera
+                let mut projection = if data.orthographic {
+                    rend3::types::CameraProjection::Orthographic {
+                        size: Vec3A::new(data.ortho_size * aspect, data.ortho_size, 8000.),
+                    }
+                } else {
+                    rend3::types::CameraProjection::Perspective {
+                        vfov: data.vfov,
+                        near: data.near,
+                    }
+                };
+
+                // Authored cameras ride along with the ship. glTF cameras look down -Z,
+                // ours look down +Z, hence the flip.
+                if let Some(index) = data.gltf_camera {
+                    let camera = &data.player_cameras[index];
+                    data.view = (data.player.transform
+                        * camera.transform
+                        * Mat4::from_scale(Vec3::new(1., 1., -1.)))
+                    .inverse();
+                    projection = camera.projection;
+                }
+
+                if let Some(matrix) = data.projection_override {
+                    projection = rend3::types::CameraProjection::Raw(matrix);
+                }
+
+                if data.ship_animation.kind != material_animation::AnimationKind::None {
+                    renderer.update_material(
+                        &data.player.material,
+                        data.ship_animation.material(
+                            data.color,
+                            data.roughness,
+                            data.metallic,
+                            &data.surface,
+                            data.albedo_texture.as_ref(),
+                            data.start_time.elapsed().as_secs_f32(),
+                        ),
+                    );
+                }
+
+                if let Some(player) = &mut data.scene_animation {
+                    if let Some(pose) = player.update(data.start_time.elapsed().as_secs_f32()) {
+                        for (object, &node) in
+                            data.scene.objects.iter_mut().zip(&data.scene.object_nodes)
+                        {
+                            if let Some(transform) = pose[node] {
+                                object.set_transform(renderer, gltf_scene_root() * transform);
+                            }
+                            if let (true, Some(skin)) =
+                                (object.skeleton.is_some(), player.nodes.skins_of[node])
+                            {
+                                object.set_joint_matrices(
+                                    renderer,
+                                    player.nodes.joint_matrices(skin, node, &pose),
+                                );
+                            }
+                        }
+                    }
+                }
+
+                let camera_position = data.view.inverse().w_axis.truncate();
+                let mut objects: Vec<&mut SceneObject> = [&mut data.player, &mut data.station]
+                    .into_iter()
+                    .chain(data.scene.objects.iter_mut())
+                    .chain(data.scene.spawned.iter_mut())
+                    .chain(
+                        data.terrain
+                            .iter_mut()
+                            .flat_map(|terrain| terrain.chunks.iter_mut()),
+                    )
+                    .collect();
+                lod::update(
+                    renderer,
+                    &mut objects,
+                    data.clay_mode.then(|| &data.clay_material_handle),
+                    camera_position,
+                    data.projection,
+                    scene_resolution.y as f32,
+                );
+                render_mode::update(
+                    renderer,
+                    &data.debug_materials,
+                    &mut objects,
+                    data.render_mode,
+                    data.clay_mode.then(|| &data.clay_material_handle),
+                    data.wireframe,
+                    camera_position,
+                    &mut data.render_mode_camera,
+                );
+                data.debug_draw.update(renderer, camera_position);
+                data.particles
+                    .update(renderer, data.start_time.elapsed().as_secs_f32(), data.view);
+                let objects: Vec<&SceneObject> = [&data.player, &data.station]
+                    .into_iter()
+                    .chain(data.scene.objects.iter())
+                    .chain(data.scene.spawned.iter())
+                    .chain(
+                        data.terrain
+                            .iter()
+                            .flat_map(|terrain| terrain.chunks.iter()),
+                    )
+                    .collect();
+                let lights: Vec<&rend3::types::DirectionalLight> = std::iter::once(&data.sun)
+                    .chain(data.extra_lights.iter().map(|extra| &extra.light))
+                    .collect();
+                data.debug_draw.update_lines(
+                    renderer,
+                    &objects,
+                    &lights,
+                    camera_position,
+                    projection::matrix(projection, aspect) * data.view,
+                );
+                data.audio.update(&data.settings.audio, data.view, &objects);
+
+                data.projection = projection;
                 renderer.set_camera_data(rend3::types::Camera {
-                    projection: rend3::types::CameraProjection::Perspective {
-                        vfov: 60.0,
-                        near: 0.1,
-                    },
+                    projection,
                     view: data.view,
                 });
 
+                // A screenshot frame is drawn offscreen instead of to the surface
+                let screenshot = data.screenshot_requested.then(|| {
+                    screenshot::ScreenshotTarget::new(renderer, resolution, data.surface_format)
+                });
+                data.screenshot_requested = false;
+                let include_ui =
+                    screenshot.is_none() || data.screenshot_include_ui || data.viewport.enabled;
+
                 // Get a frame
-                let frame = rend3::util::output::OutputFrame::Surface {
-                    surface: Arc::clone(surface.unwrap()),
+                let frame = match (&screenshot, surface) {
+                    (Some(target), _) => Ok(rend3::util::output::OutputFrame::View(Arc::clone(
+                        &target.view,
+                    ))),
+                    (None, Some(surface)) => acquire_frame(
+                        renderer,
+                        surface,
+                        data.surface_format,
+                        resolution,
+                        data.settings.graphics.present_mode,
+                    ),
+                    (None, None) => Err(error::Error::NoSurface),
                 };
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(ex) => {
+                        data.errors.frame_failed(ex);
+                        window.request_redraw();
+                        control_flow(control_flow_mode);
+                        return;
+                    }
+                };
+
+                // Covers building and running the rendergraph, to the end of the frame
+                puffin::profile_scope!("rendergraph");
 
                 // Ready up the renderer
                 let (cmd_bufs, ready) = renderer.ready();
 
                 // Lock the routines
                 let pbr_routine = rend3_framework::lock(&routines.pbr);
-                let tonemapping_routine = rend3_framework::lock(&routines.tonemapping);
+                let mut skybox_routine = rend3_framework::lock(&routines.skybox);
+                skybox_routine.ready(renderer);
+
+                let tonemapper = data.tonemapper.get_or_insert_with(|| {
+                    tonemapping::Tonemapper::new(
+                        renderer,
+                        &base_rendergraph.interfaces,
+                        data.surface_format,
+                    )
+                });
+                let hdr_view = tonemapper.prepare(
+                    renderer,
+                    scene_resolution,
+                    &data.tonemapping,
+                    delta_time.as_secs_f32(),
+                );
 
                 // Build a rendergraph
                 let mut graph = rend3::graph::RenderGraph::new();
 
-                // Add the default rendergraph without a skybox
+                // Tonemapping stretches the scene over the whole output, so it can be
+                // rendered smaller or larger than what it ends up on
+                let render_resolution = (scene_resolution.as_vec2()
+                    * data.settings.graphics.render_scale)
+                    .as_uvec2()
+                    .max(glam::UVec2::ONE);
+
+                // Add the default rendergraph, with the skybox if there is one turned on.
+                // It only copies the scene into the HDR texture instead of tonemapping it.
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
                     &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
+                    (data.skybox_enabled && data.skybox.is_some()).then(|| &*skybox_routine),
+                    &tonemapper.copy,
+                    render_resolution,
+                    data.sample_count,
                     glam::Vec4::splat(0.),
                 );
+                graph.execute(
+                    renderer,
+                    rend3::util::output::OutputFrame::View(Arc::clone(&hdr_view)),
+                    cmd_bufs,
+                    &ready,
+                );
+                data.post_fx.apply(
+                    renderer,
+                    &hdr_view,
+                    scene_resolution,
+                    &data.post_fx_settings,
+                );
+                tonemapper.update_exposure(renderer);
+
+                let mut graph = rend3::graph::RenderGraph::new();
+                let output = graph.add_surface_texture();
+                tonemapper.add_to_graph(&mut graph, output);
+
+                // In window mode the scene goes to its own texture, and the surface only gets egui
+                if let Some(target) = viewport_target {
+                    graph.execute(
+                        renderer,
+                        rend3::util::output::OutputFrame::View(Arc::clone(&target.view)),
+                        Vec::new(),
+                        &ready,
+                    );
+                    graph = rend3::graph::RenderGraph::new();
+                }
 
                 // Add egui on top of all the other passes
-                let surface = graph.add_surface_texture();
-                data.egui_routine.add_to_graph(&mut graph, input, surface);
+                if include_ui {
+                    let surface = graph.add_surface_texture();
+                    data.egui_routine.add_to_graph(&mut graph, input, surface);
+                }
 
                 // Dispatch a render using the built up rendergraph!
-                graph.execute(renderer, frame, cmd_bufs, &ready);
+                graph.execute(renderer, frame, Vec::new(), &ready);
 
-                window.request_redraw();
-                control_flow(winit::event_loop::ControlFlow::Poll);
+                if let Some(target) = screenshot {
+                    data.screenshot_status = Some(match target.save(renderer) {
+                        Ok(path) => format!("Saved {}", path.display()),
+                        Err(ex) => ex,
+                    });
+                }
+
+                // Held keys and a drifting ship keep the simulation going, as do animations
+                // Anything that moves with the clock rather than with input, idle or not
+                let animating = output.needs_repaint
+                    || self.input.any_held()
+                    || data.velocity_vec != Vec3A::ZERO
+                    || data.ship_animation.kind != material_animation::AnimationKind::None
+                    || data
+                        .scene_animation
+                        .as_ref()
+                        .map_or(false, |player| player.playing)
+                    || data.fly_to.is_some()
+                    || data.idle_orbit
+                    || data.physics.is_some()
+                    || data.particles.active()
+                    // Polled once a frame until the worker thread is done
+                    || !data.pending_loads.is_empty()
+                    || data
+                        .drop_error
+                        .as_ref()
+                        .map_or(false, |(_, shown)| shown.elapsed().as_secs_f32() < 4.);
+                data.pacing.end_frame(
+                    window,
+                    now,
+                    &data.settings.graphics,
+                    !on_demand || animating,
+                );
+                control_flow(data.pacing.control_flow(window, control_flow_mode));
+            }
+            rend3_framework::Event::MainEventsCleared => {
+                control_flow(data.pacing.control_flow(window, control_flow_mode));
             }
             rend3_framework::Event::WindowEvent {
                 event: winit::event::WindowEvent::Focused(focus),
@@ -717,32 +3735,90 @@ impl rend3_framework::App for Rendering {
                     winit::event::WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
-                                scancode, state, ..
+                                scancode,
+                                state,
+                                virtual_keycode,
+                                ..
                             },
                         ..
                     },
                 ..
             } => {
-                self.scancode_status.insert(
-                    scancode,
-                    match state {
-                        winit::event::ElementState::Pressed => true,
-                        winit::event::ElementState::Released => false,
-                    },
-                );
+                self.last_input = Some(now);
+                // Typing into the UI shouldn't grab the cursor or hide the menu
+                let typing = data.platform.context().wants_keyboard_input();
+                for action in self.input.key_event(scancode, virtual_keycode, state) {
+                    run_action(
+                        window,
+                        data,
+                        self.grabber.as_mut().unwrap(),
+                        &mut self.menu_toggle,
+                        action,
+                        typing,
+                    );
+                }
+            }
+            rend3_framework::Event::WindowEvent {
+                event: winit::event::WindowEvent::MouseInput { button, state, .. },
+                ..
+            } if button != MouseButton::Left => {
+                self.last_input = Some(now);
+                for action in self.input.mouse_event(button, state) {
+                    run_action(
+                        window,
+                        data,
+                        self.grabber.as_mut().unwrap(),
+                        &mut self.menu_toggle,
+                        action,
+                        false,
+                    );
+                }
             }
             rend3_framework::Event::WindowEvent {
                 event:
                     winit::event::WindowEvent::MouseInput {
                         button: MouseButton::Left,
-                        state: ElementState::Pressed,
+                        state,
                         ..
                     },
                 ..
             } => {
-                let grabber = self.grabber.as_mut().unwrap();
+                self.last_input = Some(now);
+
+                // Clicks through the viewport window or with a grabbed cursor don't pick
+                match state {
+                    ElementState::Pressed => {
+                        data.pick_start = data.cursor_position.filter(|_| {
+                            !data.platform.context().wants_pointer_input()
+                                && !data.viewport.enabled
+                                && !self.grabber.as_ref().unwrap().grabbed()
+                        });
+                    }
+                    ElementState::Released => {
+                        if let (Some(start), Some(cursor)) =
+                            (data.pick_start.take(), data.cursor_position)
+                        {
+                            if (cursor.x - start.x).abs() + (cursor.y - start.y).abs() < 4. {
+                                pick_object(window, data, cursor);
+                                window.request_redraw();
+                            }
+                        }
+                    }
+                }
 
-                if !grabber.grabbed() {
+                // Orbiting uses the visible cursor, so it never grabs it
+                if data.orbit_camera {
+                    data.orbit_dragging = state == ElementState::Pressed
+                        && !data.platform.context().wants_pointer_input();
+                    return;
+                }
+
+                // Clicks on the UI, gizmo handles included, leave the cursor free
+                let grabber = self.grabber.as_mut().unwrap();
+                if state == ElementState::Pressed
+                    && !grabber.grabbed()
+                    && !data.platform.context().wants_pointer_input()
+                {
                     grabber.request_grab(window);
                 }
             }
@@ -754,14 +3830,18 @@ impl rend3_framework::App for Rendering {
                     },
                 ..
             } => {
+                self.last_input = Some(now);
                 if !self.grabber.as_ref().unwrap().grabbed() {
                     return;
                 }
+                window.request_redraw();
 
                 let mouse_delta = DVec2::new(delta_x, delta_y);
 
-                data.camera_yaw -= (mouse_delta.x / 2000.0) as f32;
-                data.camera_pitch -= (mouse_delta.y / 2000.0) as f32;
+                let sensitivity = data.settings.sensitivity;
+                let pitch_sign = if sensitivity.invert_y { -1. } else { 1. };
+                data.camera_yaw -= mouse_delta.x as f32 * sensitivity.mouse_look;
+                data.camera_pitch -= pitch_sign * mouse_delta.y as f32 * sensitivity.mouse_look;
 
                 /*
                 if data.camera_yaw < 0.0 {
@@ -777,12 +3857,104 @@ impl rend3_framework::App for Rendering {
             }
             rend3_framework::Event::WindowEvent { event, .. } => match event {
                 winit::event::WindowEvent::Resized(size) => {
-                    data.egui_routine
-                        .resize(size.width, size.height, window.scale_factor() as f32);
+                    // Minimizing resizes to 0x0, which egui can't lay out in
+                    if size.width != 0 && size.height != 0 {
+                        data.egui_routine.resize(
+                            size.width,
+                            size.height,
+                            window.scale_factor() as f32 * data.settings.ui_scale,
+                        );
+                        // The framework configures the new size with mailbox
+                        let present_mode = data.settings.graphics.present_mode;
+                        if let Some(surface) =
+                            surface.filter(|_| present_mode != settings::PresentMode::Mailbox)
+                        {
+                            rend3::configure_surface(
+                                surface,
+                                &renderer.device,
+                                data.surface_format,
+                                glam::UVec2::new(size.width, size.height),
+                                present_mode.wgpu(),
+                            );
+                        }
+                        window.request_redraw();
+                    }
+                }
+                // Moving to a monitor with another DPI, egui has to lay out at the new scale
+                winit::event::WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    if new_inner_size.width != 0 && new_inner_size.height != 0 {
+                        apply_ui_scale(window, data, *new_inner_size, scale_factor);
+                        window.request_redraw();
+                    }
                 }
                 winit::event::WindowEvent::CloseRequested => {
+                    remember_window(window, data);
+                    settings::save(&data.settings);
                     control_flow(winit::event_loop::ControlFlow::Exit);
                 }
+                winit::event::WindowEvent::CursorMoved { position, .. } => {
+                    self.last_input = Some(now);
+                    if let (true, Some(previous)) = (data.orbit_dragging, data.cursor_position) {
+                        let sensitivity = data.settings.sensitivity;
+                        let pitch_sign = if sensitivity.invert_y { -1. } else { 1. };
+                        data.orbit_yaw += (position.x - previous.x) as f32 * sensitivity.orbit;
+                        data.orbit_pitch = (data.orbit_pitch
+                            + pitch_sign * (position.y - previous.y) as f32 * sensitivity.orbit)
+                            .clamp(
+                                -std::f32::consts::FRAC_PI_2 + 0.01,
+                                std::f32::consts::FRAC_PI_2 - 0.01,
+                            );
+                    }
+                    data.cursor_position = Some(position);
+                }
+                winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                    self.last_input = Some(now);
+                    if data.orbit_camera && !data.platform.context().wants_pointer_input() {
+                        let lines = match delta {
+                            winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+                            // Roughly one line per 50 pixels on touchpads
+                            winit::event::MouseScrollDelta::PixelDelta(position) => {
+                                position.y as f32 / 50.
+                            }
+                        };
+                        data.orbit_distance = (data.orbit_distance
+                            * (1. - data.settings.sensitivity.zoom).powf(lines))
+                        .clamp(0.5, MAX_ORBIT_DISTANCE);
+                    } else if data.camtype && !data.platform.context().wants_pointer_input() {
+                        let lines = match delta {
+                            winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+                            winit::event::MouseScrollDelta::PixelDelta(position) => {
+                                position.y as f32 / 50.
+                            }
+                        };
+                        // Scrolling scales the free camera's speed, running stays twice walking
+                        data.walk_speed = (data.walk_speed * 1.2f32.powf(lines))
+                            .clamp(MIN_FLY_SPEED, MAX_FLY_SPEED);
+                        data.run_speed = data.walk_speed * 2.;
+                    }
+                }
+                winit::event::WindowEvent::HoveredFile(_) => {
+                    data.file_hovered = true;
+                }
+                winit::event::WindowEvent::HoveredFileCancelled => {
+                    data.file_hovered = false;
+                }
+                winit::event::WindowEvent::DroppedFile(path) => {
+                    data.file_hovered = false;
+                    // Anything that isn't glTF, OBJ or STL is turned away by the importer
+                    asset_loader::queue(
+                        &mut data.pending_loads,
+                        asset_loader::PendingLoad::start(
+                            path,
+                            asset_loader::LoadTarget::Station,
+                            true,
+                        ),
+                    );
+                    window.request_redraw();
+                }
                 _ => {}
             },
             _ => {}
@@ -0,0 +1,132 @@
+use glam::Mat4;
+use instant::Instant;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use super::hot_reload;
+use super::mesh_generator::Primitive;
+use super::mesh_importer::{self, GltfLoadError, Imported};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LoadTarget {
+    // Replaces the ship's mesh, it keeps the material the menu edits
+    Ship,
+    // Replaces the station's model
+    Station,
+    // Replaces the objects of the opened glTF scene
+    Scene,
+}
+
+// What an opened project saved about the objects a load brings in, put back once they are
+// in. Saved transforms and extras only fit the objects they were saved from, so they are
+// dropped when the file has a different number of them now.
+#[derive(Default)]
+pub struct Restore {
+    pub transforms: Vec<Mat4>,
+    pub extras: Vec<Vec<(String, String)>>,
+    // The shape standing in for the station's model
+    pub primitive: Option<Primitive>,
+}
+
+// A glTF file being read and decoded on a worker thread. The GPU uploads still have to
+// happen on the main thread, so the result is picked up with `poll` and finished there,
+// along with the files it pulled in for hot reload to watch.
+pub struct PendingLoad {
+    pub path: PathBuf,
+    pub target: LoadTarget,
+    // Errors go to a toast instead of the error window
+    pub dropped: bool,
+    // A file that changed on disk, it takes the old one's place without moving the camera
+    pub reload: bool,
+    // Points the camera at the scene once the load is in
+    pub frame: bool,
+    // Adds the file to the recently opened ones
+    pub remember: bool,
+    // Only the geometry is swapped in, the object keeps its material and edits
+    pub mesh_only: bool,
+    pub restore: Restore,
+    pub started: Instant,
+    receiver: mpsc::Receiver<(Result<Imported, GltfLoadError>, Vec<PathBuf>)>,
+}
+
+impl PendingLoad {
+    pub fn start(path: PathBuf, target: LoadTarget, dropped: bool) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let import_path = path.clone();
+        // Fails only when the load was replaced by another one, nobody wants it then
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let imported = mesh_importer::import(&import_path);
+            let _ = sender.send((imported, hot_reload::dependencies(&import_path)));
+        });
+        // No threads in the browser, the file is fetched from the page's server and decoded
+        // once it is in
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = super::web::fetch(&import_path)
+                .await
+                .and_then(|bytes| mesh_importer::import_bytes(&import_path, &bytes));
+            let _ = sender.send((result, hot_reload::dependencies(&import_path)));
+        });
+
+        Self {
+            path,
+            target,
+            dropped,
+            reload: false,
+            frame: true,
+            remember: true,
+            mesh_only: false,
+            restore: Restore::default(),
+            started: Instant::now(),
+            receiver,
+        }
+    }
+
+    // Errors of a reload nobody asked for go to a toast too
+    pub fn reload(path: PathBuf, target: LoadTarget) -> Self {
+        Self {
+            reload: true,
+            frame: false,
+            remember: false,
+            ..Self::start(path, target, true)
+        }
+    }
+
+    // The geometry alone, for an object that keeps everything else
+    pub fn geometry(path: PathBuf, target: LoadTarget) -> Self {
+        Self {
+            frame: false,
+            remember: false,
+            mesh_only: true,
+            ..Self::start(path, target, false)
+        }
+    }
+
+    // None while the worker is still busy
+    pub fn poll(&self) -> Option<(Result<Imported, GltfLoadError>, Vec<PathBuf>)> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            // The worker panicked on the file
+            Err(mpsc::TryRecvError::Disconnected) => Some((
+                Err(GltfLoadError::Parse(
+                    self.path.clone(),
+                    "the loader stopped while reading the file".to_string(),
+                )),
+                Vec::new(),
+            )),
+        }
+    }
+}
+
+// A load takes the place of the one already on its way for the same target. The geometry
+// alone isn't worth waiting for when a file is coming anyway, it's finished with the
+// settings of the moment it's in.
+pub fn queue(loads: &mut Vec<PendingLoad>, load: PendingLoad) {
+    if load.mesh_only && loads.iter().any(|pending| pending.target == load.target) {
+        return;
+    }
+    loads.retain(|pending| pending.target != load.target);
+    loads.push(load);
+}
@@ -0,0 +1,283 @@
+use glam::{Mat4, Vec3};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::error::{Error, ErrorQueue};
+use super::file_dialog;
+use super::scene::SceneObject;
+use super::settings::AudioSettings;
+
+const SOUND_EXTENSIONS: [&str; 2] = ["ogg", "wav"];
+// Full volume up to this far from the camera, falling off with distance past it
+const REFERENCE_DISTANCE: f32 = 5.;
+
+// Left and right gain, set every frame and read by the audio thread for every sample
+#[derive(Default)]
+struct Gains([AtomicU32; 2]);
+
+impl Gains {
+    fn set(&self, gains: [f32; 2]) {
+        for (gain, value) in self.0.iter().zip(gains) {
+            gain.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    fn get(&self, channel: usize) -> f32 {
+        f32::from_bits(self.0[channel].load(Ordering::Relaxed))
+    }
+}
+
+// Mixes the sound down to mono and plays it out in stereo through the gains. Decoded files
+// keep one channel count throughout, so it's read once up front.
+struct Spatial<S> {
+    source: S,
+    channels: u16,
+    gains: Arc<Gains>,
+    // Second half of the stereo frame started last
+    right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> Spatial<S> {
+    fn new(source: S, gains: Arc<Gains>) -> Self {
+        Self {
+            channels: source.channels().max(1),
+            source,
+            gains,
+            right: None,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Spatial<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let mut sum = 0.;
+        for _ in 0..self.channels {
+            sum += self.source.next()?;
+        }
+        let mono = sum / self.channels as f32;
+        self.right = Some(mono * self.gains.get(1));
+        Some(mono * self.gains.get(0))
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Spatial<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+// Inverse distance falloff past the reference distance, and constant power panning by how
+// far off to the side of the camera the sound is
+fn spatial_gains(offset: Vec3, right: Vec3) -> [f32; 2] {
+    let attenuation = REFERENCE_DISTANCE / offset.length().max(REFERENCE_DISTANCE);
+    let pan = offset.normalize_or_zero().dot(right).clamp(-1., 1.);
+    let angle = (pan + 1.) * std::f32::consts::FRAC_PI_4;
+    [angle.cos() * attenuation, angle.sin() * attenuation]
+}
+
+fn decode(path: &Path, looping: bool) -> Result<Box<dyn Source<Item = f32> + Send>, Error> {
+    let error = |what: String| Error::Audio(format!("{}: {}", path.display(), what));
+    let bytes = std::fs::read(path).map_err(|ex| error(ex.to_string()))?;
+    let source = Decoder::new(Cursor::new(bytes))
+        .map_err(|ex| error(ex.to_string()))?
+        .convert_samples::<f32>();
+    Ok(if looping {
+        Box::new(source.buffered().repeat_infinite())
+    } else {
+        Box::new(source)
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+pub struct SoundEmitter {
+    pub path: PathBuf,
+    // Id of the object it plays from, silent once that object is gone. The name is what
+    // the object was last called, for the list.
+    pub object: u64,
+    pub name: String,
+    pub volume: f32,
+    sink: Sink,
+    gains: Arc<Gains>,
+}
+
+pub struct Audio {
+    pub open: bool,
+    pub emitters: Vec<SoundEmitter>,
+    // Whether sounds attached next play on repeat
+    pub looping: bool,
+    // None without an output device, nothing plays then
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    // Plays everywhere at once, not from the scene
+    music: Option<(PathBuf, Sink)>,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        let output = OutputStream::try_default()
+            .map_err(|ex| tracing::warn!("no audio output, {}", ex))
+            .ok();
+        Self {
+            open: false,
+            emitters: Vec::new(),
+            looping: true,
+            output,
+            music: None,
+        }
+    }
+}
+
+impl Audio {
+    fn sink(&self) -> Result<Sink, Error> {
+        let (_, handle) = self
+            .output
+            .as_ref()
+            .ok_or_else(|| Error::Audio("there's no audio output".to_string()))?;
+        Sink::try_new(handle).map_err(|ex| Error::Audio(ex.to_string()))
+    }
+
+    pub fn attach(&mut self, path: PathBuf, object: &SceneObject) -> Result<(), Error> {
+        let source = decode(&path, self.looping)?;
+        let gains = Arc::new(Gains::default());
+        let sink = self.sink()?;
+        sink.append(Spatial::new(source, gains.clone()));
+        self.emitters.push(SoundEmitter {
+            path,
+            object: object.id(),
+            name: object.name.clone(),
+            volume: 1.,
+            sink,
+            gains,
+        });
+        Ok(())
+    }
+
+    pub fn play_music(&mut self, path: PathBuf) -> Result<(), Error> {
+        let source = decode(&path, true)?;
+        let sink = self.sink()?;
+        sink.append(source);
+        self.music = Some((path, sink));
+        Ok(())
+    }
+
+    // Pans and attenuates every emitter for where its object is from the camera `view`
+    // looks from, and applies the mixer. Sounds that have finished are dropped.
+    pub fn update(&mut self, mixer: &AudioSettings, view: Mat4, objects: &[&SceneObject]) {
+        let camera = view.inverse();
+        let right = camera.x_axis.truncate().normalize_or_zero();
+        let listener = camera.w_axis.truncate();
+        self.emitters.retain(|emitter| !emitter.sink.empty());
+        for emitter in &mut self.emitters {
+            emitter
+                .sink
+                .set_volume(mixer.master * mixer.sfx * emitter.volume);
+            let object = objects.iter().find(|object| object.id() == emitter.object);
+            if let Some(object) = object {
+                emitter.name.clone_from(&object.name);
+            }
+            let gains = object.map_or([0.; 2], |object| {
+                spatial_gains(object.transform.w_axis.truncate() - listener, right)
+            });
+            emitter.gains.set(gains);
+        }
+        if let Some((_, sink)) = &self.music {
+            sink.set_volume(mixer.master * mixer.music);
+        }
+    }
+}
+
+// Sounds are attached to `selected`, the object selected in the scene panel, or to the ship
+pub fn audio_window(
+    ctx: &egui::CtxRef,
+    audio: &mut Audio,
+    ship: &SceneObject,
+    selected: Option<&SceneObject>,
+    errors: &mut ErrorQueue,
+) {
+    let mut open = audio.open;
+    egui::Window::new("Audio")
+        .open(&mut open)
+        .default_width(320.)
+        .show(ctx, |ui| {
+            if audio.output.is_none() {
+                ui.label("No audio output device");
+            }
+            ui.horizontal(|ui| {
+                ui.label("Music");
+                if let Some((path, _)) = &audio.music {
+                    ui.label(file_name(path));
+                    if ui.button("Stop").clicked() {
+                        audio.music = None;
+                    }
+                } else if ui.button("Open…").clicked() {
+                    if let Some(path) = file_dialog::open("Sound", &SOUND_EXTENSIONS) {
+                        if let Err(ex) = audio.play_music(path) {
+                            errors.push(ex);
+                        }
+                    }
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let attach = ui
+                    .add_enabled(selected.is_some(), egui::Button::new("Attach sound…"))
+                    .on_disabled_hover_text("Select an object in the scene panel first");
+                let attach_to_ship = ui.button("Attach to ship…");
+                let object = match (attach.clicked(), attach_to_ship.clicked()) {
+                    (true, _) => selected,
+                    (_, true) => Some(ship),
+                    _ => None,
+                };
+                if let Some(object) = object {
+                    if let Some(path) = file_dialog::open("Sound", &SOUND_EXTENSIONS) {
+                        if let Err(ex) = audio.attach(path, object) {
+                            errors.push(ex);
+                        }
+                    }
+                }
+                ui.checkbox(&mut audio.looping, "Loop");
+            });
+            let mut removed = None;
+            for (index, emitter) in audio.emitters.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} on {}", file_name(&emitter.path), emitter.name));
+                    ui.add(egui::Slider::new(&mut emitter.volume, 0.0..=2.0));
+                    if ui.button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            // Dropping the sink stops the sound
+            if let Some(index) = removed {
+                audio.emitters.remove(index);
+            }
+        });
+    audio.open &= open;
+}
@@ -0,0 +1,141 @@
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+// A named camera pose, kept in the project file. Rotation is the free camera's, the one
+// its view matrix is built from.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub location: [f32; 3],
+    pub rotation: [f32; 4],
+    pub orthographic: bool,
+    pub vfov: f32,
+    pub near: f32,
+    pub ortho_size: f32,
+}
+
+impl CameraBookmark {
+    // Any rigid view matrix works, whichever camera mode produced it
+    pub fn from_view(
+        name: String,
+        view: Mat4,
+        orthographic: bool,
+        vfov: f32,
+        near: f32,
+        ortho_size: f32,
+    ) -> Self {
+        let (_, rotation, _) = view.to_scale_rotation_translation();
+        Self {
+            name,
+            location: view.inverse().w_axis.truncate().to_array(),
+            rotation: rotation.normalize().to_array(),
+            orthographic,
+            vfov,
+            near,
+            ortho_size,
+        }
+    }
+
+    pub fn location(&self) -> Vec3 {
+        Vec3::from(self.location)
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_array(self.rotation).normalize()
+    }
+}
+
+fn view(location: Vec3, rotation: Quat) -> Mat4 {
+    Mat4::from_quat(rotation) * Mat4::from_translation(-location)
+}
+
+// Slow at both ends
+fn ease(t: f32) -> f32 {
+    let t = t.clamp(0., 1.);
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
+
+// Eases the view from where the camera was to a bookmark. Times are seconds since start.
+pub struct FlyTo {
+    from_location: Vec3,
+    from_rotation: Quat,
+    from_vfov: f32,
+    from_ortho_size: f32,
+    pub to: CameraBookmark,
+    started: f32,
+    duration: f32,
+}
+
+impl FlyTo {
+    pub fn new(from: &CameraBookmark, to: CameraBookmark, now: f32, duration: f32) -> Self {
+        Self {
+            from_location: from.location(),
+            from_rotation: from.rotation(),
+            from_vfov: from.vfov,
+            from_ortho_size: from.ortho_size,
+            to,
+            started: now,
+            duration,
+        }
+    }
+
+    // View, vertical FOV and ortho size along the way, None once it has arrived
+    pub fn sample(&self, now: f32) -> Option<(Mat4, f32, f32)> {
+        let t = (now - self.started) / self.duration.max(0.0001);
+        if t >= 1. {
+            return None;
+        }
+        let t = ease(t);
+        Some((
+            view(
+                self.from_location.lerp(self.to.location(), t),
+                self.from_rotation.slerp(self.to.rotation(), t),
+            ),
+            self.from_vfov + (self.to.vfov - self.from_vfov) * t,
+            self.from_ortho_size + (self.to.ortho_size - self.from_ortho_size) * t,
+        ))
+    }
+}
+
+// Returns the bookmark to fly to when one is picked. `current` is what "Add" saves, under
+// the typed name.
+pub fn bookmarks_ui(
+    ui: &mut egui::Ui,
+    bookmarks: &mut Vec<CameraBookmark>,
+    new_name: &mut String,
+    current: CameraBookmark,
+) -> Option<CameraBookmark> {
+    let mut picked = None;
+    let mut removed = None;
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.button(bookmark.name.as_str()).clicked() {
+                picked = Some(bookmark.clone());
+            }
+            if ui.small_button("✖").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed {
+        bookmarks.remove(index);
+    }
+
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(new_name);
+        if ui.button("Add").clicked() {
+            let name = if new_name.trim().is_empty() {
+                format!("View {}", bookmarks.len() + 1)
+            } else {
+                new_name.trim().to_string()
+            };
+            bookmarks.push(CameraBookmark { name, ..current });
+            new_name.clear();
+        }
+    });
+    picked
+}
@@ -0,0 +1,145 @@
+use glam::{EulerRot, Quat, Vec3};
+use std::path::PathBuf;
+
+use super::settings::Msaa;
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+pub enum Backend {
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl Backend {
+    pub fn wgpu(self) -> wgpu::Backend {
+        match self {
+            Backend::Vulkan => wgpu::Backend::Vulkan,
+            Backend::Dx12 => wgpu::Backend::Dx12,
+            Backend::Metal => wgpu::Backend::Metal,
+            Backend::Gl => wgpu::Backend::Gl,
+        }
+    }
+}
+
+// Where the camera starts. Angles are in degrees, looking up and turning right are positive.
+#[derive(Clone, Copy)]
+pub struct CameraPose {
+    pub location: Vec3,
+    // (pitch, yaw), left out the headless render looks at the model instead
+    pub angles: Option<(f32, f32)>,
+}
+
+impl CameraPose {
+    // Camera to world, looking down +Z when both angles are zero
+    pub fn orientation(&self) -> Quat {
+        let (pitch, yaw) = self.angles.unwrap_or((0., 0.));
+        Quat::from_euler(EulerRot::YXZ, yaw.to_radians(), -pitch.to_radians(), 0.)
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.orientation() * Vec3::Z
+    }
+}
+
+fn parse_numbers(value: &str) -> Option<Vec<f32>> {
+    value
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    match parse_numbers(value).as_deref() {
+        Some(&[x, y, z]) => Ok(Vec3::new(x, y, z)),
+        _ => Err("expected x,y,z".to_string()),
+    }
+}
+
+fn parse_camera(value: &str) -> Result<CameraPose, String> {
+    let (location, angles) = match parse_numbers(value).as_deref() {
+        Some(&[x, y, z]) => (Vec3::new(x, y, z), None),
+        Some(&[x, y, z, pitch, yaw]) => (Vec3::new(x, y, z), Some((pitch, yaw))),
+        _ => return Err("expected x,y,z or x,y,z,pitch,yaw".to_string()),
+    };
+    Ok(CameraPose { location, angles })
+}
+
+fn parse_msaa(value: &str) -> Result<Msaa, String> {
+    match value {
+        "1" => Ok(Msaa::Off),
+        "4" => Ok(Msaa::X4),
+        _ => Err("MSAA is either 1 or 4".to_string()),
+    }
+}
+
+fn parse_dimension(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(dimension) if dimension > 0 => Ok(dimension),
+        _ => Err("expected a size in pixels".to_string()),
+    }
+}
+
+// Startup options, they win over config.toml
+#[derive(Clone, Default, clap::Parser)]
+#[clap(name = "project-heaven", version, about = "Project Heaven")]
+pub struct Cli {
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Model to open as the station, or to render with --headless"
+    )]
+    pub gltf: Option<PathBuf>,
+    #[clap(
+        long,
+        parse(try_from_str = parse_dimension),
+        help = "Window width, starts windowed instead of fullscreen"
+    )]
+    pub width: Option<u32>,
+    #[clap(
+        long,
+        parse(try_from_str = parse_dimension),
+        help = "Window height, starts windowed instead of fullscreen"
+    )]
+    pub height: Option<u32>,
+    #[clap(long, value_name = "1|4", parse(try_from_str = parse_msaa))]
+    pub msaa: Option<Msaa>,
+    #[clap(long, help = "Render one frame of --gltf to a PNG and exit")]
+    pub headless: bool,
+    #[clap(
+        long,
+        value_name = "X,Y,Z,PITCH,YAW",
+        allow_hyphen_values = true,
+        parse(try_from_str = parse_camera),
+        help = "Start in the free camera here, angles in degrees"
+    )]
+    pub camera: Option<CameraPose>,
+    #[clap(long, arg_enum, help = "Graphics API, picked by wgpu when left out")]
+    pub backend: Option<Backend>,
+    #[clap(long, help = "Turn on wgpu validation, like HEAVEN_VALIDATION")]
+    pub validation: bool,
+
+    #[clap(
+        long,
+        value_name = "PATH",
+        requires = "headless",
+        help = "Image to write, the model's name with .png by default"
+    )]
+    pub output: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "X,Y,Z",
+        requires = "headless",
+        allow_hyphen_values = true,
+        parse(try_from_str = parse_vec3),
+        help = "Point the headless camera looks at, the model's center by default"
+    )]
+    pub look_at: Option<Vec3>,
+    #[clap(
+        long,
+        value_name = "DEGREES",
+        requires = "headless",
+        help = "Vertical field of view of the headless camera"
+    )]
+    pub fov: Option<f32>,
+}
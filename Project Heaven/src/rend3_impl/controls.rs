@@ -1,12 +1,8 @@
 use glam::f32::{Quat, Vec3A};
-use rend3::util::typedefs::FastHashMap;
-use std::{collections::HashMap, hash::BuildHasher};
 
-pub mod platform;
+use super::input::{Action, Input};
 
-fn button_pressed<Hash: BuildHasher>(map: &HashMap<u32, bool, Hash>, key: u32) -> bool {
-    map.get(&key).map_or(false, |b| *b)
-}
+pub mod platform;
 
 pub struct SpaceCam {
     pub camera_yaw: f32,
@@ -26,10 +22,7 @@ pub struct SpaceCam {
 
     pub camera_location: Vec3A,
 }
-pub fn space_cam(
-    mut data: SpaceCam,
-    scancode_status: &FastHashMap<u32, bool>,
-) -> (Quat, Vec3A, f32) {
+pub fn space_cam(mut data: SpaceCam, input: &Input) -> (Quat, Vec3A, f32) {
     let quaternion_new = Quat::from_euler(
         glam::EulerRot::YXZ,
         data.camera_yaw,
@@ -47,33 +40,36 @@ pub fn space_cam(
     data.up = Quat::mul_vec3a(data.rotation.inverse(), Vec3A::Y);
     data.forward = Quat::mul_vec3a(data.rotation.inverse(), Vec3A::Z);
 
-    let velocity = if button_pressed(scancode_status, platform::Scancodes::SHIFT) {
+    // Shift runs, Alt creeps for lining things up
+    let velocity = if input.held(Action::Run) {
         data.run_speed
+    } else if input.held(Action::Creep) {
+        data.walk_speed * 0.1
     } else {
         data.walk_speed
     };
-    if button_pressed(scancode_status, platform::Scancodes::W) {
+    if input.held(Action::MoveForward) {
         data.camera_location += data.forward * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::S) {
+    if input.held(Action::MoveBack) {
         data.camera_location -= data.forward * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::A) {
+    if input.held(Action::MoveLeft) {
         data.camera_location -= data.side * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::D) {
+    if input.held(Action::MoveRight) {
         data.camera_location += data.side * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::SPACE) {
+    if input.held(Action::MoveUp) {
         data.camera_location += data.up * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::COMMA) {
+    if input.held(Action::MoveDown) {
         data.camera_location -= data.up * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::Q) {
+    if input.held(Action::RollLeft) {
         data.camera_roll -= 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::E) {
+    if input.held(Action::RollRight) {
         data.camera_roll += 1. * data.delta_time.as_secs_f32();
     }
 
@@ -83,6 +79,7 @@ pub fn space_cam(
 pub struct ShipCam {
     pub camera_yaw: f32,
     pub camera_pitch: f32,
+    pub look_speed: f32,
 
     pub ship_yaw: f32,
     pub ship_pitch: f32,
@@ -108,7 +105,7 @@ pub struct ShipCam {
 
 pub fn ship_cam(
     mut data: ShipCam,
-    scancode_status: &FastHashMap<u32, bool>,
+    input: &Input,
 ) -> (f32, f32, f32, f32, Quat, Vec3A, Quat, Vec3A, f32, f32) {
     let ship_new_rotation_quaternion = Quat::from_euler(
         glam::EulerRot::YXZ,
@@ -144,13 +141,13 @@ pub fn ship_cam(
     data.ship_up = Quat::mul_vec3a(data.ship_rotation, Vec3A::Y);
     data.ship_forward = Quat::mul_vec3a(data.ship_rotation, Vec3A::Z);
 
-    if button_pressed(scancode_status, platform::Scancodes::PLUS_NUM) {
+    if input.held(Action::Accelerate) {
         data.acceleration = data.acceleration + (0.1 * data.delta_time.as_secs_f32());
         if data.acceleration > data.acceleration_max {
             data.acceleration = data.acceleration_max;
         }
     }
-    if button_pressed(scancode_status, platform::Scancodes::MINUS_NUM) {
+    if input.held(Action::Decelerate) {
         data.acceleration = data.acceleration - (0.1 * data.delta_time.as_secs_f32());
         if data.acceleration < 0. {
             data.acceleration = 0.;
@@ -163,53 +160,53 @@ pub fn ship_cam(
 
     let velocity = 10.;
 
-    if button_pressed(scancode_status, platform::Scancodes::W) {
+    if input.held(Action::MoveForward) {
         data.velocity_vec += data.ship_forward * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::S) {
+    if input.held(Action::MoveBack) {
         data.velocity_vec -= data.ship_forward * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::A) {
+    if input.held(Action::MoveLeft) {
         data.velocity_vec -= data.ship_side * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::D) {
+    if input.held(Action::MoveRight) {
         data.velocity_vec += data.ship_side * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::SPACE) {
+    if input.held(Action::MoveUp) {
         data.velocity_vec += data.ship_up * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::COMMA) {
+    if input.held(Action::MoveDown) {
         data.velocity_vec -= data.ship_up * velocity * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::Q) {
+    if input.held(Action::RollLeft) {
         data.ship_roll += 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::E) {
+    if input.held(Action::RollRight) {
         data.ship_roll -= 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::UP) {
+    if input.held(Action::PitchUp) {
         data.ship_pitch -= 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::DOWN) {
+    if input.held(Action::PitchDown) {
         data.ship_pitch += 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::LEFT) {
+    if input.held(Action::YawLeft) {
         data.ship_yaw -= 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::RIGHT) {
+    if input.held(Action::YawRight) {
         data.ship_yaw += 1. * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::I) {
-        data.camera_pitch -= 1. * data.delta_time.as_secs_f32();
+    if input.held(Action::LookUp) {
+        data.camera_pitch -= data.look_speed * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::K) {
-        data.camera_pitch += 1. * data.delta_time.as_secs_f32();
+    if input.held(Action::LookDown) {
+        data.camera_pitch += data.look_speed * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::J) {
-        data.camera_yaw -= 1. * data.delta_time.as_secs_f32();
+    if input.held(Action::LookLeft) {
+        data.camera_yaw -= data.look_speed * data.delta_time.as_secs_f32();
     }
-    if button_pressed(scancode_status, platform::Scancodes::L) {
-        data.camera_yaw += 1. * data.delta_time.as_secs_f32();
+    if input.held(Action::LookRight) {
+        data.camera_yaw += data.look_speed * data.delta_time.as_secs_f32();
     }
 
     (
@@ -225,3 +222,51 @@ pub fn ship_cam(
         data.camera_pitch,
     )
 }
+
+// Fixed orbit angles looking straight along an axis, like the numpad views in a DCC tool.
+// Models face +Z, so the front view looks back down -Z at them.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ViewPreset {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 6] = [
+        ViewPreset::Front,
+        ViewPreset::Back,
+        ViewPreset::Left,
+        ViewPreset::Right,
+        ViewPreset::Top,
+        ViewPreset::Bottom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewPreset::Front => "Front",
+            ViewPreset::Back => "Back",
+            ViewPreset::Left => "Left",
+            ViewPreset::Right => "Right",
+            ViewPreset::Top => "Top",
+            ViewPreset::Bottom => "Bottom",
+        }
+    }
+
+    // (yaw, pitch) for the orbit camera. Straight up or down would leave look_at without
+    // an up direction, so those stop just short, as the orbit drag does.
+    pub fn orbit_angles(self) -> (f32, f32) {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        match self {
+            ViewPreset::Front => (PI, 0.),
+            ViewPreset::Back => (0., 0.),
+            ViewPreset::Left => (FRAC_PI_2, 0.),
+            ViewPreset::Right => (-FRAC_PI_2, 0.),
+            ViewPreset::Top => (0., FRAC_PI_2 - 0.01),
+            ViewPreset::Bottom => (0., -FRAC_PI_2 + 0.01),
+        }
+    }
+}
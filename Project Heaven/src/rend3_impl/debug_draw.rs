@@ -0,0 +1,369 @@
+use glam::{Mat4, Quat, Vec3};
+use rend3::types::{DirectionalLight, MaterialHandle, MeshHandle, ObjectHandle};
+use rend3_routine::pbr::{AlbedoComponent, PbrMaterial, Transparency};
+
+use super::mesh_generator::{self, MeshData};
+use super::scene::SceneObject;
+
+// Cells from the middle of the grid to its edge, it can't fade out any further away
+pub const GRID_RADIUS: i32 = 50;
+// Every tenth line is a major one, drawn brighter and wider
+const MAJOR: i32 = 10;
+// Lines are split up so the fade has vertices to interpolate between
+const SEGMENT: i32 = 5;
+
+// Reference helpers drawn in the scene but not part of it, so picking, framing and the
+// render modes leave them alone
+pub struct DebugDraw {
+    pub grid: bool,
+    pub axes: bool,
+    pub bounding_boxes: bool,
+    pub shadow_frustums: bool,
+    // Leaves the camera's frustum where it was and lets the camera fly off to look at it
+    pub debug_camera: bool,
+    // Cells out from the camera where the grid and axes start fading and where they are gone
+    pub fade_start: f32,
+    pub fade_end: f32,
+    material: MaterialHandle,
+    // What the meshes were built with
+    built_fade: (f32, f32),
+    grid_mesh: MeshHandle,
+    // X, Y and Z
+    axis_meshes: [MeshHandle; 3],
+    // Only there while shown, dropping them removes them from the renderer
+    grid_object: Option<ObjectHandle>,
+    axis_objects: Vec<ObjectHandle>,
+    // Spacing and grid origin the objects were last placed with
+    placement: Option<(f32, Vec3)>,
+
+    // View projection the debug camera took over from
+    frozen_camera: Option<Mat4>,
+    // Local bounds per mesh, so each is only scanned once
+    bounds: Vec<(MeshHandle, Option<(Vec3, Vec3)>)>,
+    // Boxes and frustums, remade when any of them change
+    lines: Vec<Line>,
+    lines_object: Option<ObjectHandle>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Line {
+    a: Vec3,
+    b: Vec3,
+    width: f32,
+    color: [u8; 4],
+}
+
+fn fade(distance: f32, (start, end): (f32, f32)) -> u8 {
+    let faded = ((distance - start) / (end - start).max(f32::EPSILON)).clamp(0., 1.);
+    ((1. - faded).powi(2) * 255.) as u8
+}
+
+// Unit spaced lines on y = 0 around the origin, fading out towards the edge. Scaled and
+// moved under the camera every frame, which makes it look endless.
+fn grid_mesh(fade_range: (f32, f32)) -> rend3::types::Mesh {
+    let mut mesh = MeshData::default();
+    let mut colors = Vec::new();
+    let color = |position: Vec3, brightness: u8| {
+        [
+            brightness,
+            brightness,
+            brightness,
+            fade(position.length(), fade_range),
+        ]
+    };
+    for line in -GRID_RADIUS..=GRID_RADIUS {
+        let (brightness, half_width) = if line % MAJOR == 0 {
+            (150, 0.02)
+        } else {
+            (90, 0.01)
+        };
+        for segment in (-GRID_RADIUS..GRID_RADIUS).step_by(SEGMENT as usize) {
+            let (line, start, end) = (line as f32, segment as f32, (segment + SEGMENT) as f32);
+            for (a, b, offset) in [
+                // Along X
+                (
+                    Vec3::new(start, 0., line),
+                    Vec3::new(end, 0., line),
+                    Vec3::Z * half_width,
+                ),
+                // Along Z
+                (
+                    Vec3::new(line, 0., start),
+                    Vec3::new(line, 0., end),
+                    Vec3::X * half_width,
+                ),
+            ] {
+                mesh_generator::push_strip(&mut mesh, (a, b), offset);
+                let (color_a, color_b) = (color(a, brightness), color(b, brightness));
+                colors.extend([color_a, color_b, color_b, color_a]);
+            }
+        }
+    }
+    mesh.normals = vec![Vec3::Y; mesh.positions.len()];
+    mesh.build_with_colors(colors)
+}
+
+fn push_line(
+    mesh: &mut MeshData,
+    colors: &mut Vec<[u8; 4]>,
+    (a, b): (Vec3, Vec3),
+    width: f32,
+    (color_a, color_b): ([u8; 4], [u8; 4]),
+) {
+    mesh_generator::push_crossed_strips(mesh, (a, b), width);
+    colors.extend([color_a, color_b, color_b, color_a].repeat(2));
+}
+
+// Line through the origin along `direction`
+fn axis_mesh(direction: Vec3, color: [u8; 3], fade_range: (f32, f32)) -> rend3::types::Mesh {
+    let mut mesh = MeshData::default();
+    let mut colors = Vec::new();
+    let color = |t: f32| [color[0], color[1], color[2], fade(t.abs(), fade_range)];
+    for segment in (-GRID_RADIUS..GRID_RADIUS).step_by(SEGMENT as usize) {
+        let (start, end) = (segment as f32, (segment + SEGMENT) as f32);
+        push_line(
+            &mut mesh,
+            &mut colors,
+            (direction * start, direction * end),
+            0.03,
+            (color(start), color(end)),
+        );
+    }
+    mesh.normals = vec![Vec3::Y; mesh.positions.len()];
+    mesh.build_with_colors(colors)
+}
+
+// X, Y and Z
+fn axis_meshes(renderer: &rend3::Renderer, fade_range: (f32, f32)) -> [MeshHandle; 3] {
+    [
+        renderer.add_mesh(axis_mesh(Vec3::X, [255, 60, 60], fade_range)),
+        renderer.add_mesh(axis_mesh(Vec3::Y, [60, 255, 60], fade_range)),
+        renderer.add_mesh(axis_mesh(Vec3::Z, [60, 120, 255], fade_range)),
+    ]
+}
+
+// Corners are numbered by bits, x in the first, y in the second and z in the third, so
+// the twelve edges join the corners one bit apart
+fn push_box(lines: &mut Vec<Line>, corners: [Vec3; 8], width: f32, color: [u8; 4]) {
+    for corner in 0..8 {
+        for bit in [1, 2, 4] {
+            if corner & bit == 0 {
+                lines.push(Line {
+                    a: corners[corner],
+                    b: corners[corner | bit],
+                    width,
+                    color,
+                });
+            }
+        }
+    }
+}
+
+fn box_corners(min: Vec3, max: Vec3) -> [Vec3; 8] {
+    let mut corners = [Vec3::ZERO; 8];
+    for (index, corner) in corners.iter_mut().enumerate() {
+        *corner = Vec3::new(
+            if index & 1 == 0 { min.x } else { max.x },
+            if index & 2 == 0 { min.y } else { max.y },
+            if index & 4 == 0 { min.z } else { max.z },
+        );
+    }
+    corners
+}
+
+// rend3 fits a directional light's shadow map to a box `distance` out either way from the
+// camera, turned to face along the light
+fn shadow_box(light: &DirectionalLight, camera: Vec3) -> [Vec3; 8] {
+    // Y can't be up for a light shining straight up or down
+    let up = if light.direction.normalize().dot(Vec3::Y).abs() > 0.999 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let to_world = Mat4::look_at_lh(camera, camera + light.direction, up).inverse();
+    box_corners(Vec3::splat(-light.distance), Vec3::splat(light.distance))
+        .map(|corner| to_world.transform_point3(corner))
+}
+
+// Reverse z, so the near plane is at one. The infinite far plane is cut off at a hundredth,
+// which for rend3's perspective is a hundred times the near distance.
+fn frustum_corners(view_projection: Mat4) -> [Vec3; 8] {
+    let to_world = view_projection.inverse();
+    box_corners(Vec3::new(-1., -1., 0.01), Vec3::ONE).map(|corner| {
+        let point = to_world * corner.extend(1.);
+        point.truncate() / point.w
+    })
+}
+
+impl DebugDraw {
+    pub fn new(renderer: &rend3::Renderer) -> Self {
+        let fade_range = (0., GRID_RADIUS as f32);
+        Self {
+            grid: false,
+            axes: false,
+            fade_start: fade_range.0,
+            fade_end: fade_range.1,
+            material: renderer.add_material(PbrMaterial {
+                albedo: AlbedoComponent::Vertex { srgb: false },
+                unlit: true,
+                transparency: Transparency::Blend,
+                ..PbrMaterial::default()
+            }),
+            built_fade: fade_range,
+            grid_mesh: renderer.add_mesh(grid_mesh(fade_range)),
+            axis_meshes: axis_meshes(renderer, fade_range),
+            grid_object: None,
+            axis_objects: Vec::new(),
+            placement: None,
+            bounding_boxes: false,
+            shadow_frustums: false,
+            debug_camera: false,
+            frozen_camera: None,
+            bounds: Vec::new(),
+            lines: Vec::new(),
+            lines_object: None,
+        }
+    }
+
+    fn mesh_bounds(&mut self, object: &SceneObject) -> Option<(Vec3, Vec3)> {
+        if let Some((_, bounds)) = self.bounds.iter().find(|(mesh, _)| *mesh == object.mesh) {
+            return *bounds;
+        }
+        let bounds = object.mesh_data.as_ref().and_then(MeshData::bounds);
+        self.bounds.push((object.mesh.clone(), bounds));
+        bounds
+    }
+
+    // Boxes around the objects, the lights' shadow boxes and the frozen camera frustum.
+    // Remade only when a line moved, which is every frame while the camera does.
+    pub fn update_lines(
+        &mut self,
+        renderer: &rend3::Renderer,
+        objects: &[&SceneObject],
+        lights: &[&DirectionalLight],
+        camera: Vec3,
+        view_projection: Mat4,
+    ) {
+        // Meshes that are gone don't need their bounds any more
+        self.bounds
+            .retain(|(mesh, _)| objects.iter().any(|object| object.mesh == *mesh));
+
+        let mut lines = Vec::new();
+        if self.bounding_boxes {
+            for object in objects {
+                if let Some(bounds) = self.mesh_bounds(object) {
+                    let (min, max) = mesh_generator::transform_bounds(bounds, object.transform);
+                    let width = (max - min).length() * 0.002;
+                    push_box(&mut lines, box_corners(min, max), width, [255, 200, 0, 255]);
+                }
+            }
+        }
+        if self.shadow_frustums {
+            for light in lights {
+                let corners = shadow_box(light, camera);
+                let width = light.distance * 0.002;
+                push_box(&mut lines, corners, width, [255, 120, 200, 255]);
+                // Which way the light shines, from the middle of the box
+                lines.push(Line {
+                    a: camera,
+                    b: camera + light.direction.normalize_or_zero() * light.distance,
+                    width,
+                    color: [255, 120, 200, 255],
+                });
+            }
+        }
+        self.frozen_camera = match (self.debug_camera, self.frozen_camera) {
+            (true, frozen) => Some(frozen.unwrap_or(view_projection)),
+            (false, _) => None,
+        };
+        if let Some(frozen) = self.frozen_camera {
+            let corners = frustum_corners(frozen);
+            let width = (corners[7] - corners[0]).length() * 0.002;
+            push_box(&mut lines, corners, width, [0, 255, 255, 255]);
+        }
+
+        if lines == self.lines {
+            return;
+        }
+        self.lines_object = (!lines.is_empty()).then(|| {
+            let mut mesh = MeshData::default();
+            let mut colors = Vec::new();
+            for line in &lines {
+                push_line(
+                    &mut mesh,
+                    &mut colors,
+                    (line.a, line.b),
+                    line.width,
+                    (line.color, line.color),
+                );
+            }
+            mesh.normals = vec![Vec3::Y; mesh.positions.len()];
+            let mesh = renderer.add_mesh(mesh.build_with_colors(colors));
+            self.add(renderer, &mesh)
+        });
+        self.lines = lines;
+    }
+
+    fn add(&self, renderer: &rend3::Renderer, mesh: &MeshHandle) -> ObjectHandle {
+        renderer.add_object(rend3::types::Object {
+            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh.clone()),
+            material: self.material.clone(),
+            transform: Mat4::IDENTITY,
+        })
+    }
+
+    // Cells grow by ten as the camera climbs by ten, so the grid stays readable from any
+    // height. The grid only moves in whole major cells so its lines hold still.
+    pub fn update(&mut self, renderer: &rend3::Renderer, camera: Vec3) {
+        let fade_range = (self.fade_start, self.fade_end.max(self.fade_start));
+        if fade_range != self.built_fade {
+            self.grid_mesh = renderer.add_mesh(grid_mesh(fade_range));
+            self.axis_meshes = axis_meshes(renderer, fade_range);
+            self.built_fade = fade_range;
+            // Added again below with the new meshes
+            self.grid_object = None;
+            self.axis_objects = Vec::new();
+        }
+        if self.grid != self.grid_object.is_some() {
+            self.grid_object = self.grid.then(|| self.add(renderer, &self.grid_mesh));
+            self.placement = None;
+        }
+        if self.axes != !self.axis_objects.is_empty() {
+            self.axis_objects = if self.axes {
+                self.axis_meshes
+                    .iter()
+                    .map(|mesh| self.add(renderer, mesh))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            self.placement = None;
+        }
+
+        let spacing = 10f32.powf(camera.y.abs().max(1.).log10().floor());
+        let step = spacing * MAJOR as f32;
+        let origin = Vec3::new(
+            (camera.x / step).round() * step,
+            0.,
+            (camera.z / step).round() * step,
+        );
+        if self.placement == Some((spacing, origin)) {
+            return;
+        }
+        self.placement = Some((spacing, origin));
+
+        let place = |translation: Vec3| {
+            Mat4::from_scale_rotation_translation(Vec3::splat(spacing), Quat::IDENTITY, translation)
+        };
+        if let Some(grid) = &self.grid_object {
+            renderer.set_object_transform(grid, place(origin));
+        }
+        // The axes sit a hair above the grid so they don't fight with its lines. X and Z
+        // slide along themselves with the camera, Y stays on the origin.
+        let lift = Vec3::Y * spacing * 0.005;
+        let translations = [Vec3::X * origin.x + lift, lift, Vec3::Z * origin.z + lift];
+        for (axis, translation) in self.axis_objects.iter().zip(translations) {
+            renderer.set_object_transform(axis, place(translation));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use super::mesh_importer::GltfLoadError;
+
+pub enum Error {
+    Gltf(GltfLoadError),
+    // Acquiring the swapchain failed even after reconfiguring it
+    Surface(wgpu::SurfaceError),
+    // Events can come in before the window has a surface, and while it is minimized
+    NoSurface,
+    // A sound that couldn't be read or played
+    Audio(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Gltf(ex) => write!(f, "{}", ex),
+            Error::Surface(ex) => write!(f, "couldn't get a frame to draw to, {}", ex),
+            Error::NoSurface => write!(f, "there's no surface to draw to"),
+            Error::Audio(ex) => write!(f, "{}", ex),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<GltfLoadError> for Error {
+    fn from(ex: GltfLoadError) -> Self {
+        Error::Gltf(ex)
+    }
+}
+
+impl From<wgpu::SurfaceError> for Error {
+    fn from(ex: wgpu::SurfaceError) -> Self {
+        Error::Surface(ex)
+    }
+}
+
+// Errors the user has to see, shown one at a time until dismissed
+#[derive(Default)]
+pub struct ErrorQueue {
+    errors: Vec<Error>,
+}
+
+impl ErrorQueue {
+    // Logged as well. The same error twice in a row, like a reload that keeps failing,
+    // is only shown once.
+    pub fn push(&mut self, error: impl Into<Error>) {
+        let error = error.into();
+        tracing::error!("{}", error);
+        let repeated = self
+            .errors
+            .last()
+            .map_or(false, |last| last.to_string() == error.to_string());
+        if !repeated {
+            self.errors.push(error);
+        }
+    }
+
+    // The frame is skipped either way and the next one tries again, so only running out
+    // of memory is worth stopping the user for
+    pub fn frame_failed(&mut self, error: Error) {
+        match error {
+            Error::NoSurface => {}
+            Error::Surface(wgpu::SurfaceError::OutOfMemory) => self.push(error),
+            error => tracing::warn!("frame skipped, {}", error),
+        }
+    }
+}
+
+pub fn error_modal(ctx: &egui::CtxRef, queue: &mut ErrorQueue) {
+    let error = match queue.errors.first() {
+        Some(error) => error,
+        None => return,
+    };
+    let mut dismissed = false;
+    let mut dismissed_all = false;
+    egui::Window::new("Error")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .show(ctx, |ui| {
+            ui.colored_label(egui::Color32::from_rgb(255, 80, 80), error.to_string());
+            if queue.errors.len() > 1 {
+                ui.label(format!("{} more after this one", queue.errors.len() - 1));
+            }
+            ui.horizontal(|ui| {
+                dismissed = ui.button("OK").clicked();
+                if queue.errors.len() > 1 {
+                    dismissed_all = ui.button("Dismiss all").clicked();
+                }
+            });
+        });
+    if dismissed_all {
+        queue.errors.clear();
+    } else if dismissed {
+        queue.errors.remove(0);
+    }
+}
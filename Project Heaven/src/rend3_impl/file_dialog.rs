@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+// Native pickers. The browser build has no file system to pick from, there they come back
+// empty and the buttons do nothing.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(filter: &str, extensions: &[&str]) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter(filter, extensions)
+        .pick_file()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(filter: &str, extensions: &[&str], file_name: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter(filter, extensions)
+        .set_file_name(file_name)
+        .save_file()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn folder() -> Option<PathBuf> {
+    rfd::FileDialog::new().pick_folder()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn open(_: &str, _: &[&str]) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(_: &str, _: &[&str], _: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn folder() -> Option<PathBuf> {
+    None
+}
@@ -0,0 +1,114 @@
+use instant::Instant;
+use std::time::Duration;
+use winit::event::{DeviceEvent, WindowEvent};
+use winit::event_loop::ControlFlow;
+
+use super::settings::GraphicsSettings;
+
+// Seconds without input before the app counts as idle
+const IDLE_AFTER: f32 = 5.;
+// Animations keep playing behind other windows, just not at full rate
+const BACKGROUND_FPS: u32 = 10;
+
+pub struct FramePacing {
+    focused: bool,
+    last_input: Instant,
+    // Set while the limiter holds the next frame back
+    next_frame: Option<Instant>,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            focused: true,
+            last_input: Instant::now(),
+            next_frame: None,
+        }
+    }
+}
+
+impl FramePacing {
+    pub fn event(&mut self, event: &rend3_framework::Event<'_, ()>) {
+        match event {
+            rend3_framework::Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => {
+                self.focused = *focused;
+                self.last_input = Instant::now();
+            }
+            rend3_framework::Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput { .. }
+                    | WindowEvent::MouseInput { .. }
+                    | WindowEvent::MouseWheel { .. }
+                    | WindowEvent::CursorMoved { .. }
+                    | WindowEvent::Touch(..),
+                ..
+            }
+            | rend3_framework::Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { .. },
+                ..
+            } => self.last_input = Instant::now(),
+            _ => {}
+        }
+    }
+
+    // Idle, the loop waits for events and only animations ask for frames, the same as
+    // redrawing on demand
+    pub fn idle(&self, graphics: &GraphicsSettings) -> bool {
+        graphics.idle_mode
+            && (!self.focused || self.last_input.elapsed().as_secs_f32() > IDLE_AFTER)
+    }
+
+    fn target_fps(&self, graphics: &GraphicsSettings) -> Option<u32> {
+        if graphics.idle_mode && !self.focused {
+            Some(
+                graphics
+                    .fps_limit
+                    .unwrap_or(BACKGROUND_FPS)
+                    .min(BACKGROUND_FPS),
+            )
+        } else {
+            graphics.fps_limit
+        }
+    }
+
+    // At the end of a frame that started at `frame_start`. If it wants another one, that is
+    // requested right away or held back until the limiter lets it start.
+    pub fn end_frame(
+        &mut self,
+        window: &winit::window::Window,
+        frame_start: Instant,
+        graphics: &GraphicsSettings,
+        another: bool,
+    ) {
+        self.next_frame = None;
+        if !another {
+            return;
+        }
+        match self.target_fps(graphics) {
+            Some(fps) => {
+                self.next_frame = Some(frame_start + Duration::from_secs_f32(1. / fps as f32))
+            }
+            None => window.request_redraw(),
+        }
+    }
+
+    // `wait` is what the loop does when no frame is held back
+    pub fn control_flow(
+        &mut self,
+        window: &winit::window::Window,
+        wait: ControlFlow,
+    ) -> ControlFlow {
+        match self.next_frame {
+            Some(next) if Instant::now() >= next => {
+                self.next_frame = None;
+                window.request_redraw();
+                wait
+            }
+            Some(next) => ControlFlow::WaitUntil(next),
+            None => wait,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use egui::plot::{Line, Plot, Value, Values};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const FRAMES: usize = 300;
+
+// Milliseconds of the last FRAMES frames, oldest first
+#[derive(Default)]
+pub struct FrameStats {
+    frame_times: VecDeque<f32>,
+}
+
+impl FrameStats {
+    pub fn push(&mut self, delta_time: Duration) {
+        if self.frame_times.len() == FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta_time.as_secs_f32() * 1000.);
+    }
+
+    pub fn average(&self) -> f32 {
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32
+    }
+}
+
+// What was handed to the renderer this frame. rend3 doesn't report its draw calls, but
+// every object is drawn on its own, so the object count stands in for them.
+#[derive(Default)]
+pub struct SceneCounts {
+    pub objects: usize,
+    pub triangles: usize,
+    pub lights: usize,
+}
+
+pub fn performance_window(
+    ctx: &egui::CtxRef,
+    renderer: &rend3::Renderer,
+    stats: &FrameStats,
+    counts: &SceneCounts,
+    open: &mut bool,
+) {
+    egui::Window::new("Performance")
+        .open(open)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10., -50.])
+        .show(ctx, |ui| {
+            let average = stats.average();
+            let min = stats.frame_times.iter().copied().fold(f32::MAX, f32::min);
+            let max = stats.frame_times.iter().copied().fold(0., f32::max);
+
+            ui.label(format!("{:.0} FPS", 1000. / average.max(0.001)));
+            ui.label(format!("{:.2} ms average", average));
+            ui.label(format!("{:.2} ms min, {:.2} ms max", min, max));
+            ui.label(format!(
+                "{} objects, {} triangles, {} lights",
+                counts.objects, counts.triangles, counts.lights
+            ));
+
+            let line = Line::new(Values::from_values_iter(
+                stats
+                    .frame_times
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &ms)| Value::new(i as f64, ms as f64)),
+            ));
+            Plot::new("frame_times")
+                .height(80.)
+                .width(300.)
+                .allow_zoom(false)
+                .allow_drag(false)
+                .include_y(0.)
+                .show(ui, |plot_ui| plot_ui.line(line));
+
+            // wgpu doesn't expose how much video memory is in use, only what the adapter is
+            let adapter = &renderer.adapter_info;
+            ui.separator();
+            ui.label(&adapter.name);
+            ui.label(format!(
+                "{:?} on {:?}, {:?}",
+                adapter.device_type, adapter.backend, renderer.mode
+            ));
+            let limits = renderer.device.limits();
+            ui.label(format!(
+                "Max texture {}px, max buffer binding {} MiB",
+                limits.max_texture_dimension_2d,
+                limits.max_storage_buffer_binding_size / (1024 * 1024)
+            ));
+        });
+}
@@ -0,0 +1,302 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+use rend3::types::CameraProjection;
+
+use super::picking::{self, Ray};
+use super::projection;
+use super::scale_bar;
+use super::scene::SceneObject;
+
+// How far the handles reach out from the object, in screen points
+const HANDLE_LENGTH: f32 = 80.;
+// How close the pointer has to be to a handle to grab it, in screen points
+const GRAB_DISTANCE: f32 = 8.;
+const RING_SEGMENTS: usize = 48;
+
+const AXIS_COLORS: [egui::Color32; 3] = [
+    egui::Color32::from_rgb(230, 60, 60),
+    egui::Color32::from_rgb(60, 200, 60),
+    egui::Color32::from_rgb(60, 110, 230),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub const ALL: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Move",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+}
+
+struct Drag {
+    axis: usize,
+    // Transform and pointer ray when the handle was grabbed, the drag is applied to these
+    transform: Mat4,
+    ray: Ray,
+}
+
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    drag: Option<Drag>,
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            drag: None,
+        }
+    }
+}
+
+// Moving and rotating follow the world axes, scaling follows the object's own
+fn axes(mode: GizmoMode, rotation: Quat) -> [Vec3; 3] {
+    match mode {
+        GizmoMode::Translate | GizmoMode::Rotate => [Vec3::X, Vec3::Y, Vec3::Z],
+        GizmoMode::Scale => [rotation * Vec3::X, rotation * Vec3::Y, rotation * Vec3::Z],
+    }
+}
+
+pub fn to_screen(view_projection: Mat4, screen: egui::Rect, point: Vec3) -> Option<egui::Pos2> {
+    let clip = view_projection * point.extend(1.);
+    if clip.w <= 0. {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+    Some(egui::pos2(
+        screen.left() + (ndc.x + 1.) / 2. * screen.width(),
+        screen.top() + (1. - ndc.y) / 2. * screen.height(),
+    ))
+}
+
+fn distance_to_segment(point: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let (ab, ap) = (b - a, point - a);
+    let t = ((ab.x * ap.x + ab.y * ap.y) / ab.length_sq().max(1e-6)).clamp(0., 1.);
+    (a + ab * t).distance(point)
+}
+
+// Where along the axis line through `origin` the ray passes closest, None when the axis
+// points straight at the camera
+fn axis_parameter(ray: &Ray, origin: Vec3, axis: Vec3) -> Option<f32> {
+    let facing = axis.dot(ray.direction);
+    let denominator = 1. - facing * facing;
+    if denominator < 1e-4 {
+        return None;
+    }
+    let offset = origin - ray.origin;
+    Some((facing * ray.direction.dot(offset) - axis.dot(offset)) / denominator)
+}
+
+// Where the ray crosses the plane through `origin`, relative to it. None when the plane
+// is seen edge on.
+fn plane_offset(ray: &Ray, origin: Vec3, normal: Vec3) -> Option<Vec3> {
+    let facing = normal.dot(ray.direction);
+    if facing.abs() < 1e-4 {
+        return None;
+    }
+    let distance = normal.dot(origin - ray.origin) / facing;
+    Some(ray.origin + ray.direction * distance - origin)
+}
+
+fn dragged_transform(mode: GizmoMode, drag: &Drag, ray: &Ray) -> Option<Mat4> {
+    let (scale, rotation, translation) = drag.transform.to_scale_rotation_translation();
+    let axis = axes(mode, rotation)[drag.axis];
+    match mode {
+        GizmoMode::Translate => {
+            let start = axis_parameter(&drag.ray, translation, axis)?;
+            let end = axis_parameter(ray, translation, axis)?;
+            Some(Mat4::from_scale_rotation_translation(
+                scale,
+                rotation,
+                translation + axis * (end - start),
+            ))
+        }
+        GizmoMode::Rotate => {
+            let start = plane_offset(&drag.ray, translation, axis)?;
+            let end = plane_offset(ray, translation, axis)?;
+            let angle = axis.dot(start.cross(end)).atan2(start.dot(end));
+            Some(Mat4::from_scale_rotation_translation(
+                scale,
+                Quat::from_axis_angle(axis, angle) * rotation,
+                translation,
+            ))
+        }
+        GizmoMode::Scale => {
+            let start = axis_parameter(&drag.ray, translation, axis)?;
+            let end = axis_parameter(ray, translation, axis)?;
+            if start.abs() < 1e-6 {
+                return None;
+            }
+            let mut scale = scale;
+            scale[drag.axis] *= (end / start).max(0.01);
+            Some(Mat4::from_scale_rotation_translation(
+                scale,
+                rotation,
+                translation,
+            ))
+        }
+    }
+}
+
+// Draws the handles for `gizmo.mode` over `object` and applies dragging them to its
+// transform. Uses last frame's camera, like the scale bar.
+pub fn gizmo_ui(
+    ctx: &egui::CtxRef,
+    renderer: &rend3::Renderer,
+    gizmo: &mut Gizmo,
+    object: &mut SceneObject,
+    view: Mat4,
+    projection: CameraProjection,
+) {
+    let screen = ctx.input().screen_rect();
+    let projection_matrix = projection::matrix(projection, screen.width() / screen.height());
+    let view_projection = projection_matrix * view;
+
+    let (_, rotation, position) = object.transform.to_scale_rotation_translation();
+    let camera_position = view.inverse().w_axis.truncate();
+    let length = match scale_bar::points_per_meter(
+        projection,
+        camera_position.distance(position),
+        screen.height(),
+    ) {
+        Some(points_per_meter) => HANDLE_LENGTH / points_per_meter,
+        None => return,
+    };
+    let center = match to_screen(view_projection, screen, position) {
+        Some(center) => center,
+        None => return,
+    };
+
+    // Screen space outline of every handle, None for handles that go behind the camera
+    let axes = axes(gizmo.mode, rotation);
+    let handles: Vec<Option<Vec<egui::Pos2>>> = (0..3)
+        .map(|axis| {
+            let points: Vec<Vec3> = match gizmo.mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    vec![position, position + axes[axis] * length]
+                }
+                GizmoMode::Rotate => {
+                    let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                    (0..=RING_SEGMENTS)
+                        .map(|segment| {
+                            let angle =
+                                segment as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                            position + (u * angle.cos() + v * angle.sin()) * length
+                        })
+                        .collect()
+                }
+            };
+            points
+                .into_iter()
+                .map(|point| to_screen(view_projection, screen, point))
+                .collect()
+        })
+        .collect();
+
+    let bounds = handles
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(
+            egui::Rect::from_center_size(center, egui::Vec2::ZERO),
+            |rect, point| rect.union(egui::Rect::from_center_size(*point, egui::Vec2::ZERO)),
+        )
+        .expand(GRAB_DISTANCE);
+
+    // Only a handle under the pointer takes over the mouse, clicks anywhere else still
+    // reach the scene
+    let hovered = ctx.input().pointer.hover_pos().and_then(|pointer| {
+        handles
+            .iter()
+            .enumerate()
+            .filter_map(|(axis, handle)| {
+                let distance = handle
+                    .as_ref()?
+                    .windows(2)
+                    .map(|segment| distance_to_segment(pointer, segment[0], segment[1]))
+                    .fold(f32::MAX, f32::min);
+                (distance < GRAB_DISTANCE).then(|| (axis, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(axis, _)| axis)
+    });
+    if hovered.is_some() || gizmo.drag.is_some() {
+        egui::Area::new("gizmo")
+            .fixed_pos(bounds.min)
+            .movable(false)
+            .show(ctx, |ui| {
+                let response = ui.allocate_rect(bounds, egui::Sense::drag());
+                let pointer = response.interact_pointer_pos();
+                if response.drag_started() {
+                    gizmo.drag = hovered.zip(pointer).and_then(|(axis, pointer)| {
+                        Some(Drag {
+                            axis,
+                            transform: object.transform,
+                            ray: pointer_ray(view, projection_matrix, screen, pointer)?,
+                        })
+                    });
+                }
+                if !response.dragged() {
+                    gizmo.drag = None;
+                }
+                if let (Some(drag), Some(pointer)) = (&gizmo.drag, pointer) {
+                    if let Some(transform) = pointer_ray(view, projection_matrix, screen, pointer)
+                        .and_then(|ray| dragged_transform(gizmo.mode, drag, &ray))
+                    {
+                        object.set_transform(renderer, transform);
+                    }
+                }
+            });
+    }
+
+    let highlighted = gizmo.drag.as_ref().map(|drag| drag.axis).or(hovered);
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for (axis, handle) in handles.iter().enumerate() {
+        let points = match handle {
+            Some(points) => points,
+            None => continue,
+        };
+        let color = if highlighted == Some(axis) {
+            egui::Color32::YELLOW
+        } else {
+            AXIS_COLORS[axis]
+        };
+        painter.add(egui::Shape::line(
+            points.clone(),
+            egui::Stroke::new(2., color),
+        ));
+        let end = points[points.len() - 1];
+        match gizmo.mode {
+            GizmoMode::Translate => painter.circle_filled(end, 5., color),
+            GizmoMode::Scale => painter.rect_filled(
+                egui::Rect::from_center_size(end, egui::vec2(8., 8.)),
+                0.,
+                color,
+            ),
+            GizmoMode::Rotate => {}
+        }
+    }
+}
+
+fn pointer_ray(
+    view: Mat4,
+    projection: Mat4,
+    screen: egui::Rect,
+    pointer: egui::Pos2,
+) -> Option<Ray> {
+    picking::cursor_ray(
+        view,
+        projection,
+        Vec2::new(pointer.x - screen.left(), pointer.y - screen.top()),
+        Vec2::new(screen.width(), screen.height()),
+    )
+}
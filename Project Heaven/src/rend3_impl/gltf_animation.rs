@@ -0,0 +1,412 @@
+use glam::{Mat4, Quat, Vec3};
+use rend3::types::CameraProjection;
+
+use super::{gizmo, projection};
+
+const BONE_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 255, 160);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // Every keyframe stores (in tangent, value, out tangent)
+    CubicSpline,
+}
+
+// Morph target weights are dropped for now, they need morph target support in the meshes
+pub enum ChannelValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+pub struct Channel {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+}
+
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    // Time of the last keyframe in any channel
+    pub duration: f32,
+}
+
+pub struct Skin {
+    pub joints: Vec<usize>,
+    // One per joint, identity when the file leaves them out
+    pub inverse_bind: Vec<Mat4>,
+}
+
+// Rest pose and hierarchy of the scene's nodes, and the skins that posed nodes drive
+pub struct NodeTree {
+    pub roots: Vec<usize>,
+    pub children: Vec<Vec<usize>>,
+    // Local (translation, rotation, scale) of every node
+    pub rest: Vec<(Vec3, Quat, Vec3)>,
+    pub skins: Vec<Skin>,
+    // Skin each node's mesh is bound to
+    pub skins_of: Vec<Option<usize>>,
+}
+
+impl NodeTree {
+    pub fn load(doc: &gltf::Document, datas: &[gltf::buffer::Data]) -> Self {
+        let roots = doc
+            .default_scene()
+            .or_else(|| doc.scenes().next())
+            .map_or_else(Vec::new, |scene| {
+                scene.nodes().map(|node| node.index()).collect()
+            });
+        let children = doc
+            .nodes()
+            .map(|node| node.children().map(|child| child.index()).collect())
+            .collect();
+        let rest = doc
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                (
+                    Vec3::from(translation),
+                    Quat::from_array(rotation),
+                    Vec3::from(scale),
+                )
+            })
+            .collect();
+        let skins =
+            doc.skins()
+                .map(|skin| {
+                    let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+                    let reader = skin.reader(|buffer| Some(&datas[buffer.index()]));
+                    let mut inverse_bind: Vec<Mat4> = reader
+                        .read_inverse_bind_matrices()
+                        .map_or_else(Vec::new, |matrices| {
+                            matrices
+                                .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                                .collect()
+                        });
+                    inverse_bind.resize(joints.len(), Mat4::IDENTITY);
+                    Skin {
+                        joints,
+                        inverse_bind,
+                    }
+                })
+                .collect();
+        let skins_of = doc
+            .nodes()
+            .map(|node| node.skin().map(|skin| skin.index()))
+            .collect();
+
+        Self {
+            roots,
+            children,
+            rest,
+            skins,
+            skins_of,
+        }
+    }
+
+    pub fn rest_pose(&self) -> Vec<Option<Mat4>> {
+        self.world_transforms(&self.rest)
+    }
+
+    // Joint matrices for a mesh on `mesh_node` bound to `skin`, relative to that node since
+    // the object transform is applied on top. `world` comes from pose() or rest_pose().
+    pub fn joint_matrices(
+        &self,
+        skin: usize,
+        mesh_node: usize,
+        world: &[Option<Mat4>],
+    ) -> Vec<Mat4> {
+        let skin = &self.skins[skin];
+        let to_mesh = world[mesh_node].unwrap_or(Mat4::IDENTITY).inverse();
+        skin.joints
+            .iter()
+            .zip(&skin.inverse_bind)
+            .map(|(&joint, &inverse_bind)| {
+                to_mesh * world[joint].unwrap_or(Mat4::IDENTITY) * inverse_bind
+            })
+            .collect()
+    }
+
+    // From each joint of `skin` to its child joints, in the space `world` is in. Joints
+    // without a joint below them have nothing to point at and are left out.
+    pub fn bones(&self, skin: usize, world: &[Option<Mat4>]) -> Vec<(Vec3, Vec3)> {
+        let joints = &self.skins[skin].joints;
+        let position = |node: usize| world[node].map(|transform| transform.w_axis.truncate());
+        joints
+            .iter()
+            .flat_map(|&joint| {
+                self.children[joint]
+                    .iter()
+                    .filter(|&child| joints.contains(child))
+                    .filter_map(move |&child| Some((position(joint)?, position(child)?)))
+            })
+            .collect()
+    }
+
+    // World transform of every node reachable from the roots, None for the rest
+    fn world_transforms(&self, locals: &[(Vec3, Quat, Vec3)]) -> Vec<Option<Mat4>> {
+        let mut transforms = vec![None; locals.len()];
+        let mut stack: Vec<(usize, Mat4)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, Mat4::IDENTITY))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            // Malformed files can list a node twice, visit it once
+            if transforms[node].is_some() {
+                continue;
+            }
+            let (translation, rotation, scale) = locals[node];
+            let transform =
+                parent * Mat4::from_scale_rotation_translation(scale, rotation, translation);
+            transforms[node] = Some(transform);
+            stack.extend(self.children[node].iter().map(|&child| (child, transform)));
+        }
+        transforms
+    }
+}
+
+fn read_channel(
+    channel: gltf::animation::Channel,
+    datas: &[gltf::buffer::Data],
+) -> Option<Channel> {
+    let reader = channel.reader(|buffer| Some(&datas[buffer.index()]));
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+    let values = match reader.read_outputs()? {
+        gltf::animation::util::ReadOutputs::Translations(values) => {
+            ChannelValues::Translation(values.map(Vec3::from).collect())
+        }
+        gltf::animation::util::ReadOutputs::Rotations(values) => {
+            ChannelValues::Rotation(values.into_f32().map(Quat::from_array).collect())
+        }
+        gltf::animation::util::ReadOutputs::Scales(values) => {
+            ChannelValues::Scale(values.map(Vec3::from).collect())
+        }
+        gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => return None,
+    };
+    let interpolation = match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+    };
+
+    let count = match &values {
+        ChannelValues::Translation(values) | ChannelValues::Scale(values) => values.len(),
+        ChannelValues::Rotation(values) => values.len(),
+    };
+    let per_key = if interpolation == Interpolation::CubicSpline {
+        3
+    } else {
+        1
+    };
+    // A channel that doesn't line up with its keyframes can't be sampled safely
+    if times.is_empty() || count != times.len() * per_key {
+        return None;
+    }
+
+    Some(Channel {
+        node: channel.target().node().index(),
+        interpolation,
+        times,
+        values,
+    })
+}
+
+pub fn load_animations(doc: &gltf::Document, datas: &[gltf::buffer::Data]) -> Vec<Animation> {
+    doc.animations()
+        .map(|animation| {
+            let channels: Vec<Channel> = animation
+                .channels()
+                .filter_map(|channel| read_channel(channel, datas))
+                .collect();
+            let duration = channels
+                .iter()
+                .filter_map(|channel| channel.times.last().copied())
+                .fold(0., f32::max);
+            Animation {
+                name: match animation.name() {
+                    Some(name) => name.to_string(),
+                    None => format!("Animation {}", animation.index()),
+                },
+                channels,
+                duration,
+            }
+        })
+        .collect()
+}
+
+// Keyframes either side of `time`, how far along between them it is, and their spacing.
+// Outside the keyframes the first or last one holds.
+fn keyframe(times: &[f32], time: f32) -> (usize, usize, f32, f32) {
+    let last = times.len() - 1;
+    if last == 0 || time <= times[0] {
+        return (0, 0, 0., 0.);
+    }
+    if time >= times[last] {
+        return (last, last, 0., 0.);
+    }
+
+    let next = times.partition_point(|&key| key <= time).clamp(1, last);
+    let previous = next - 1;
+    let step = times[next] - times[previous];
+    let factor = if step > 0. {
+        (time - times[previous]) / step
+    } else {
+        0.
+    };
+    (previous, next, factor, step)
+}
+
+fn hermite<T>(values: &[T], previous: usize, next: usize, s: f32, step: f32) -> T
+where
+    T: Copy + std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+    let (s2, s3) = (s * s, s * s * s);
+    let start = values[previous * 3 + 1];
+    let out_tangent = values[previous * 3 + 2];
+    let in_tangent = values[next * 3];
+    let end = values[next * 3 + 1];
+    start * (2. * s3 - 3. * s2 + 1.)
+        + out_tangent * ((s3 - 2. * s2 + s) * step)
+        + end * (-2. * s3 + 3. * s2)
+        + in_tangent * ((s3 - s2) * step)
+}
+
+fn sample_vec3(channel: &Channel, values: &[Vec3], time: f32) -> Vec3 {
+    let (previous, next, factor, step) = keyframe(&channel.times, time);
+    match channel.interpolation {
+        Interpolation::Step => values[previous],
+        Interpolation::Linear => values[previous].lerp(values[next], factor),
+        Interpolation::CubicSpline => hermite(values, previous, next, factor, step),
+    }
+}
+
+fn sample_quat(channel: &Channel, values: &[Quat], time: f32) -> Quat {
+    let (previous, next, factor, step) = keyframe(&channel.times, time);
+    match channel.interpolation {
+        Interpolation::Step => values[previous],
+        Interpolation::Linear => values[previous].slerp(values[next], factor),
+        Interpolation::CubicSpline => hermite(values, previous, next, factor, step).normalize(),
+    }
+}
+
+// World transform of every node at `time`. Nodes no channel touches keep their rest pose.
+pub fn pose(animation: &Animation, nodes: &NodeTree, time: f32) -> Vec<Option<Mat4>> {
+    let mut locals = nodes.rest.clone();
+    for channel in &animation.channels {
+        let local = match locals.get_mut(channel.node) {
+            Some(local) => local,
+            None => continue,
+        };
+        match &channel.values {
+            ChannelValues::Translation(values) => local.0 = sample_vec3(channel, values, time),
+            ChannelValues::Rotation(values) => local.1 = sample_quat(channel, values, time),
+            ChannelValues::Scale(values) => local.2 = sample_vec3(channel, values, time),
+        }
+    }
+    nodes.world_transforms(&locals)
+}
+
+pub struct AnimationPlayer {
+    pub animations: Vec<Animation>,
+    pub nodes: NodeTree,
+    pub selected: usize,
+    pub playing: bool,
+    // Multiplies how fast the clock runs
+    pub speed: f32,
+    // Seconds into the selected animation
+    pub time: f32,
+    last_update: Option<f32>,
+}
+
+impl AnimationPlayer {
+    pub fn new(animations: Vec<Animation>, nodes: NodeTree) -> Self {
+        Self {
+            animations,
+            nodes,
+            selected: 0,
+            playing: true,
+            speed: 1.,
+            time: 0.,
+            last_update: None,
+        }
+    }
+
+    // Advances the clock to `now` (seconds since start) and returns the posed nodes,
+    // or None while paused since nothing moves
+    pub fn update(&mut self, now: f32) -> Option<Vec<Option<Mat4>>> {
+        let elapsed = now - self.last_update.unwrap_or(now);
+        self.last_update = Some(now);
+        let animation = self.animations.get(self.selected)?;
+        if !self.playing {
+            return None;
+        }
+
+        self.time += elapsed * self.speed;
+        if animation.duration > 0. {
+            self.time %= animation.duration;
+        } else {
+            self.time = 0.;
+        }
+        Some(pose(animation, &self.nodes, self.time))
+    }
+
+    // Where the nodes are at the current time, without moving the clock
+    pub fn current_pose(&self) -> Vec<Option<Mat4>> {
+        match self.animations.get(self.selected) {
+            Some(animation) => pose(animation, &self.nodes, self.time),
+            None => self.nodes.rest_pose(),
+        }
+    }
+}
+
+// Bones in world space, painted over the scene so they show through the mesh. Uses last
+// frame's camera, like the gizmo.
+pub fn bones_overlay(
+    ctx: &egui::CtxRef,
+    bones: &[(Vec3, Vec3)],
+    view: Mat4,
+    projection: CameraProjection,
+) {
+    let screen = ctx.input().screen_rect();
+    let view_projection = projection::matrix(projection, screen.width() / screen.height()) * view;
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for &(from, to) in bones {
+        let from = gizmo::to_screen(view_projection, screen, from);
+        let to = gizmo::to_screen(view_projection, screen, to);
+        if let (Some(from), Some(to)) = (from, to) {
+            painter.line_segment([from, to], egui::Stroke::new(2., BONE_COLOR));
+            painter.circle_filled(from, 3., BONE_COLOR);
+        }
+    }
+}
+
+pub fn animation_ui(ui: &mut egui::Ui, player: &mut AnimationPlayer) {
+    let selected_name = player
+        .animations
+        .get(player.selected)
+        .map_or("None", |animation| animation.name.as_str());
+    let previous = player.selected;
+    egui::ComboBox::from_label("Animation")
+        .selected_text(selected_name)
+        .show_ui(ui, |ui| {
+            for (index, animation) in player.animations.iter().enumerate() {
+                ui.selectable_value(&mut player.selected, index, animation.name.as_str());
+            }
+        });
+    if player.selected != previous {
+        player.time = 0.;
+    }
+
+    ui.horizontal(|ui| {
+        let label = if player.playing { "Pause" } else { "Play" };
+        if ui.button(label).clicked() {
+            player.playing = !player.playing;
+        }
+        ui.label(format!("{:.2}s", player.time));
+    });
+    ui.add(egui::Slider::new(&mut player.speed, 0.0..=4.0).text("Speed"));
+}
@@ -0,0 +1,332 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+use gltf::json;
+use gltf::json::validation::Checked::Valid;
+use std::path::Path;
+
+use super::mesh_generator::MeshData;
+use super::scene::PbrFactors;
+
+pub struct ExportObject<'a> {
+    pub name: &'a str,
+    pub mesh: &'a MeshData,
+    // In glTF's right handed space, the caller takes it out of the app's
+    pub transform: Mat4,
+    pub factors: PbrFactors,
+    // Labelled the way the importer reads them, "Node ...", "Mesh ..." or "Material ..."
+    pub extras: &'a [(String, String)],
+}
+
+const GLB_VERSION: u32 = 2;
+
+// Everything goes into one binary buffer, each attribute gets its own view and accessor
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    views: Vec<json::buffer::View>,
+    accessors: Vec<json::Accessor>,
+}
+
+impl Buffer {
+    fn push(
+        &mut self,
+        bytes: Vec<u8>,
+        target: json::buffer::Target,
+        count: usize,
+        component_type: json::accessor::ComponentType,
+        type_: json::accessor::Type,
+        bounds: Option<(Vec3, Vec3)>,
+    ) -> json::Index<json::Accessor> {
+        // Accessors need their components aligned
+        while self.data.len() % 4 != 0 {
+            self.data.push(0);
+        }
+        self.views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: bytes.len() as u32,
+            byte_offset: Some(self.data.len() as u32),
+            byte_stride: None,
+            name: None,
+            target: Some(Valid(target)),
+            extensions: Default::default(),
+            extras: Default::default(),
+        });
+        self.data.extend(bytes);
+
+        let bound = |value: Vec3| json::Value::from(value.to_array().to_vec());
+        self.accessors.push(json::Accessor {
+            buffer_view: Some(json::Index::new(self.views.len() as u32 - 1)),
+            byte_offset: 0,
+            count: count as u32,
+            component_type: Valid(json::accessor::GenericComponentType(component_type)),
+            type_: Valid(type_),
+            min: bounds.map(|(min, _)| bound(min)),
+            max: bounds.map(|(_, max)| bound(max)),
+            name: None,
+            normalized: false,
+            sparse: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+        });
+        json::Index::new(self.accessors.len() as u32 - 1)
+    }
+
+    fn push_vec3(&mut self, values: &[Vec3]) -> json::Index<json::Accessor> {
+        // Only required for positions, but cheap enough to always write
+        let min = values.iter().fold(Vec3::splat(f32::MAX), |a, &b| a.min(b));
+        let max = values.iter().fold(Vec3::splat(f32::MIN), |a, &b| a.max(b));
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        self.push(
+            bytes,
+            json::buffer::Target::ArrayBuffer,
+            values.len(),
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            Some((min, max)),
+        )
+    }
+
+    fn push_vec2(&mut self, values: &[Vec2]) -> json::Index<json::Accessor> {
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        self.push(
+            bytes,
+            json::buffer::Target::ArrayBuffer,
+            values.len(),
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec2,
+            None,
+        )
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> json::Index<json::Accessor> {
+        let bytes = indices.iter().copied().flat_map(u32::to_le_bytes).collect();
+        self.push(
+            bytes,
+            json::buffer::Target::ElementArrayBuffer,
+            indices.len(),
+            json::accessor::ComponentType::U32,
+            json::accessor::Type::Scalar,
+            None,
+        )
+    }
+}
+
+// The object's extras that were read from a `kind`, to go back on the same kind of thing
+fn extras(object: &ExportObject, kind: &str) -> json::Extras {
+    let (_, value) = object
+        .extras
+        .iter()
+        .find(|(label, _)| label.split(' ').next() == Some(kind))?;
+    json::extras::RawValue::from_string(value.clone()).ok()
+}
+
+fn node(name: String) -> json::Node {
+    json::Node {
+        camera: None,
+        children: None,
+        matrix: None,
+        mesh: None,
+        name: Some(name),
+        rotation: None,
+        scale: None,
+        translation: None,
+        skin: None,
+        weights: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+    }
+}
+
+// Header, then the JSON chunk padded with spaces and the binary chunk padded with zeros
+fn write_glb(path: &Path, root: &json::Root, mut bin: Vec<u8>) -> Result<(), String> {
+    let mut json = json::serialize::to_vec(root).map_err(|e| e.to_string())?;
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    let mut glb = Vec::with_capacity(length);
+    glb.extend(b"glTF");
+    glb.extend(GLB_VERSION.to_le_bytes());
+    glb.extend((length as u32).to_le_bytes());
+    for (chunk, kind) in [(&json, b"JSON"), (&bin, b"BIN\0")] {
+        glb.extend((chunk.len() as u32).to_le_bytes());
+        glb.extend(kind);
+        glb.extend(chunk);
+    }
+    std::fs::write(path, glb).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Writes a .glb, or a .gltf with its geometry in a .bin next to it for any other
+// extension. Lights are written as KHR_lights_punctual directional lights, with their
+// directions in glTF's space like the transforms.
+//
+// Extras go back on the node, mesh or material they were read from.
+//
+// Skinned meshes go out in their rest pose without their skin, and textures are left out.
+// So are tangents, MeshData doesn't keep their handedness and importers can make them again.
+pub fn export_gltf(
+    path: &Path,
+    objects: &[ExportObject],
+    lights: &[rend3::types::DirectionalLight],
+) -> Result<(), String> {
+    let mut buffer = Buffer::default();
+    let mut root = json::Root {
+        asset: json::Asset {
+            generator: Some("Project Heaven".to_string()),
+            ..json::Asset::default()
+        },
+        ..json::Root::default()
+    };
+
+    for object in objects {
+        // MeshData keeps the layout it was imported with, so it goes back out untouched
+        let mut attributes = vec![(
+            json::mesh::Semantic::Positions,
+            buffer.push_vec3(&object.mesh.positions),
+        )];
+        if !object.mesh.normals.is_empty() {
+            attributes.push((
+                json::mesh::Semantic::Normals,
+                buffer.push_vec3(&object.mesh.normals),
+            ));
+        }
+        if !object.mesh.uvs.is_empty() {
+            attributes.push((
+                json::mesh::Semantic::TexCoords(0),
+                buffer.push_vec2(&object.mesh.uvs),
+            ));
+        }
+        if !object.mesh.uvs1.is_empty() {
+            attributes.push((
+                json::mesh::Semantic::TexCoords(1),
+                buffer.push_vec2(&object.mesh.uvs1),
+            ));
+        }
+        let indices = buffer.push_indices(&object.mesh.indices);
+
+        let factors = object.factors;
+        root.materials.push(json::Material {
+            name: Some(object.name.to_string()),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(factors.base_color),
+                metallic_factor: json::material::StrengthFactor(factors.metallic),
+                roughness_factor: json::material::StrengthFactor(factors.roughness),
+                ..json::material::PbrMetallicRoughness::default()
+            },
+            emissive_factor: json::material::EmissiveFactor(factors.emissive),
+            alpha_mode: Valid(if factors.base_color[3] < 1. {
+                json::material::AlphaMode::Blend
+            } else {
+                json::material::AlphaMode::Opaque
+            }),
+            extras: extras(object, "Material"),
+            ..json::Material::default()
+        });
+        root.meshes.push(json::Mesh {
+            name: Some(object.name.to_string()),
+            primitives: vec![json::mesh::Primitive {
+                attributes: attributes
+                    .into_iter()
+                    .map(|(semantic, accessor)| (Valid(semantic), accessor))
+                    .collect(),
+                indices: Some(indices),
+                material: Some(json::Index::new(root.materials.len() as u32 - 1)),
+                mode: Valid(json::mesh::Mode::Triangles),
+                targets: None,
+                extensions: Default::default(),
+                extras: Default::default(),
+            }],
+            weights: None,
+            extensions: Default::default(),
+            extras: extras(object, "Mesh"),
+        });
+        root.nodes.push(json::Node {
+            mesh: Some(json::Index::new(root.meshes.len() as u32 - 1)),
+            matrix: Some(object.transform.to_cols_array()),
+            extras: extras(object, "Node"),
+            ..node(object.name.to_string())
+        });
+    }
+
+    let mut gltf_lights = Vec::new();
+    for light in lights {
+        // glTF directional lights shine down their node's -Z
+        let rotation = Quat::from_rotation_arc(Vec3::NEG_Z, light.direction.normalize());
+        gltf_lights.push(json::extensions::scene::khr_lights_punctual::Light {
+            color: light.color.to_array(),
+            intensity: light.intensity,
+            name: None,
+            range: None,
+            spot: None,
+            type_: Valid(json::extensions::scene::khr_lights_punctual::Type::Directional),
+            extensions: Default::default(),
+            extras: Default::default(),
+        });
+        let index = json::Index::new(gltf_lights.len() as u32 - 1);
+        root.nodes.push(json::Node {
+            rotation: Some(json::scene::UnitQuaternion(rotation.to_array())),
+            extensions: Some(json::extensions::scene::Node {
+                khr_lights_punctual: Some(
+                    json::extensions::scene::khr_lights_punctual::KhrLightsPunctual {
+                        light: index,
+                    },
+                ),
+            }),
+            ..node(format!("Light {}", gltf_lights.len() - 1))
+        });
+    }
+
+    root.extensions_used = vec!["KHR_lights_punctual".to_string()];
+    root.extensions = Some(json::extensions::root::Root {
+        khr_lights_punctual: Some(json::extensions::root::KhrLightsPunctual {
+            lights: gltf_lights,
+        }),
+        ..json::extensions::root::Root::default()
+    });
+    root.scenes = vec![json::Scene {
+        name: None,
+        nodes: (0..root.nodes.len() as u32).map(json::Index::new).collect(),
+        extensions: Default::default(),
+        extras: Default::default(),
+    }];
+    root.scene = Some(json::Index::new(0));
+    root.accessors = buffer.accessors;
+    root.buffer_views = buffer.views;
+    let mut gltf_buffer = json::Buffer {
+        byte_length: buffer.data.len() as u32,
+        name: None,
+        uri: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+    };
+
+    let binary = path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("glb"));
+    if binary {
+        root.buffers = vec![gltf_buffer];
+        return write_glb(path, &root, buffer.data);
+    }
+    let bin_path = path.with_extension("bin");
+    let bin_name = bin_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid path {}", path.display()))?;
+    gltf_buffer.uri = Some(bin_name.to_string());
+    root.buffers = vec![gltf_buffer];
+
+    std::fs::write(&bin_path, &buffer.data)
+        .map_err(|e| format!("Failed to write {}: {}", bin_path.display(), e))?;
+    let text = json::serialize::to_string_pretty(&root).map_err(|e| e.to_string())?;
+    std::fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,132 @@
+use glam::{Mat4, UVec2, Vec3, Vec4};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::cli::{Backend, CameraPose, Cli};
+use super::mesh_generator;
+use super::mesh_importer::{self, NormalsMode};
+use super::scene::SceneObject;
+use super::screenshot::ScreenshotTarget;
+
+// One frame of a glTF model rendered without a window, e.g. for asset previews
+pub struct HeadlessArgs {
+    pub model: PathBuf,
+    pub output: PathBuf,
+    pub size: UVec2,
+    // Both default to framing the whole model from the front. A camera with angles looks
+    // along them when there is no look-at point.
+    pub camera: Option<CameraPose>,
+    pub look_at: Option<Vec3>,
+    pub vfov: f32,
+    pub backend: Option<wgpu::Backend>,
+}
+
+impl HeadlessArgs {
+    pub fn from_cli(cli: &Cli) -> Result<Self, String> {
+        let model = cli
+            .gltf
+            .clone()
+            .ok_or_else(|| "--headless needs a model to render, pass it with --gltf".to_string())?;
+        let vfov = cli.fov.unwrap_or(60.);
+        if vfov <= 0. || vfov >= 180. {
+            return Err(format!("Invalid value for --fov: {}", vfov));
+        }
+        Ok(Self {
+            output: cli
+                .output
+                .clone()
+                .unwrap_or_else(|| model.with_extension("png")),
+            model,
+            size: UVec2::new(cli.width.unwrap_or(512), cli.height.unwrap_or(512)),
+            camera: cli.camera,
+            look_at: cli.look_at,
+            vfov,
+            backend: cli.backend.map(Backend::wgpu),
+        })
+    }
+}
+
+// Renders the model the way the app shows the station, lit by the default sun, and
+// writes the frame to the output PNG
+pub fn render(args: &HeadlessArgs) -> Result<(), String> {
+    let iad = pollster::block_on(rend3::create_iad(args.backend, None, None, None))
+        .map_err(|ex| format!("No usable GPU: {}", ex))?;
+    let renderer = rend3::Renderer::new(
+        iad,
+        rend3::types::Handedness::Left,
+        Some(args.size.x as f32 / args.size.y as f32),
+    )
+    .map_err(|ex| format!("Failed to create the renderer: {}", ex))?;
+
+    // Same routines the framework sets up for the window
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let base_rendergraph = rend3_routine::base::BaseRenderGraph::new(&renderer);
+    let mut data_core = renderer.data_core.lock();
+    let pbr_routine = rend3_routine::pbr::PbrRoutine::new(
+        &renderer,
+        &mut data_core,
+        &base_rendergraph.interfaces,
+    );
+    drop(data_core);
+    let tonemapping_routine = rend3_routine::tonemapping::TonemappingRoutine::new(
+        &renderer,
+        &base_rendergraph.interfaces,
+        format,
+    );
+
+    let model = mesh_importer::load_gltf(&renderer, &args.model, NormalsMode::RecomputeIfMissing)
+        .map_err(|ex| ex.to_string())?;
+    let transform = super::gltf_scene_root();
+    let bounds = model
+        .mesh_data
+        .bounds()
+        .map(|bounds| mesh_generator::transform_bounds(bounds, transform))
+        .unwrap_or((Vec3::ZERO, Vec3::ZERO));
+    let _object = SceneObject::new(&renderer, model.mesh, model.material, transform);
+    let _sun = renderer.add_directional_light(super::lighting::sun());
+
+    // Far enough back along -Z for the bounding sphere to fit, like framing the scene
+    let center = (bounds.0 + bounds.1) / 2.;
+    let radius = ((bounds.1 - bounds.0).length() / 2.).max(0.5);
+    let camera = args.camera.map_or_else(
+        || {
+            let distance = radius * 1.1 / (args.vfov.to_radians() / 2.).sin();
+            center + Vec3::new(0., 0.3, -1.).normalize() * distance
+        },
+        |pose| pose.location,
+    );
+    let look_at = match (args.look_at, args.camera) {
+        (Some(look_at), _) => look_at,
+        (None, Some(pose)) if pose.angles.is_some() => camera + pose.forward(),
+        (None, _) => center,
+    };
+    renderer.set_camera_data(rend3::types::Camera {
+        projection: rend3::types::CameraProjection::Perspective {
+            vfov: args.vfov,
+            near: 0.1,
+        },
+        view: Mat4::look_at_lh(camera, look_at, Vec3::Y),
+    });
+
+    let target = ScreenshotTarget::new(&renderer, args.size, format);
+    let (cmd_bufs, ready) = renderer.ready();
+    let mut graph = rend3::graph::RenderGraph::new();
+    base_rendergraph.add_to_graph(
+        &mut graph,
+        &ready,
+        &pbr_routine,
+        None,
+        &tonemapping_routine,
+        args.size,
+        rend3::types::SampleCount::One,
+        Vec4::splat(0.),
+    );
+    graph.execute(
+        &renderer,
+        rend3::util::output::OutputFrame::View(Arc::clone(&target.view)),
+        cmd_bufs,
+        &ready,
+    );
+
+    target.save_as(&renderer, &args.output)
+}
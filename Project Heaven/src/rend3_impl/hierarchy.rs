@@ -0,0 +1,118 @@
+use glam::{EulerRot, Mat4, Quat, Vec3};
+
+use super::gizmo::GizmoMode;
+use super::lod;
+use super::render_mode::RenderMode;
+use super::scene::SceneObject;
+
+// Side panel listing `objects`, with the transform of the selected one editable.
+// Rotation is shown as yaw, pitch and roll in degrees.
+pub fn hierarchy_ui(
+    ctx: &egui::CtxRef,
+    renderer: &rend3::Renderer,
+    objects: &mut [&mut SceneObject],
+    selected: &mut Option<usize>,
+    gizmo_mode: &mut GizmoMode,
+    first_deletable: usize,
+) -> Option<usize> {
+    // The list can shrink under the selection when a scene is cleared
+    if selected.map_or(false, |index| index >= objects.len()) {
+        *selected = None;
+    }
+
+    let mut deleted = None;
+    egui::SidePanel::right("hierarchy")
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.heading("Scene");
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for (index, object) in objects.iter().enumerate() {
+                        let name = if object.name.is_empty() {
+                            "Unnamed"
+                        } else {
+                            object.name.as_str()
+                        };
+                        if ui
+                            .selectable_label(*selected == Some(index), name)
+                            .clicked()
+                        {
+                            *selected = Some(index);
+                        }
+                    }
+                });
+
+            let object = match *selected {
+                Some(index) => &mut objects[index],
+                None => return,
+            };
+            ui.separator();
+            // Only added primitives can be deleted, everything else comes from a file
+            if selected.map_or(false, |index| index >= first_deletable)
+                && ui.button("Delete").clicked()
+            {
+                deleted = *selected;
+            }
+
+            egui::ComboBox::from_label("Render mode")
+                .selected_text(object.render_mode.map_or("Global", RenderMode::name))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut object.render_mode, None, "Global");
+                    for mode in RenderMode::ALL {
+                        ui.selectable_value(&mut object.render_mode, Some(mode), mode.name());
+                    }
+                });
+            lod::lod_ui(ui, renderer, object);
+
+            ui.horizontal(|ui| {
+                for mode in GizmoMode::ALL {
+                    ui.selectable_value(gizmo_mode, mode, mode.name());
+                }
+            });
+            let (scale, rotation, translation) = object.transform.to_scale_rotation_translation();
+            let (yaw, pitch, roll) = rotation.to_euler(EulerRot::YXZ);
+            let mut translation = translation.to_array();
+            let mut angles = [yaw.to_degrees(), pitch.to_degrees(), roll.to_degrees()];
+            let mut scale = scale.to_array();
+
+            let mut changed = false;
+            egui::Grid::new("transform").show(ui, |ui| {
+                ui.label("Translation");
+                for value in &mut translation {
+                    changed |= ui.add(egui::DragValue::new(value).speed(0.1)).changed();
+                }
+                ui.end_row();
+                ui.label("Rotation");
+                for value in &mut angles {
+                    changed |= ui
+                        .add(egui::DragValue::new(value).speed(1.).suffix("°"))
+                        .changed();
+                }
+                ui.end_row();
+                ui.label("Scale");
+                for value in &mut scale {
+                    changed |= ui.add(egui::DragValue::new(value).speed(0.01)).changed();
+                }
+                ui.end_row();
+            });
+
+            if changed {
+                let rotation = Quat::from_euler(
+                    EulerRot::YXZ,
+                    angles[0].to_radians(),
+                    angles[1].to_radians(),
+                    angles[2].to_radians(),
+                );
+                object.set_transform(
+                    renderer,
+                    Mat4::from_scale_rotation_translation(
+                        Vec3::from(scale),
+                        rotation,
+                        Vec3::from(translation),
+                    ),
+                );
+            }
+        });
+    deleted
+}
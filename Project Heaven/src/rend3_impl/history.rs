@@ -0,0 +1,436 @@
+use glam::Mat4;
+use rend3::types::DirectionalLight;
+
+use super::lighting;
+use super::material_animation::SurfaceParams;
+use super::mesh_generator::Primitive;
+use super::RenderingData;
+use super::{hierarchy_object, hierarchy_objects, material_target};
+use super::{refresh_ship_material, spawn_primitive};
+
+// Oldest steps are dropped past this many
+const MAX_HISTORY: usize = 100;
+
+// One undoable step. Commands only hold values, never handles, so an undone spawn doesn't
+// keep its object alive in the renderer.
+pub trait EditorCommand {
+    // What the History panel lists it as
+    fn name(&self) -> String;
+    fn apply(&self, data: &mut RenderingData, renderer: &rend3::Renderer);
+    fn revert(&self, data: &mut RenderingData, renderer: &rend3::Renderer);
+}
+
+// Objects are numbered the way the scene panel lists them
+pub struct TransformEdit {
+    pub name: String,
+    pub object: usize,
+    pub before: Mat4,
+    pub after: Mat4,
+}
+
+impl EditorCommand for TransformEdit {
+    fn name(&self) -> String {
+        format!("Transform {}", self.name)
+    }
+
+    fn apply(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        if let Some(object) = hierarchy_object(data, self.object) {
+            object.set_transform(renderer, self.after);
+        }
+    }
+
+    fn revert(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        if let Some(object) = hierarchy_object(data, self.object) {
+            object.set_transform(renderer, self.before);
+        }
+    }
+}
+
+// The values the material editor sets, textures aren't part of it. `object` is numbered
+// the way the scene panel lists them, None for the ship.
+#[derive(Clone, PartialEq)]
+pub struct MaterialState {
+    pub object: Option<usize>,
+    pub color: [f32; 4],
+    pub roughness: f32,
+    pub metallic: f32,
+    pub surface: SurfaceParams,
+}
+
+impl MaterialState {
+    fn of(data: &RenderingData, object: Option<usize>) -> Option<Self> {
+        let index = match object {
+            Some(index) => index,
+            None => {
+                return Some(Self {
+                    object,
+                    color: data.color,
+                    roughness: data.roughness,
+                    metallic: data.metallic,
+                    surface: data.surface.clone(),
+                })
+            }
+        };
+        let scene_object = hierarchy_objects(data).nth(index)?;
+        Some(Self {
+            object,
+            color: scene_object.factors.base_color,
+            roughness: scene_object.factors.roughness,
+            metallic: scene_object.factors.metallic,
+            surface: scene_object.surface(),
+        })
+    }
+
+    // An object that still had the material it came with gets one made from the same
+    // factors, without its textures
+    fn restore(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        let index = match self.object {
+            Some(index) => index,
+            None => {
+                data.color = self.color;
+                data.roughness = self.roughness;
+                data.metallic = self.metallic;
+                data.surface = self.surface.clone();
+                refresh_ship_material(data, renderer);
+                return;
+            }
+        };
+        let clay = data.clay_mode.then(|| data.clay_material_handle.clone());
+        if let Some(object) = hierarchy_object(data, index) {
+            object.factors.base_color = self.color;
+            object.factors.roughness = self.roughness;
+            object.factors.metallic = self.metallic;
+            object.set_material(renderer, self.surface.clone(), clay.as_ref());
+        }
+    }
+}
+
+pub struct MaterialEdit {
+    // Of the object, or the ship
+    pub name: String,
+    pub before: MaterialState,
+    pub after: MaterialState,
+}
+
+impl EditorCommand for MaterialEdit {
+    fn name(&self) -> String {
+        format!("{} material", self.name)
+    }
+
+    fn apply(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        self.after.restore(data, renderer);
+    }
+
+    fn revert(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        self.before.restore(data, renderer);
+    }
+}
+
+// Light 0 is the sun, the extra lights follow
+pub struct LightEdit {
+    pub light: usize,
+    pub before: DirectionalLight,
+    pub after: DirectionalLight,
+}
+
+fn set_light(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    index: usize,
+    light: &DirectionalLight,
+) {
+    let (handle, target) = match index {
+        0 => (&data.sun_handle, &mut data.sun),
+        _ => match data.extra_lights.get_mut(index - 1) {
+            Some(extra) => (&extra.handle, &mut extra.light),
+            None => return,
+        },
+    };
+    *target = light.clone();
+    lighting::update_light(renderer, handle, target);
+}
+
+impl EditorCommand for LightEdit {
+    fn name(&self) -> String {
+        match self.light {
+            0 => "Sun".to_string(),
+            index => format!("Light {}", index),
+        }
+    }
+
+    fn apply(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        set_light(data, renderer, self.light, &self.after);
+    }
+
+    fn revert(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        set_light(data, renderer, self.light, &self.before);
+    }
+}
+
+// A primitive added from the menu, `index` is its place among the spawned objects
+#[derive(Clone)]
+pub struct SpawnedPrimitive {
+    pub index: usize,
+    pub name: String,
+    pub primitive: Primitive,
+    pub transform: Mat4,
+}
+
+impl SpawnedPrimitive {
+    pub fn of(data: &RenderingData, index: usize) -> Self {
+        Self {
+            index,
+            name: data.scene.spawned[index].name.clone(),
+            primitive: data.scene.spawned_primitives[index],
+            transform: data.scene.spawned[index].transform,
+        }
+    }
+
+    fn insert(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        spawn_primitive(
+            data,
+            renderer,
+            self.primitive,
+            self.name.clone(),
+            self.transform,
+        );
+        // Spawning appends, so it's moved to where it was
+        let index = self.index.min(data.scene.spawned.len() - 1);
+        let object = data.scene.spawned.pop().unwrap();
+        let primitive = data.scene.spawned_primitives.pop().unwrap();
+        data.scene.spawned.insert(index, object);
+        data.scene.spawned_primitives.insert(index, primitive);
+    }
+
+    fn remove(&self, data: &mut RenderingData) {
+        if self.index < data.scene.spawned.len() {
+            data.scene.spawned.remove(self.index);
+            data.scene.spawned_primitives.remove(self.index);
+            data.selected_object = None;
+        }
+    }
+}
+
+pub struct Spawn(pub SpawnedPrimitive);
+
+impl EditorCommand for Spawn {
+    fn name(&self) -> String {
+        format!("Add {}", self.0.name)
+    }
+
+    fn apply(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        self.0.insert(data, renderer);
+    }
+
+    fn revert(&self, data: &mut RenderingData, _: &rend3::Renderer) {
+        self.0.remove(data);
+    }
+}
+
+pub struct Delete(pub SpawnedPrimitive);
+
+impl EditorCommand for Delete {
+    fn name(&self) -> String {
+        format!("Delete {}", self.0.name)
+    }
+
+    fn apply(&self, data: &mut RenderingData, _: &rend3::Renderer) {
+        self.0.remove(data);
+    }
+
+    fn revert(&self, data: &mut RenderingData, renderer: &rend3::Renderer) {
+        self.0.insert(data, renderer);
+    }
+}
+
+// What the UI can change without going through a command, taken before the UI runs.
+// Edits are only turned into commands once the pointer is let go, so a drag is one step.
+pub struct Snapshot {
+    transform: Option<(usize, Mat4)>,
+    material: Option<MaterialState>,
+    lights: Vec<DirectionalLight>,
+}
+
+fn lights(data: &RenderingData) -> Vec<DirectionalLight> {
+    std::iter::once(data.sun.clone())
+        .chain(data.extra_lights.iter().map(|extra| extra.light.clone()))
+        .collect()
+}
+
+fn same_light(a: &DirectionalLight, b: &DirectionalLight) -> bool {
+    a.color == b.color
+        && a.intensity == b.intensity
+        && a.direction == b.direction
+        && a.distance == b.distance
+}
+
+#[derive(Default)]
+pub struct History {
+    done: Vec<Box<dyn EditorCommand>>,
+    undone: Vec<Box<dyn EditorCommand>>,
+    // Values from before an edit that is still going on
+    pending_transform: Option<(usize, Mat4)>,
+    pending_material: Option<MaterialState>,
+    pending_light: Option<(usize, DirectionalLight)>,
+}
+
+impl History {
+    // For edits that have already been made
+    pub fn push(&mut self, command: Box<dyn EditorCommand>) {
+        self.undone.clear();
+        self.done.push(command);
+        if self.done.len() > MAX_HISTORY {
+            self.done.remove(0);
+        }
+    }
+
+    // Loading a scene or clearing a list renumbers the objects the commands point at
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub fn snapshot(data: &RenderingData) -> Snapshot {
+    Snapshot {
+        transform: data.selected_object.and_then(|index| {
+            let object = hierarchy_objects(data).nth(index)?;
+            Some((index, object.transform))
+        }),
+        material: MaterialState::of(data, material_target(data)),
+        lights: lights(data),
+    }
+}
+
+pub fn run(data: &mut RenderingData, renderer: &rend3::Renderer, command: Box<dyn EditorCommand>) {
+    command.apply(data, renderer);
+    data.history.push(command);
+}
+
+pub fn undo(data: &mut RenderingData, renderer: &rend3::Renderer) {
+    if let Some(command) = data.history.done.pop() {
+        command.revert(data, renderer);
+        data.history.undone.push(command);
+    }
+}
+
+pub fn redo(data: &mut RenderingData, renderer: &rend3::Renderer) {
+    if let Some(command) = data.history.undone.pop() {
+        command.apply(data, renderer);
+        data.history.done.push(command);
+    }
+}
+
+// Compares against the snapshot taken before the UI ran, and records whatever edit has
+// finished once `dragging` is over
+pub fn track(data: &mut RenderingData, before: Snapshot, dragging: bool) {
+    let current = snapshot(data);
+    let snapshot = before;
+
+    // Lights were added or removed, the numbers in the light commands don't fit any more
+    if current.lights.len() != snapshot.lights.len() {
+        data.history.clear();
+        return;
+    }
+
+    let history = &mut data.history;
+    if let (Some(before), Some(now)) = (snapshot.transform, current.transform) {
+        if before.0 == now.0 && before.1 != now.1 && history.pending_transform.is_none() {
+            history.pending_transform = Some(before);
+        }
+    }
+    if let (Some(before), Some(now)) = (snapshot.material, &current.material) {
+        if before.object == now.object && before != *now && history.pending_material.is_none() {
+            history.pending_material = Some(before);
+        }
+    }
+    if history.pending_light.is_none() {
+        history.pending_light = snapshot
+            .lights
+            .iter()
+            .zip(&current.lights)
+            .position(|(before, now)| !same_light(before, now))
+            .map(|index| (index, snapshot.lights[index].clone()));
+    }
+    if dragging {
+        return;
+    }
+
+    if let Some((index, before)) = history.pending_transform.take() {
+        if let Some(object) = hierarchy_objects(data).nth(index) {
+            if object.transform != before {
+                let command = TransformEdit {
+                    name: object.name.clone(),
+                    object: index,
+                    before,
+                    after: object.transform,
+                };
+                data.history.push(Box::new(command));
+            }
+        }
+    }
+    if let Some(before) = data.history.pending_material.take() {
+        let name = match before.object {
+            Some(index) => hierarchy_objects(data)
+                .nth(index)
+                .map(|object| object.name.clone()),
+            None => Some("Ship".to_string()),
+        };
+        if let (Some(name), Some(after)) = (name, MaterialState::of(data, before.object)) {
+            if before != after {
+                data.history.push(Box::new(MaterialEdit {
+                    name,
+                    before,
+                    after,
+                }));
+            }
+        }
+    }
+    if let Some((index, before)) = data.history.pending_light.take() {
+        let after = current.lights[index].clone();
+        if !same_light(&before, &after) {
+            data.history.push(Box::new(LightEdit {
+                light: index,
+                before,
+                after,
+            }));
+        }
+    }
+}
+
+pub fn history_window(ctx: &egui::CtxRef, data: &mut RenderingData, renderer: &rend3::Renderer) {
+    let mut open = data.show_history;
+    egui::Window::new("History")
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!data.history.done.is_empty(), egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    undo(data, renderer);
+                }
+                if ui
+                    .add_enabled(!data.history.undone.is_empty(), egui::Button::new("Redo"))
+                    .clicked()
+                {
+                    redo(data, renderer);
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    if data.history.done.is_empty() && data.history.undone.is_empty() {
+                        ui.label("Nothing to undo");
+                    }
+                    for command in &data.history.done {
+                        ui.label(command.name());
+                    }
+                    // Last undone is redone first, so it is listed first
+                    for command in data.history.undone.iter().rev() {
+                        ui.label(egui::RichText::new(command.name()).weak());
+                    }
+                });
+        });
+    data.show_history &= open;
+}
@@ -0,0 +1,173 @@
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::asset_loader::LoadTarget;
+use super::mesh_import;
+
+// Editors often save in bursts, a change only counts once the file has been quiet this long
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+// The file itself and whatever it pulls in from beside it, buffers and textures. Read by the
+// asset loader with the file, so opening it again stays off the main thread.
+pub fn dependencies(path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut paths = vec![path.to_path_buf()];
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => {
+            // Only the JSON is parsed, nothing is decoded
+            if let Ok(gltf) = gltf::Gltf::open(path) {
+                let buffers = gltf.buffers().filter_map(|buffer| match buffer.source() {
+                    gltf::buffer::Source::Uri(uri) => Some(uri),
+                    gltf::buffer::Source::Bin => None,
+                });
+                let images = gltf.images().filter_map(|image| match image.source() {
+                    gltf::image::Source::Uri { uri, .. } => Some(uri),
+                    gltf::image::Source::View { .. } => None,
+                });
+                paths.extend(
+                    buffers
+                        .chain(images)
+                        .filter(|uri| !uri.starts_with("data:"))
+                        .map(|uri| directory.join(uri.replace("%20", " "))),
+                );
+            }
+        }
+        Some("obj") => paths.extend(mesh_import::obj_dependencies(path)),
+        _ => {}
+    }
+    // Watchers report absolute paths, so these have to be too
+    paths
+        .into_iter()
+        .map(|path| std::fs::canonicalize(&path).unwrap_or(path))
+        .collect()
+}
+
+struct WatchedAsset {
+    target: LoadTarget,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+// Watches the station's and the scene's files, and the ones they reference. Directories
+// are watched instead of the files, which editors often replace rather than write to.
+pub struct AssetWatcher {
+    watcher: RecommendedWatcher,
+    receiver: mpsc::Receiver<DebouncedEvent>,
+    // What set_assets was last called with
+    requested: Vec<(LoadTarget, Option<(PathBuf, Vec<PathBuf>)>)>,
+    assets: Vec<WatchedAsset>,
+    directories: HashSet<PathBuf>,
+    // Assets that changed and still have to be reloaded
+    changed: Vec<LoadTarget>,
+}
+
+impl AssetWatcher {
+    pub fn new() -> Result<Self, String> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher: RecommendedWatcher =
+            Watcher::new(sender, SETTLE_TIME).map_err(|ex| ex.to_string())?;
+        Ok(Self {
+            watcher,
+            receiver,
+            requested: Vec::new(),
+            assets: Vec::new(),
+            directories: HashSet::new(),
+            changed: Vec::new(),
+        })
+    }
+
+    // Each asset with where it is and the files it was read from, as `dependencies` found
+    // them. Cheap when nothing was opened, closed or reloaded since the last call.
+    pub fn set_assets(&mut self, assets: &[(LoadTarget, Option<(&Path, &[PathBuf])>)]) {
+        let unchanged = assets.len() == self.requested.len()
+            && assets.iter().zip(&self.requested).all(
+                |((target, asset), (requested_target, requested))| {
+                    let requested = requested
+                        .as_ref()
+                        .map(|(path, files)| (path.as_path(), files.as_slice()));
+                    target == requested_target && *asset == requested
+                },
+            );
+        if unchanged {
+            return;
+        }
+        self.requested = assets
+            .iter()
+            .map(|&(target, asset)| {
+                let asset = asset.map(|(path, files)| (path.to_path_buf(), files.to_vec()));
+                (target, asset)
+            })
+            .collect();
+
+        self.assets = self
+            .requested
+            .iter()
+            .filter_map(|(target, asset)| {
+                let (path, files) = asset.as_ref()?;
+                Some(WatchedAsset {
+                    target: *target,
+                    path: path.clone(),
+                    files: files.clone(),
+                })
+            })
+            .collect();
+        let assets = &self.assets;
+        self.changed
+            .retain(|target| assets.iter().any(|asset| asset.target == *target));
+        self.update_directories();
+    }
+
+    fn update_directories(&mut self) {
+        let wanted: HashSet<PathBuf> = self
+            .assets
+            .iter()
+            .flat_map(|asset| asset.files.iter())
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .collect();
+        for directory in self.directories.difference(&wanted) {
+            let _ = self.watcher.unwatch(directory);
+        }
+        let mut watched = HashSet::new();
+        for directory in wanted {
+            if self.directories.contains(&directory) {
+                watched.insert(directory);
+                continue;
+            }
+            match self.watcher.watch(&directory, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    watched.insert(directory);
+                }
+                Err(ex) => tracing::error!("{}: {}", directory.display(), ex),
+            }
+        }
+        self.directories = watched;
+    }
+
+    // The next asset to reload and where it is, once per change. The reload reads its files
+    // again, the new version may reference other ones than the old one did.
+    pub fn next_change(&mut self) -> Option<(LoadTarget, PathBuf)> {
+        while let Ok(event) = self.receiver.try_recv() {
+            let file = match event {
+                DebouncedEvent::Write(file) | DebouncedEvent::Create(file) => file,
+                DebouncedEvent::Rename(_, file) => file,
+                _ => continue,
+            };
+            for asset in &self.assets {
+                if asset.files.contains(&file) && !self.changed.contains(&asset.target) {
+                    self.changed.push(asset.target);
+                }
+            }
+        }
+
+        let target = self.changed.pop()?;
+        let asset = self.assets.iter().find(|asset| asset.target == target)?;
+        Some((target, asset.path.clone()))
+    }
+}
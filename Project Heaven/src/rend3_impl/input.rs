@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+
+use super::controls::platform::Scancodes;
+use super::settings::{config_path, write_config};
+
+// Beside config.toml. Before that it was in the working directory, where it's read from
+// once if there is no config yet.
+const BINDINGS_NAME: &str = "input.toml";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RollLeft,
+    RollRight,
+    Run,
+    Creep,
+    // Ship only, the free camera looks with the mouse
+    PitchUp,
+    PitchDown,
+    YawLeft,
+    YawRight,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    Accelerate,
+    Decelerate,
+    ToggleCamera,
+    SwitchFixture,
+    ToggleGrab,
+    ReleaseCursor,
+    ToggleMenu,
+    ToggleFullscreen,
+    TogglePerformance,
+    CycleRenderMode,
+}
+
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::Run,
+        Action::Creep,
+        Action::PitchUp,
+        Action::PitchDown,
+        Action::YawLeft,
+        Action::YawRight,
+        Action::LookUp,
+        Action::LookDown,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::Accelerate,
+        Action::Decelerate,
+        Action::ToggleCamera,
+        Action::SwitchFixture,
+        Action::ToggleGrab,
+        Action::ReleaseCursor,
+        Action::ToggleMenu,
+        Action::ToggleFullscreen,
+        Action::TogglePerformance,
+        Action::CycleRenderMode,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBack => "Move back",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::RollLeft => "Roll left",
+            Action::RollRight => "Roll right",
+            Action::Run => "Run",
+            Action::Creep => "Creep",
+            Action::PitchUp => "Pitch up",
+            Action::PitchDown => "Pitch down",
+            Action::YawLeft => "Yaw left",
+            Action::YawRight => "Yaw right",
+            Action::LookUp => "Look up",
+            Action::LookDown => "Look down",
+            Action::LookLeft => "Look left",
+            Action::LookRight => "Look right",
+            Action::Accelerate => "Accelerate",
+            Action::Decelerate => "Decelerate",
+            Action::ToggleCamera => "Switch ship and free camera",
+            Action::SwitchFixture => "Switch ship camera position",
+            Action::ToggleGrab => "Grab or release the cursor",
+            Action::ReleaseCursor => "Release the cursor",
+            Action::ToggleMenu => "Show or hide the menu",
+            Action::ToggleFullscreen => "Fullscreen",
+            Action::TogglePerformance => "Performance overlay",
+            Action::CycleRenderMode => "Next render mode",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Binding {
+    // Physical key, so it stays in place whatever the keyboard layout. The name is only shown.
+    Scancode { code: u32, name: String },
+    // Keys that are about what is printed on them rather than where they are, like F11
+    Key(VirtualKeyCode),
+    // The left button is left to the UI, picking and grabbing
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    fn scancode(code: u32, name: &str) -> Self {
+        Binding::Scancode {
+            code,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Scancode { name, .. } => name.clone(),
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(MouseButton::Other(button)) => format!("Mouse {}", button),
+            Binding::Mouse(button) => format!("{:?} mouse", button),
+        }
+    }
+}
+
+pub fn default_bindings() -> BTreeMap<Action, Binding> {
+    BTreeMap::from([
+        (Action::MoveForward, Binding::scancode(Scancodes::W, "W")),
+        (Action::MoveBack, Binding::scancode(Scancodes::S, "S")),
+        (Action::MoveLeft, Binding::scancode(Scancodes::A, "A")),
+        (Action::MoveRight, Binding::scancode(Scancodes::D, "D")),
+        (Action::MoveUp, Binding::scancode(Scancodes::SPACE, "Space")),
+        (
+            Action::MoveDown,
+            Binding::scancode(Scancodes::COMMA, "Comma"),
+        ),
+        (Action::RollLeft, Binding::scancode(Scancodes::Q, "Q")),
+        (Action::RollRight, Binding::scancode(Scancodes::E, "E")),
+        (Action::Run, Binding::scancode(Scancodes::SHIFT, "Shift")),
+        (Action::Creep, Binding::scancode(Scancodes::LALT, "Alt")),
+        (Action::PitchUp, Binding::scancode(Scancodes::UP, "Up")),
+        (
+            Action::PitchDown,
+            Binding::scancode(Scancodes::DOWN, "Down"),
+        ),
+        (Action::YawLeft, Binding::scancode(Scancodes::LEFT, "Left")),
+        (
+            Action::YawRight,
+            Binding::scancode(Scancodes::RIGHT, "Right"),
+        ),
+        (Action::LookUp, Binding::scancode(Scancodes::I, "I")),
+        (Action::LookDown, Binding::scancode(Scancodes::K, "K")),
+        (Action::LookLeft, Binding::scancode(Scancodes::J, "J")),
+        (Action::LookRight, Binding::scancode(Scancodes::L, "L")),
+        (
+            Action::Accelerate,
+            Binding::scancode(Scancodes::PLUS_NUM, "Numpad +"),
+        ),
+        (
+            Action::Decelerate,
+            Binding::scancode(Scancodes::MINUS_NUM, "Numpad -"),
+        ),
+        (
+            Action::ToggleCamera,
+            Binding::scancode(Scancodes::PERIOD, "Period"),
+        ),
+        (
+            Action::SwitchFixture,
+            Binding::scancode(Scancodes::CTRL, "Ctrl"),
+        ),
+        (Action::ToggleGrab, Binding::Key(VirtualKeyCode::G)),
+        (
+            Action::ReleaseCursor,
+            Binding::scancode(Scancodes::ESCAPE, "Escape"),
+        ),
+        (Action::ToggleMenu, Binding::Key(VirtualKeyCode::Tab)),
+        (Action::ToggleFullscreen, Binding::Key(VirtualKeyCode::F11)),
+        (Action::TogglePerformance, Binding::Key(VirtualKeyCode::F3)),
+        (Action::CycleRenderMode, Binding::Key(VirtualKeyCode::F4)),
+    ])
+}
+
+// Actions the file leaves out keep their default binding
+#[derive(Serialize, Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    bindings: BTreeMap<Action, Binding>,
+}
+
+fn load_bindings() -> BTreeMap<Action, Binding> {
+    let mut bindings = default_bindings();
+    let path = config_path(BINDINGS_NAME);
+    let legacy = PathBuf::from(BINDINGS_NAME);
+    let (path, text, migrating) = match std::fs::read_to_string(&path) {
+        Ok(text) => (path, text, false),
+        Err(_) => match std::fs::read_to_string(&legacy) {
+            Ok(text) => (legacy, text, true),
+            Err(_) => return bindings,
+        },
+    };
+    match toml::from_str::<BindingsFile>(&text) {
+        Ok(file) => bindings.extend(file.bindings),
+        Err(ex) => {
+            tracing::error!("{}: {}", path.display(), ex);
+            return bindings;
+        }
+    }
+    // Written where it belongs now, the old file is left for older builds
+    if migrating {
+        save_bindings(&bindings);
+    }
+    bindings
+}
+
+fn save_bindings(bindings: &BTreeMap<Action, Binding>) {
+    let file = BindingsFile {
+        bindings: bindings.clone(),
+    };
+    let path = config_path(BINDINGS_NAME);
+    let result = toml::to_string_pretty(&file)
+        .map_err(|ex| ex.to_string())
+        .and_then(|text| write_config(&path, &text));
+    if let Err(ex) = result {
+        tracing::error!("{}: {}", path.display(), ex);
+    }
+}
+
+// What is held down right now, and what each action is bound to
+#[derive(Default)]
+pub struct Input {
+    pub bindings: BTreeMap<Action, Binding>,
+    scancodes: HashSet<u32>,
+    keys: HashSet<VirtualKeyCode>,
+    buttons: HashSet<MouseButton>,
+    // Set from the controls UI, the next key or button pressed gets bound to it
+    pub listening: Option<Action>,
+}
+
+impl Input {
+    pub fn load() -> Self {
+        Self {
+            bindings: load_bindings(),
+            ..Self::default()
+        }
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        match self.bindings.get(&action) {
+            Some(Binding::Scancode { code, .. }) => self.scancodes.contains(code),
+            Some(Binding::Key(key)) => self.keys.contains(key),
+            Some(Binding::Mouse(button)) => self.buttons.contains(button),
+            None => false,
+        }
+    }
+
+    // Any key or button at all, bound or not
+    pub fn any_held(&self) -> bool {
+        !self.scancodes.is_empty() || !self.buttons.is_empty()
+    }
+
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .get(&action)
+            .map_or_else(|| "Unbound".to_string(), Binding::label)
+    }
+
+    fn actions(&self, bound: impl Fn(&Binding) -> bool) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, binding)| bound(binding))
+            .map(|(&action, _)| action)
+            .collect()
+    }
+
+    // Binds whatever was pressed to the action being listened for, if there is one
+    fn bind(&mut self, binding: Binding) -> bool {
+        match self.listening.take() {
+            Some(action) => {
+                self.bindings.insert(action, binding);
+                save_bindings(&self.bindings);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns the actions that just went down. Held keys repeat their press, only the first
+    // one counts.
+    pub fn key_event(
+        &mut self,
+        scancode: u32,
+        key: Option<VirtualKeyCode>,
+        state: ElementState,
+    ) -> Vec<Action> {
+        if state == ElementState::Released {
+            self.scancodes.remove(&scancode);
+            if let Some(key) = key {
+                self.keys.remove(&key);
+            }
+            return Vec::new();
+        }
+        if !self.scancodes.insert(scancode) {
+            return Vec::new();
+        }
+        if let Some(key) = key {
+            self.keys.insert(key);
+        }
+
+        let name = key.map_or_else(
+            || format!("Scancode {}", scancode),
+            |key| format!("{:?}", key),
+        );
+        if self.bind(Binding::Scancode {
+            code: scancode,
+            name,
+        }) {
+            return Vec::new();
+        }
+        self.actions(|binding| match binding {
+            Binding::Scancode { code, .. } => *code == scancode,
+            Binding::Key(bound) => Some(*bound) == key,
+            Binding::Mouse(_) => false,
+        })
+    }
+
+    pub fn mouse_event(&mut self, button: MouseButton, state: ElementState) -> Vec<Action> {
+        if button == MouseButton::Left {
+            return Vec::new();
+        }
+        match state {
+            ElementState::Released => {
+                self.buttons.remove(&button);
+                Vec::new()
+            }
+            ElementState::Pressed => {
+                if !self.buttons.insert(button) || self.bind(Binding::Mouse(button)) {
+                    return Vec::new();
+                }
+                self.actions(|binding| *binding == Binding::Mouse(button))
+            }
+        }
+    }
+}
+
+pub fn controls_ui(ui: &mut egui::Ui, input: &mut Input) {
+    egui::Grid::new("controls").striped(true).show(ui, |ui| {
+        for action in Action::ALL {
+            ui.label(action.name());
+            let label = if input.listening == Some(action) {
+                "Press a key…".to_string()
+            } else {
+                input.label(action)
+            };
+            if ui.button(label).clicked() {
+                input.listening = Some(action);
+            }
+            ui.end_row();
+        }
+    });
+    ui.horizontal(|ui| {
+        if input.listening.is_some() && ui.button("Cancel").clicked() {
+            input.listening = None;
+        }
+        if ui.button("Reset to defaults").clicked() {
+            input.bindings = default_bindings();
+            input.listening = None;
+            save_bindings(&input.bindings);
+        }
+    });
+}
@@ -0,0 +1,227 @@
+use glam::{Quat, Vec3};
+use rend3::types::{DirectionalLight, DirectionalLightChange, DirectionalLightHandle};
+
+// Key, fill and rim as (direction the light travels, share of the master intensity).
+// The key comes from above front-left, the fill from lower front-right, the rim from behind.
+const RIG: [(Vec3, f32); 3] = [
+    (Vec3::new(1.0, -1.0, 1.0), 1.0),
+    (Vec3::new(-1.0, -0.3, 1.0), 0.4),
+    (Vec3::new(0.0, -0.5, -1.0), 0.7),
+];
+
+// The scene's main light
+pub fn sun() -> DirectionalLight {
+    DirectionalLight {
+        color: Vec3::new(0.95, 0.9, 0.6),
+        intensity: 5.,
+        // Direction will be normalized
+        direction: Vec3::new(-1.0, -4.0, 2.0),
+        distance: 4000.0,
+    }
+}
+
+// Lights added from the lighting window on top of the sun
+pub struct ExtraLight {
+    pub light: DirectionalLight,
+    pub handle: DirectionalLightHandle,
+}
+
+// Where the light comes from, as (azimuth, elevation) in degrees. Azimuth 0 is +Z and
+// elevation 90 straight overhead.
+fn light_angles(direction: Vec3) -> (f32, f32) {
+    let towards_light = -direction.normalize_or_zero();
+    (
+        towards_light.x.atan2(towards_light.z).to_degrees(),
+        towards_light.y.clamp(-1., 1.).asin().to_degrees(),
+    )
+}
+
+fn light_direction(azimuth: f32, elevation: f32) -> Vec3 {
+    let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+    -Vec3::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        elevation.cos() * azimuth.cos(),
+    )
+}
+
+// Elevation stops short of straight up or down, where the azimuth would be lost
+pub fn light_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    handle: &DirectionalLightHandle,
+    light: &mut DirectionalLight,
+) {
+    let (mut azimuth, mut elevation) = light_angles(light.direction);
+    let mut color = light.color.to_array();
+
+    let azimuth_changed = ui
+        .add(egui::Slider::new(&mut azimuth, -180.0..=180.0).text("Azimuth"))
+        .changed();
+    let elevation_changed = ui
+        .add(egui::Slider::new(&mut elevation, -89.0..=89.0).text("Elevation"))
+        .changed();
+    let direction_changed = azimuth_changed || elevation_changed;
+    let color_changed = ui
+        .horizontal(|ui| {
+            let changed = ui.color_edit_button_rgb(&mut color).changed();
+            ui.label("Color");
+            changed
+        })
+        .inner;
+    let intensity_changed = ui
+        .add(egui::Slider::new(&mut light.intensity, 0.0..=50.0).text("Intensity"))
+        .changed();
+    let distance_changed = ui
+        .add(
+            egui::Slider::new(&mut light.distance, 10.0..=10_000.0)
+                .logarithmic(true)
+                .text("Shadow distance"),
+        )
+        .changed();
+
+    if direction_changed {
+        light.direction = light_direction(azimuth, elevation);
+    }
+    light.color = Vec3::from(color);
+    if direction_changed || color_changed || intensity_changed || distance_changed {
+        update_light(renderer, handle, light);
+    }
+}
+
+pub fn update_light(
+    renderer: &rend3::Renderer,
+    handle: &DirectionalLightHandle,
+    light: &DirectionalLight,
+) {
+    renderer.update_directional_light(
+        handle,
+        DirectionalLightChange {
+            color: Some(light.color),
+            intensity: Some(light.intensity),
+            direction: Some(light.direction.normalize()),
+            distance: Some(light.distance),
+        },
+    );
+}
+
+// The sun can be edited but not removed, extra lights are removed by dropping their handle.
+// `exposure` is the tonemapper's, next to the lights it's balanced against.
+pub fn lighting_window(
+    ctx: &egui::CtxRef,
+    renderer: &rend3::Renderer,
+    sun_handle: &DirectionalLightHandle,
+    sun: &mut DirectionalLight,
+    extra_lights: &mut Vec<ExtraLight>,
+    exposure: &mut f32,
+    open: &mut bool,
+) {
+    egui::Window::new("Lighting").open(open).show(ctx, |ui| {
+        ui.add(egui::Slider::new(exposure, -8.0..=8.0).text("Exposure (stops)"));
+        ui.collapsing("Sun", |ui| light_ui(ui, renderer, sun_handle, sun));
+
+        let mut removed = None;
+        for (index, extra) in extra_lights.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.collapsing(format!("Light {}", index + 1), |ui| {
+                    light_ui(ui, renderer, &extra.handle, &mut extra.light);
+                    if ui.button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            });
+        }
+        if let Some(index) = removed {
+            extra_lights.remove(index);
+        }
+
+        if ui.button("Add light").clicked() {
+            let light = DirectionalLight {
+                color: Vec3::ONE,
+                intensity: 2.,
+                direction: light_direction(45., 45.),
+                distance: 4000.0,
+            };
+            extra_lights.push(ExtraLight {
+                handle: renderer.add_directional_light(light.clone()),
+                light,
+            });
+        }
+    });
+}
+
+pub struct TurntableRig {
+    pub intensity: f32,
+    // Degrees around the vertical axis
+    pub rotation: f32,
+    handles: Vec<DirectionalLightHandle>,
+}
+
+fn rig_direction(direction: Vec3, rotation: f32) -> Vec3 {
+    Quat::from_rotation_y(rotation.to_radians()) * direction
+}
+
+impl TurntableRig {
+    pub fn light_count(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn new(renderer: &rend3::Renderer, intensity: f32, rotation: f32) -> Self {
+        let mut rig = Self {
+            intensity,
+            rotation,
+            handles: Vec::new(),
+        };
+        rig.handles = rig
+            .lights()
+            .into_iter()
+            .map(|light| renderer.add_directional_light(light))
+            .collect();
+        rig
+    }
+
+    pub fn lights(&self) -> Vec<DirectionalLight> {
+        RIG.iter()
+            .map(|&(direction, share)| DirectionalLight {
+                color: Vec3::ONE,
+                intensity: self.intensity * share,
+                direction: rig_direction(direction, self.rotation),
+                distance: 4000.0,
+            })
+            .collect()
+    }
+
+    pub fn update(&self, renderer: &rend3::Renderer) {
+        for (handle, &(direction, share)) in self.handles.iter().zip(RIG.iter()) {
+            renderer.update_directional_light(
+                handle,
+                DirectionalLightChange {
+                    color: None,
+                    intensity: Some(self.intensity * share),
+                    direction: Some(rig_direction(direction, self.rotation)),
+                    distance: None,
+                },
+            );
+        }
+    }
+}
+
+// Dropping the rig drops its light handles, which removes the lights
+pub fn turntable_ui(ui: &mut egui::Ui, renderer: &rend3::Renderer, rig: &mut Option<TurntableRig>) {
+    let mut enabled = rig.is_some();
+    if ui.checkbox(&mut enabled, "Turntable lighting").changed() {
+        *rig = enabled.then(|| TurntableRig::new(renderer, 5., 0.));
+    }
+
+    if let Some(rig) = rig {
+        let intensity_changed = ui
+            .add(egui::Slider::new(&mut rig.intensity, 0.0..=20.0).text("Rig intensity"))
+            .changed();
+        let rotation_changed = ui
+            .add(egui::Slider::new(&mut rig.rotation, 0.0..=360.0).text("Rig rotation"))
+            .changed();
+        if intensity_changed || rotation_changed {
+            rig.update(renderer);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use glam::{Mat4, Vec3};
+use rend3::types::{CameraProjection, MaterialHandle, MeshHandle};
+use std::collections::HashMap;
+
+use super::gizmo;
+use super::mesh_generator;
+use super::projection;
+use super::scale_bar;
+use super::scene::SceneObject;
+
+// Pixels of screen height the object has to cover to be drawn at full detail. Each level
+// after that takes over once it's a little under half as big as for the one before.
+const FULL_DETAIL: f32 = 300.;
+const STEP: f32 = 0.4;
+// How far past a threshold the size has to get before the level changes, so an object sitting
+// right on one doesn't flicker between the two
+const HYSTERESIS: f32 = 0.1;
+// Grid resolutions the generated levels are clustered on
+const GENERATED_CELLS: [u32; 3] = [32, 16, 8];
+
+pub struct LodLevel {
+    pub mesh: MeshHandle,
+    pub triangles: usize,
+}
+
+pub struct Lod {
+    // Level 0 is the object's own mesh, the rest get coarser from there
+    pub levels: Vec<LodLevel>,
+    pub current: usize,
+    // Off, level 0 is drawn whatever the distance
+    pub enabled: bool,
+    // Pixels of screen height the object covered when last updated, and how far away it was
+    pub screen_size: f32,
+    pub distance: f32,
+    // Of the bounding sphere, in the mesh's own space
+    radius: f32,
+}
+
+impl Lod {
+    // `levels` are the ones after the object's own mesh, most detailed first. Skinned objects
+    // are left alone, their skeleton is made for the one mesh.
+    fn new(object: &SceneObject, mut levels: Vec<LodLevel>) -> Option<Self> {
+        if levels.is_empty() || object.skeleton.is_some() {
+            return None;
+        }
+        let mesh = object.mesh_data.as_ref()?;
+        let (min, max) = mesh.bounds()?;
+        levels.insert(
+            0,
+            LodLevel {
+                mesh: object.mesh.clone(),
+                triangles: mesh.indices.len() / 3,
+            },
+        );
+        Some(Self {
+            levels,
+            current: 0,
+            enabled: true,
+            screen_size: 0.,
+            distance: 0.,
+            radius: (max - min).length() / 2.,
+        })
+    }
+
+    // Smallest size `level` is drawn at, for levels after the first
+    fn threshold(level: usize) -> f32 {
+        FULL_DETAIL * STEP.powi(level as i32 - 1)
+    }
+
+    fn level_for(&self, screen_size: f32) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+        let mut level = 0;
+        while level + 1 < self.levels.len() && screen_size < Self::threshold(level + 1) {
+            level += 1;
+        }
+        // Only the boundary between the two levels needs the margin, a jump over several
+        // is already well past it
+        let boundary = Self::threshold(level.max(self.current));
+        if level != self.current && (screen_size - boundary).abs() < boundary * HYSTERESIS {
+            self.current
+        } else {
+            level
+        }
+    }
+}
+
+// Decimated copies of the object's mesh, None for objects there's no mesh data for
+pub fn generate(renderer: &rend3::Renderer, object: &SceneObject) -> Option<Lod> {
+    let mesh = object.mesh_data.as_ref()?;
+    let mut triangles = mesh.indices.len() / 3;
+    let mut levels = Vec::new();
+    for cells in GENERATED_CELLS {
+        let level = mesh_generator::decimate(mesh, cells);
+        // Small meshes run out of triangles to remove before the coarsest grid
+        let level_triangles = level.indices.len() / 3;
+        if level_triangles == 0 || level_triangles >= triangles {
+            continue;
+        }
+        triangles = level_triangles;
+        levels.push(LodLevel {
+            mesh: renderer.add_mesh(level.build()),
+            triangles,
+        });
+    }
+    Lod::new(object, levels)
+}
+
+// "Rock_LOD1", "Rock LOD1" and "RockLOD1" are level 1 of "Rock", the way most exporters
+// name them
+fn split_level_name(name: &str) -> Option<(String, usize)> {
+    // Objects for the primitives of a mesh end in " #<primitive>", which stays on the base
+    let (name, primitive) = name.split_at(name.rfind(" #").unwrap_or(name.len()));
+    let at = name.to_ascii_uppercase().rfind("LOD")?;
+    let level = name[at + 3..].parse().ok()?;
+    let base = name[..at].trim_end_matches(|c| c == '_' || c == ' ' || c == '-');
+    Some((format!("{}{}", base, primitive), level))
+}
+
+// Objects named as levels of the same thing become the most detailed one, drawing whichever
+// level fits. `nodes` are the glTF nodes `objects` came from, both are returned without the
+// objects that went into another.
+pub fn group_named_levels(
+    objects: Vec<SceneObject>,
+    nodes: Vec<usize>,
+) -> (Vec<SceneObject>, Vec<usize>) {
+    // Levels and indices into objects, by the name without the suffix
+    let mut groups: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for (index, object) in objects.iter().enumerate() {
+        if let Some((base, level)) = split_level_name(&object.name) {
+            groups.entry(base).or_default().push((level, index));
+        }
+    }
+
+    let mut objects: Vec<Option<SceneObject>> = objects.into_iter().map(Some).collect();
+    for (base, mut members) in groups {
+        members.sort_unstable();
+        let head = members[0].1;
+        let usable = objects[head].as_ref().map_or(false, |object| {
+            object.mesh_data.is_some() && object.skeleton.is_none()
+        });
+        if members.len() < 2 || !usable {
+            continue;
+        }
+        // Dropping the others removes them from the renderer, their meshes live on here
+        let levels = members[1..]
+            .iter()
+            .filter_map(|&(_, index)| objects[index].take())
+            .map(|object| LodLevel {
+                triangles: object
+                    .mesh_data
+                    .as_ref()
+                    .map_or(0, |mesh| mesh.indices.len() / 3),
+                mesh: object.mesh,
+            })
+            .collect();
+        if let Some(object) = &mut objects[head] {
+            object.lod = Lod::new(object, levels);
+            object.name = base;
+        }
+    }
+
+    objects
+        .into_iter()
+        .zip(nodes)
+        .filter_map(|(object, node)| Some((object?, node)))
+        .unzip()
+}
+
+// Picks every object's level for how big it is on the screen from `camera`, swapping the
+// mesh when that changed
+pub fn update(
+    renderer: &rend3::Renderer,
+    objects: &mut [&mut SceneObject],
+    clay: Option<&MaterialHandle>,
+    camera: Vec3,
+    projection: CameraProjection,
+    screen_height: f32,
+) {
+    for object in objects.iter_mut() {
+        let lod = match &mut object.lod {
+            Some(lod) => lod,
+            None => continue,
+        };
+        // Rebuilt by the mesh diagnostics, the levels are of the old mesh
+        if !lod.levels.iter().any(|level| level.mesh == object.mesh) {
+            object.lod = None;
+            continue;
+        }
+        // Normals and depth draw a coloured copy made from the full detail mesh data, the
+        // levels only have handles. Switching would only rebuild that copy each time.
+        if object
+            .debug
+            .as_ref()
+            .map_or(false, |debug| debug.mesh.is_some())
+        {
+            continue;
+        }
+        let (scale, _, position) = object.transform.to_scale_rotation_translation();
+        lod.distance = camera.distance(position);
+        lod.screen_size = scale_bar::points_per_meter(projection, lod.distance, screen_height)
+            .map_or(f32::MAX, |per_meter| {
+                2. * lod.radius * scale.max_element() * per_meter
+            });
+        let level = lod.level_for(lod.screen_size);
+        if level != lod.current {
+            lod.current = level;
+            object.mesh = lod.levels[level].mesh.clone();
+            object.refresh(renderer, clay);
+        }
+    }
+}
+
+// Part of the scene panel
+pub fn lod_ui(ui: &mut egui::Ui, renderer: &rend3::Renderer, object: &mut SceneObject) {
+    ui.horizontal(|ui| match &mut object.lod {
+        Some(lod) => {
+            ui.checkbox(&mut lod.enabled, "Level of detail");
+            ui.label(format!(
+                "{} levels, drawing {}",
+                lod.levels.len(),
+                lod.current
+            ));
+        }
+        None => {
+            let can_generate = object.mesh_data.is_some() && object.skeleton.is_none();
+            let button = ui
+                .add_enabled(can_generate, egui::Button::new("Generate LODs"))
+                .on_disabled_hover_text("Only meshes that aren't skinned, from a file or added");
+            if button.clicked() {
+                object.lod = generate(renderer, object);
+                if object.lod.is_none() {
+                    tracing::warn!("{} is too simple to have levels of detail", object.name);
+                }
+            }
+        }
+    });
+}
+
+// Label next to the selected object with the level it's drawn at
+pub fn lod_overlay(
+    ctx: &egui::CtxRef,
+    object: &SceneObject,
+    view: Mat4,
+    projection: CameraProjection,
+) {
+    let lod = match &object.lod {
+        Some(lod) => lod,
+        None => return,
+    };
+    let screen = ctx.input().screen_rect();
+    let view_projection = projection::matrix(projection, screen.width() / screen.height()) * view;
+    let at = match gizmo::to_screen(view_projection, screen, object.transform.w_axis.truncate()) {
+        Some(at) => at,
+        None => return,
+    };
+    let text = format!(
+        "LOD {}\n{} triangles\n{:.1} m away",
+        lod.current, lod.levels[lod.current].triangles, lod.distance
+    );
+    ctx.layer_painter(egui::LayerId::background()).text(
+        at + egui::vec2(12., 12.),
+        egui::Align2::LEFT_TOP,
+        text,
+        egui::TextStyle::Monospace,
+        egui::Color32::WHITE,
+    );
+}
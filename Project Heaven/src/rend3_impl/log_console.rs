@@ -0,0 +1,216 @@
+use instant::Instant;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+// Oldest lines are dropped past this many
+const MAX_LINES: usize = 2000;
+
+const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+pub struct LogLine {
+    // Seconds since the logger was set up
+    pub time: f32,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogLine {
+    fn text(&self) -> String {
+        format!(
+            "[{:>9.3}] {:<5} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+// Shared between the subscriber, which can be called from any thread, and the console
+pub type LogBuffer = Arc<Mutex<VecDeque<LogLine>>>;
+
+// The message first, then any other fields as key=value
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.insert_str(0, value);
+        } else if !field.name().starts_with("log.") {
+            let _ = write!(self.0, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else if !field.name().starts_with("log.") {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+struct ConsoleLayer {
+    lines: LogBuffer,
+    start: Instant,
+}
+
+impl<S: Subscriber> Layer<S> for ConsoleLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        // Records from the log crate (wgpu, gltf, winit) carry their real target in fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+
+        let mut lines = self.lines.lock().unwrap();
+        lines.push_back(LogLine {
+            time: self.start.elapsed().as_secs_f32(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: message.0,
+        });
+        if lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+    }
+}
+
+// Logs to the terminal and to the console. RUST_LOG overrides `filter`.
+pub fn init(filter: &str) -> LogBuffer {
+    let lines = LogBuffer::default();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter));
+    #[cfg(not(target_arch = "wasm32"))]
+    let terminal = tracing_subscriber::fmt::layer();
+    // The browser's developer console stands in for the terminal
+    #[cfg(target_arch = "wasm32")]
+    let terminal = tracing_wasm::WASMLayer::new(tracing_wasm::WASMLayerConfig::default());
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(terminal)
+        .with(ConsoleLayer {
+            lines: lines.clone(),
+            start: Instant::now(),
+        })
+        .try_init();
+    if let Err(ex) = result {
+        println!("ERROR -> {}", ex);
+    }
+    lines
+}
+
+pub struct LogConsole {
+    pub open: bool,
+    lines: LogBuffer,
+    // Most verbose level shown
+    level: Level,
+    filter: String,
+    follow: bool,
+    // Time of the newest line when last drawn, to scroll down only when something came in
+    seen: Option<f32>,
+}
+
+impl LogConsole {
+    pub fn new(lines: LogBuffer) -> Self {
+        Self {
+            open: false,
+            lines,
+            level: Level::TRACE,
+            filter: String::new(),
+            follow: true,
+            seen: None,
+        }
+    }
+
+    fn shows(&self, line: &LogLine) -> bool {
+        let filter = self.filter.to_lowercase();
+        line.level <= self.level
+            && (filter.is_empty()
+                || line.message.to_lowercase().contains(&filter)
+                || line.target.to_lowercase().contains(&filter))
+    }
+}
+
+fn level_color(level: Level, ui: &egui::Ui) -> egui::Color32 {
+    match level {
+        Level::ERROR => egui::Color32::from_rgb(255, 80, 80),
+        Level::WARN => egui::Color32::from_rgb(255, 190, 60),
+        Level::INFO => ui.visuals().text_color(),
+        _ => ui.visuals().weak_text_color(),
+    }
+}
+
+pub fn console_window(ctx: &egui::CtxRef, console: &mut LogConsole) {
+    let mut open = console.open;
+    egui::Window::new("Log")
+        .open(&mut open)
+        .default_width(600.)
+        .show(ctx, |ui| {
+            let lines = console.lines.clone();
+            let mut lines = lines.lock().unwrap();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("log_level")
+                    .selected_text(console.level.to_string())
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut console.level, level, level.to_string());
+                        }
+                    });
+                ui.label("Filter");
+                ui.text_edit_singleline(&mut console.filter);
+                ui.checkbox(&mut console.follow, "Follow");
+                if ui.button("Copy").clicked() {
+                    let text: Vec<String> = lines
+                        .iter()
+                        .filter(|line| console.shows(line))
+                        .map(LogLine::text)
+                        .collect();
+                    ui.output().copied_text = text.join("\n");
+                }
+                if ui.button("Clear").clicked() {
+                    lines.clear();
+                }
+            });
+            ui.separator();
+
+            let newest = lines.back().map(|line| line.time);
+            let arrived = newest != console.seen;
+            console.seen = newest;
+            egui::ScrollArea::vertical()
+                .max_height(400.)
+                .show(ui, |ui| {
+                    for line in lines.iter().filter(|line| console.shows(line)) {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new(format!("{:>9.3}", line.time))
+                                    .monospace()
+                                    .weak(),
+                            );
+                            ui.label(
+                                egui::RichText::new(format!("{:<5}", line.level))
+                                    .monospace()
+                                    .color(level_color(line.level, ui)),
+                            );
+                            ui.label(egui::RichText::new(&line.target).monospace().weak());
+                            ui.label(egui::RichText::new(&line.message).monospace());
+                        });
+                    }
+                    if console.follow && arrived {
+                        ui.scroll_to_cursor(egui::Align::BOTTOM);
+                    }
+                });
+        });
+    console.open &= open;
+}
@@ -0,0 +1,240 @@
+use glam::{Vec3, Vec4};
+use rend3::types::TextureHandle;
+use rend3_routine::pbr::{AlbedoComponent, MaterialComponent, PbrMaterial, Transparency};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransparencyMode {
+    Opaque,
+    // Fragments with alpha below the cutout are discarded
+    Cutout,
+    Blend,
+}
+
+impl TransparencyMode {
+    pub const ALL: [TransparencyMode; 3] = [
+        TransparencyMode::Opaque,
+        TransparencyMode::Cutout,
+        TransparencyMode::Blend,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TransparencyMode::Opaque => "Opaque",
+            TransparencyMode::Cutout => "Cutout",
+            TransparencyMode::Blend => "Blend",
+        }
+    }
+}
+
+// The rest of the PBR inputs the material editor sets, next to colour, roughness and metallic
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SurfaceParams {
+    pub reflectance: f32,
+    pub emissive: [f32; 3],
+    pub transparency: TransparencyMode,
+    pub cutout: f32,
+    pub ambient_occlusion: f32,
+}
+
+impl Default for SurfaceParams {
+    fn default() -> Self {
+        Self {
+            reflectance: 0.5,
+            emissive: [0., 0., 0.],
+            transparency: TransparencyMode::Blend,
+            cutout: 0.5,
+            ambient_occlusion: 1.,
+        }
+    }
+}
+
+// Returns true when anything changed
+pub fn surface_ui(ui: &mut egui::Ui, surface: &mut SurfaceParams) -> bool {
+    let mut changed = ui
+        .add(egui::Slider::new(&mut surface.reflectance, 0.0..=1.0).text("Reflectance"))
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(&mut surface.ambient_occlusion, 0.0..=1.0).text("Ambient occlusion"))
+        .changed();
+    ui.horizontal(|ui| {
+        changed |= ui.color_edit_button_rgb(&mut surface.emissive).changed();
+        ui.label("Emissive");
+    });
+
+    let previous = surface.transparency;
+    egui::ComboBox::from_label("Transparency")
+        .selected_text(surface.transparency.name())
+        .show_ui(ui, |ui| {
+            for mode in TransparencyMode::ALL {
+                ui.selectable_value(&mut surface.transparency, mode, mode.name());
+            }
+        });
+    changed |= surface.transparency != previous;
+    if surface.transparency == TransparencyMode::Cutout {
+        changed |= ui
+            .add(egui::Slider::new(&mut surface.cutout, 0.0..=1.0).text("Cutout"))
+            .changed();
+    }
+    changed
+}
+
+// Colour, roughness and metallic with the surface under them, for the ship or an object.
+// Returns true when anything changed.
+pub fn material_ui(
+    ui: &mut egui::Ui,
+    color: &mut [f32; 4],
+    roughness: &mut f32,
+    metallic: &mut f32,
+    surface: &mut SurfaceParams,
+) -> bool {
+    let mut changed = ui.color_edit_button_rgba_unmultiplied(color).changed();
+    changed |= ui
+        .add(egui::Slider::new(roughness, 0.0..=1.0).text("Roughness"))
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(metallic, 0.0..=1.0).text("Metallic"))
+        .changed();
+    changed |= ui
+        .collapsing("Surface", |ui| surface_ui(ui, surface))
+        .body_returned
+        .unwrap_or(false);
+    changed
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationKind {
+    None,
+    PulseEmissive,
+    CycleHue,
+    OscillateRoughness,
+}
+
+impl AnimationKind {
+    pub const ALL: [AnimationKind; 4] = [
+        AnimationKind::None,
+        AnimationKind::PulseEmissive,
+        AnimationKind::CycleHue,
+        AnimationKind::OscillateRoughness,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationKind::None => "None",
+            AnimationKind::PulseEmissive => "Pulse emissive",
+            AnimationKind::CycleHue => "Cycle hue",
+            AnimationKind::OscillateRoughness => "Oscillate roughness",
+        }
+    }
+}
+
+pub struct MaterialAnimation {
+    pub kind: AnimationKind,
+    // Cycles per second
+    pub speed: f32,
+    pub amount: f32,
+}
+
+impl Default for MaterialAnimation {
+    fn default() -> Self {
+        Self {
+            kind: AnimationKind::None,
+            speed: 0.5,
+            amount: 1.,
+        }
+    }
+}
+
+// What the colour picker sets on the ship, and what an animation goes back to when stopped.
+// An albedo texture replaces the colour.
+pub fn ship_material(
+    color: [f32; 4],
+    roughness: f32,
+    metallic: f32,
+    surface: &SurfaceParams,
+    texture: Option<&TextureHandle>,
+) -> PbrMaterial {
+    PbrMaterial {
+        albedo: match texture {
+            Some(texture) => AlbedoComponent::Texture(texture.clone()),
+            None => AlbedoComponent::Value(Vec4::from(color)),
+        },
+        roughness_factor: Some(roughness),
+        metallic_factor: Some(metallic),
+        reflectance: MaterialComponent::Value(surface.reflectance),
+        emissive: MaterialComponent::Value(Vec3::from(surface.emissive)),
+        ao_factor: Some(surface.ambient_occlusion),
+        transparency: match surface.transparency {
+            TransparencyMode::Opaque => Transparency::Opaque,
+            TransparencyMode::Cutout => Transparency::Cutout {
+                cutout: surface.cutout,
+            },
+            TransparencyMode::Blend => Transparency::Blend,
+        },
+        ..PbrMaterial::default()
+    }
+}
+
+impl MaterialAnimation {
+    pub fn material(
+        &self,
+        color: [f32; 4],
+        roughness: f32,
+        metallic: f32,
+        surface: &SurfaceParams,
+        texture: Option<&TextureHandle>,
+        time: f32,
+    ) -> PbrMaterial {
+        let wave = (time * self.speed * TAU).sin();
+        let mut material = ship_material(color, roughness, metallic, surface, texture);
+        match self.kind {
+            AnimationKind::None => {}
+            AnimationKind::PulseEmissive => {
+                let strength = self.amount * (0.5 + 0.5 * wave);
+                material.emissive =
+                    MaterialComponent::Value(Vec4::from(color).truncate() * strength);
+            }
+            AnimationKind::CycleHue => {
+                let mut hsva = egui::color::Hsva::from_rgba_unmultiplied(
+                    color[0], color[1], color[2], color[3],
+                );
+                hsva.h = (hsva.h + time * self.speed).fract();
+                let value = Vec4::from(hsva.to_rgba_unmultiplied());
+                // A texture is tinted instead of replaced
+                material.albedo = match texture {
+                    Some(texture) => AlbedoComponent::TextureValue {
+                        texture: texture.clone(),
+                        value,
+                    },
+                    None => AlbedoComponent::Value(value),
+                };
+            }
+            AnimationKind::OscillateRoughness => {
+                let roughness = (roughness + 0.5 * self.amount.min(1.) * wave).clamp(0., 1.);
+                material.roughness_factor = Some(roughness);
+            }
+        }
+        material
+    }
+}
+
+// Returns true when the animation was turned off, so the caller can put the static material back
+pub fn animation_ui(ui: &mut egui::Ui, label: &str, animation: &mut MaterialAnimation) -> bool {
+    let previous_kind = animation.kind;
+    egui::ComboBox::from_label(label)
+        .selected_text(animation.kind.name())
+        .show_ui(ui, |ui| {
+            for kind in AnimationKind::ALL {
+                ui.selectable_value(&mut animation.kind, kind, kind.name());
+            }
+        });
+    if animation.kind != AnimationKind::None {
+        ui.add(egui::Slider::new(&mut animation.speed, 0.05..=5.0).text("Speed"));
+        if animation.kind != AnimationKind::CycleHue {
+            ui.add(egui::Slider::new(&mut animation.amount, 0.0..=10.0).text("Amount"));
+        }
+    }
+    animation.kind == AnimationKind::None && previous_kind != AnimationKind::None
+}
@@ -0,0 +1,386 @@
+use glam::Vec3;
+use std::collections::{HashMap, VecDeque};
+
+use super::mesh_generator::MeshData;
+use super::scene::SceneObject;
+
+#[derive(Clone, Copy, Default)]
+pub struct MeshReport {
+    pub triangles: usize,
+    pub degenerate_triangles: usize,
+    // Vertices identical in every attribute to an earlier one, safe to merge
+    pub duplicate_vertices: usize,
+    // Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    // Edges whose two triangles both walk it in the same direction
+    pub inconsistent_edges: usize,
+}
+
+fn position_key(position: Vec3) -> [u32; 3] {
+    [
+        position.x.to_bits(),
+        position.y.to_bits(),
+        position.z.to_bits(),
+    ]
+}
+
+// Vertices split for UV seams or hard normals still share an edge, so edges are
+// compared by position rather than by index
+fn position_ids(mesh: &MeshData) -> Vec<usize> {
+    let mut ids = HashMap::new();
+    mesh.positions
+        .iter()
+        .map(|&position| {
+            let next_id = ids.len();
+            *ids.entry(position_key(position)).or_insert(next_id)
+        })
+        .collect()
+}
+
+// Triangles with a corner past the end of the vertices or off at NaN can't be drawn either,
+// so they're counted and removed along with the ones that have no area
+fn is_degenerate(mesh: &MeshData, ids: &[usize], triangle: &[u32]) -> bool {
+    let (a, b, c) = (
+        triangle[0] as usize,
+        triangle[1] as usize,
+        triangle[2] as usize,
+    );
+    if a.max(b).max(c) >= ids.len() {
+        return true;
+    }
+    if ids[a] == ids[b] || ids[b] == ids[c] || ids[c] == ids[a] {
+        return true;
+    }
+    let (pa, pb, pc) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+    let area = (pb - pa).cross(pc - pa).length_squared();
+    !area.is_finite() || area <= f32::EPSILON * f32::EPSILON
+}
+
+fn vertex_key(mesh: &MeshData, index: usize) -> Vec<u32> {
+    let mut key = position_key(mesh.positions[index]).to_vec();
+    if let Some(normal) = mesh.normals.get(index) {
+        key.extend_from_slice(&position_key(*normal));
+    }
+    if let Some(tangent) = mesh.tangents.get(index) {
+        key.extend_from_slice(&position_key(*tangent));
+    }
+    for uvs in [&mesh.uvs, &mesh.uvs1] {
+        if let Some(uv) = uvs.get(index) {
+            key.extend_from_slice(&[uv.x.to_bits(), uv.y.to_bits()]);
+        }
+    }
+    // Vertices in the same place but bound to other joints move apart once posed
+    if let Some(joints) = mesh.joints.get(index) {
+        key.extend(joints.iter().map(|&joint| joint as u32));
+    }
+    if let Some(weights) = mesh.weights.get(index) {
+        key.extend(weights.to_array().map(f32::to_bits));
+    }
+    key
+}
+
+// Edge -> triangles using it, for every triangle that isn't degenerate
+fn edge_uses(mesh: &MeshData, ids: &[usize]) -> HashMap<(usize, usize), Vec<(usize, bool)>> {
+    let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+    for (triangle_index, triangle) in mesh.indices.chunks_exact(3).enumerate() {
+        if is_degenerate(mesh, ids, triangle) {
+            continue;
+        }
+        for corner in 0..3 {
+            let from = ids[triangle[corner] as usize];
+            let to = ids[triangle[(corner + 1) % 3] as usize];
+            // Stored under the sorted pair, along with whether it was walked forwards
+            let key = (from.min(to), from.max(to));
+            edges
+                .entry(key)
+                .or_default()
+                .push((triangle_index, from < to));
+        }
+    }
+    edges
+}
+
+pub fn analyze(mesh: &MeshData) -> MeshReport {
+    let ids = position_ids(mesh);
+
+    let degenerate_triangles = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|triangle| is_degenerate(mesh, &ids, triangle))
+        .count();
+
+    let mut seen = HashMap::new();
+    let duplicate_vertices = (0..mesh.positions.len())
+        .filter(|&index| seen.insert(vertex_key(mesh, index), index).is_some())
+        .count();
+
+    let mut non_manifold_edges = 0;
+    let mut inconsistent_edges = 0;
+    for uses in edge_uses(mesh, &ids).values() {
+        match uses.as_slice() {
+            [(_, first), (_, second)] if first == second => inconsistent_edges += 1,
+            [_, _, _, ..] => non_manifold_edges += 1,
+            _ => {}
+        }
+    }
+
+    MeshReport {
+        triangles: mesh.indices.len() / 3,
+        degenerate_triangles,
+        duplicate_vertices,
+        non_manifold_edges,
+        inconsistent_edges,
+    }
+}
+
+pub fn remove_degenerate_triangles(mesh: &mut MeshData) {
+    let ids = position_ids(mesh);
+    let indices = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|triangle| !is_degenerate(mesh, &ids, triangle))
+        .flatten()
+        .copied()
+        .collect();
+    mesh.indices = indices;
+}
+
+pub fn weld_duplicate_vertices(mesh: &mut MeshData) {
+    let mut first_index = HashMap::new();
+    let mut remap = Vec::with_capacity(mesh.positions.len());
+    let mut kept = Vec::new();
+    for index in 0..mesh.positions.len() {
+        let next = kept.len();
+        let new_index = *first_index.entry(vertex_key(mesh, index)).or_insert(next);
+        if new_index == next {
+            kept.push(index);
+        }
+        remap.push(new_index as u32);
+    }
+
+    // Indices past the end stay past it, for remove_degenerate_triangles to take out
+    mesh.indices = mesh
+        .indices
+        .iter()
+        .map(|&index| remap.get(index as usize).copied().unwrap_or(u32::MAX))
+        .collect();
+    mesh.positions = kept.iter().map(|&index| mesh.positions[index]).collect();
+    if !mesh.normals.is_empty() {
+        mesh.normals = kept.iter().map(|&index| mesh.normals[index]).collect();
+    }
+    if !mesh.tangents.is_empty() {
+        mesh.tangents = kept.iter().map(|&index| mesh.tangents[index]).collect();
+    }
+    if !mesh.uvs.is_empty() {
+        mesh.uvs = kept.iter().map(|&index| mesh.uvs[index]).collect();
+    }
+    if !mesh.uvs1.is_empty() {
+        mesh.uvs1 = kept.iter().map(|&index| mesh.uvs1[index]).collect();
+    }
+    if !mesh.joints.is_empty() {
+        mesh.joints = kept.iter().map(|&index| mesh.joints[index]).collect();
+    }
+    if !mesh.weights.is_empty() {
+        mesh.weights = kept.iter().map(|&index| mesh.weights[index]).collect();
+    }
+}
+
+// Flood fills each connected patch from its first triangle, flipping neighbours
+// that disagree with it. Non-manifold edges are not walked across.
+pub fn fix_winding(mesh: &mut MeshData) {
+    let ids = position_ids(mesh);
+    let edges = edge_uses(mesh, &ids);
+
+    let triangle_count = mesh.indices.len() / 3;
+    let mut neighbours: Vec<Vec<(usize, bool)>> = vec![Vec::new(); triangle_count];
+    for uses in edges.values() {
+        if let [(a, a_forwards), (b, b_forwards)] = uses.as_slice() {
+            // Consistent neighbours walk a shared edge in opposite directions
+            let same_direction = a_forwards == b_forwards;
+            neighbours[*a].push((*b, same_direction));
+            neighbours[*b].push((*a, same_direction));
+        }
+    }
+
+    let mut flipped: Vec<Option<bool>> = vec![None; triangle_count];
+    for start in 0..triangle_count {
+        if flipped[start].is_some() {
+            continue;
+        }
+        flipped[start] = Some(false);
+        let mut queue = VecDeque::from(vec![start]);
+        while let Some(triangle) = queue.pop_front() {
+            let triangle_flipped = flipped[triangle].unwrap();
+            for &(neighbour, same_direction) in &neighbours[triangle] {
+                if flipped[neighbour].is_none() {
+                    flipped[neighbour] = Some(triangle_flipped ^ same_direction);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    for (triangle, flip) in mesh.indices.chunks_exact_mut(3).zip(flipped) {
+        if flip == Some(true) {
+            triangle.swap(1, 2);
+        }
+    }
+}
+
+// Runs `fix` on a copy, and keeps it unless it took out every triangle. rend3 can't build
+// a mesh without any.
+pub fn apply_fix(mesh: &mut MeshData, fix: fn(&mut MeshData)) -> bool {
+    let mut fixed = mesh.clone();
+    fix(&mut fixed);
+    if fixed.indices.is_empty() {
+        return false;
+    }
+    *mesh = fixed;
+    true
+}
+
+pub fn diagnostics_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    label: &str,
+    object: &mut SceneObject,
+    clay: Option<&rend3::types::MaterialHandle>,
+) {
+    let mesh = match &mut object.mesh_data {
+        Some(mesh) => mesh,
+        None => return,
+    };
+    let report = *object.report.get_or_insert_with(|| analyze(mesh));
+
+    ui.label(format!("{} ({} triangles)", label, report.triangles));
+    let mut fix: Option<fn(&mut MeshData)> = None;
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Degenerate triangles: {}",
+            report.degenerate_triangles
+        ));
+        if report.degenerate_triangles > 0 && ui.button("Remove").clicked() {
+            fix = Some(remove_degenerate_triangles);
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label(format!("Duplicate vertices: {}", report.duplicate_vertices));
+        if report.duplicate_vertices > 0 && ui.button("Weld").clicked() {
+            fix = Some(weld_duplicate_vertices);
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Inconsistent winding: {}",
+            report.inconsistent_edges
+        ));
+        if report.inconsistent_edges > 0 && ui.button("Fix").clicked() {
+            fix = Some(fix_winding);
+        }
+    });
+    // There is no safe automatic fix for these, they need to be looked at in the source asset
+    ui.label(format!("Non-manifold edges: {}", report.non_manifold_edges));
+
+    if let Some(fix) = fix {
+        if !apply_fix(mesh, fix) {
+            tracing::error!(
+                "{} would have no triangles left, leaving it as it is",
+                label
+            );
+            return;
+        }
+        let mesh = renderer.add_mesh(mesh.build());
+        object.report = None;
+        object.set_mesh(renderer, mesh, clay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(positions: &[Vec3], indices: &[u32]) -> MeshData {
+        MeshData {
+            positions: positions.to_vec(),
+            indices: indices.to_vec(),
+            ..MeshData::default()
+        }
+    }
+
+    // Two triangles of a unit square, wound the same way
+    const QUAD: [Vec3; 4] = [
+        Vec3::new(0., 0., 0.),
+        Vec3::new(1., 0., 0.),
+        Vec3::new(1., 1., 0.),
+        Vec3::new(0., 1., 0.),
+    ];
+
+    #[test]
+    fn clean_mesh() {
+        let report = analyze(&mesh(&QUAD, &[0, 1, 2, 0, 2, 3]));
+        assert_eq!(report.triangles, 2);
+        assert_eq!(report.degenerate_triangles, 0);
+        assert_eq!(report.duplicate_vertices, 0);
+        assert_eq!(report.non_manifold_edges, 0);
+        assert_eq!(report.inconsistent_edges, 0);
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        let mut broken = mesh(&QUAD, &[0, 1, 2, 0, 2, 7]);
+        assert_eq!(analyze(&broken).degenerate_triangles, 1);
+        remove_degenerate_triangles(&mut broken);
+        assert_eq!(broken.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn welding_keeps_out_of_range_indices_out_of_range() {
+        let positions = [QUAD[0], QUAD[1], QUAD[2], QUAD[0]];
+        let mut broken = mesh(&positions, &[0, 1, 2, 3, 2, 9]);
+        weld_duplicate_vertices(&mut broken);
+        assert_eq!(broken.positions.len(), 3);
+        assert_eq!(analyze(&broken).degenerate_triangles, 1);
+        remove_degenerate_triangles(&mut broken);
+        assert_eq!(broken.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn degenerate_triangles() {
+        let positions = [QUAD[0], QUAD[1], QUAD[2], Vec3::new(2., 0., 0.)];
+        // A repeated corner, and three corners on one line
+        let mut broken = mesh(&positions, &[0, 1, 2, 0, 0, 1, 0, 1, 3]);
+        assert_eq!(analyze(&broken).degenerate_triangles, 2);
+        remove_degenerate_triangles(&mut broken);
+        assert_eq!(broken.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn nan_positions() {
+        let positions = [QUAD[0], QUAD[1], QUAD[2], Vec3::new(f32::NAN, 0., 0.)];
+        let mut broken = mesh(&positions, &[0, 1, 2, 0, 2, 3]);
+        let report = analyze(&broken);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.inconsistent_edges, 0);
+        remove_degenerate_triangles(&mut broken);
+        assert_eq!(broken.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn flipped_triangle() {
+        let mut broken = mesh(&QUAD, &[0, 1, 2, 0, 3, 2]);
+        assert_eq!(analyze(&broken).inconsistent_edges, 1);
+        fix_winding(&mut broken);
+        assert_eq!(analyze(&broken).inconsistent_edges, 0);
+    }
+
+    #[test]
+    fn fix_that_leaves_no_triangles_is_refused() {
+        let mut broken = mesh(&QUAD, &[0, 0, 1, 1, 2, 9]);
+        assert!(!apply_fix(&mut broken, remove_degenerate_triangles));
+        assert_eq!(broken.indices, vec![0, 0, 1, 1, 2, 9]);
+
+        let mut partly = mesh(&QUAD, &[0, 1, 2, 1, 2, 9]);
+        assert!(apply_fix(&mut partly, remove_degenerate_triangles));
+        assert_eq!(partly.indices, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,777 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+// CPU side copy of a mesh, kept so it can be inspected and rebuilt after upload.
+// Winding and handedness follow glTF, build() converts like the importer always has.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    // Second UV set (TEXCOORD_1), often lightmap UVs. Empty if the mesh has none.
+    pub uvs1: Vec<Vec2>,
+    pub indices: Vec<u32>,
+    // Skinning inputs (JOINTS_0 and WEIGHTS_0), empty for meshes that aren't skinned
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<Vec4>,
+    // Which set gets uploaded as uv0, the only set rend3's PBR shader samples
+    pub uv_set: usize,
+}
+
+impl MeshData {
+    // Axis aligned (min, max) in the mesh's own space, None without vertices
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let first = *self.positions.first()?;
+        Some(
+            self.positions
+                .iter()
+                .fold((first, first), |(min, max), &position| {
+                    (min.min(position), max.max(position))
+                }),
+        )
+    }
+
+    pub fn build(&self) -> rend3::types::Mesh {
+        self.builder().build().unwrap()
+    }
+
+    // Same mesh with a colour per vertex, for materials that take their albedo from it
+    pub fn build_with_colors(&self, colors: Vec<[u8; 4]>) -> rend3::types::Mesh {
+        self.builder().with_vertex_colors(colors).build().unwrap()
+    }
+
+    fn builder(&self) -> rend3::types::MeshBuilder {
+        let mut builder =
+            rend3::types::MeshBuilder::new(self.positions.clone(), rend3::types::Handedness::Right);
+        // Anything missing gets generated by rend3
+        if !self.normals.is_empty() {
+            builder = builder.with_vertex_normals(self.normals.clone());
+        }
+        if !self.tangents.is_empty() {
+            builder = builder.with_vertex_tangents(self.tangents.clone());
+        }
+        let uvs = match self.uv_set {
+            1 => &self.uvs1,
+            _ => &self.uvs,
+        };
+        if !uvs.is_empty() {
+            builder = builder.with_vertex_uv0(uvs.clone());
+        }
+        if !self.joints.is_empty() && !self.weights.is_empty() {
+            builder = builder
+                .with_vertex_joint_indices(self.joints.clone())
+                .with_vertex_joint_weights(self.weights.clone());
+        }
+        builder
+            .with_indices(self.indices.clone())
+            .with_flip_winding_order()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Primitive {
+    Cube,
+    Sphere,
+    Plane,
+    Torus,
+    Cylinder,
+    Capsule,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 6] = [
+        Primitive::Cube,
+        Primitive::Sphere,
+        Primitive::Plane,
+        Primitive::Torus,
+        Primitive::Cylinder,
+        Primitive::Capsule,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Cube => "Cube",
+            Primitive::Sphere => "Sphere",
+            Primitive::Plane => "Plane",
+            Primitive::Torus => "Torus",
+            Primitive::Cylinder => "Cylinder",
+            Primitive::Capsule => "Capsule",
+        }
+    }
+
+    pub fn mesh_data(self) -> MeshData {
+        match self {
+            Primitive::Cube => create_cube(),
+            Primitive::Sphere => create_sphere(32, 64),
+            Primitive::Plane => create_plane(4.),
+            Primitive::Torus => create_torus(1., 0.35, 64),
+            Primitive::Cylinder => create_cylinder(1., 2., 64),
+            Primitive::Capsule => create_capsule(0.5, 1., 64),
+        }
+    }
+}
+
+// All primitives are built like imported meshes, counter-clockwise when seen from outside.
+// Tangents are left out for rend3 to generate from the UVs.
+fn primitive(
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    indices: Vec<u32>,
+) -> MeshData {
+    MeshData {
+        positions,
+        normals,
+        uvs,
+        indices,
+        ..MeshData::default()
+    }
+}
+
+// 2x2x2 with every face split off, so the edges stay hard
+pub fn create_cube() -> MeshData {
+    // (normal, u, v) with u cross v pointing along the normal
+    let faces = [
+        (Vec3::X, Vec3::Y, Vec3::Z),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::Z, Vec3::X),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+    ];
+
+    let mut positions = Vec::with_capacity(24);
+    let mut normals = Vec::with_capacity(24);
+    let mut uvs = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces {
+        let first = positions.len() as u32;
+        for (x, y) in [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)] {
+            positions.push(normal + u * x + v * y);
+            normals.push(normal);
+            uvs.push(Vec2::new((x + 1.) / 2., (y + 1.) / 2.));
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    primitive(positions, normals, uvs, indices)
+}
+
+// Unit sphere. The seam column is duplicated so the UVs wrap, the pole triangles that
+// would have no area are left out.
+pub fn create_sphere(rings: u32, sectors: u32) -> MeshData {
+    let rings = rings.max(2);
+    let sectors = sectors.max(3);
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    for ring in 0..=rings {
+        let theta = PI * ring as f32 / rings as f32;
+        for sector in 0..=sectors {
+            let phi = TAU * sector as f32 / sectors as f32;
+            positions.push(Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ));
+            uvs.push(Vec2::new(
+                sector as f32 / sectors as f32,
+                ring as f32 / rings as f32,
+            ));
+        }
+    }
+    // On a unit sphere the normal is the position
+    let normals = positions.clone();
+
+    let mut indices = Vec::new();
+    let stride = sectors + 1;
+    for ring in 0..rings {
+        for sector in 0..sectors {
+            let a = ring * stride + sector;
+            let b = a + stride;
+            let c = b + 1;
+            let d = a + 1;
+            if ring != 0 {
+                indices.extend([a, d, b]);
+            }
+            if ring != rings - 1 {
+                indices.extend([d, c, b]);
+            }
+        }
+    }
+
+    primitive(positions, normals, uvs, indices)
+}
+
+// Flat square on the XZ plane facing up
+pub fn create_plane(size: f32) -> MeshData {
+    let half = size / 2.;
+    let positions = vec![
+        Vec3::new(-half, 0., -half),
+        Vec3::new(half, 0., -half),
+        Vec3::new(half, 0., half),
+        Vec3::new(-half, 0., half),
+    ];
+    let uvs = vec![
+        Vec2::new(0., 0.),
+        Vec2::new(1., 0.),
+        Vec2::new(1., 1.),
+        Vec2::new(0., 1.),
+    ];
+
+    primitive(positions, vec![Vec3::Y; 4], uvs, vec![0, 2, 1, 0, 3, 2])
+}
+
+// Ring around the Y axis. The tube gets half as many segments as the ring.
+pub fn create_torus(major: f32, minor: f32, segments: u32) -> MeshData {
+    let ring_segments = segments.max(3);
+    let tube_segments = (segments / 2).max(3);
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for i in 0..=ring_segments {
+        let u = TAU * i as f32 / ring_segments as f32;
+        let center = Vec3::new(u.cos(), 0., u.sin()) * major;
+        for j in 0..=tube_segments {
+            let v = TAU * j as f32 / tube_segments as f32;
+            let normal = Vec3::new(v.cos() * u.cos(), v.sin(), v.cos() * u.sin());
+            positions.push(center + normal * minor);
+            normals.push(normal);
+            uvs.push(Vec2::new(
+                i as f32 / ring_segments as f32,
+                j as f32 / tube_segments as f32,
+            ));
+        }
+    }
+
+    let mut indices = Vec::new();
+    let stride = tube_segments + 1;
+    for i in 0..ring_segments {
+        for j in 0..tube_segments {
+            let a = i * stride + j;
+            let b = a + stride;
+            let c = b + 1;
+            let d = a + 1;
+            indices.extend([a, d, b, d, c, b]);
+        }
+    }
+
+    primitive(positions, normals, uvs, indices)
+}
+
+// Revolves a profile around the Y axis. Every profile point is (radius, height, normal in
+// the radius/height plane), listed from the top down like the sphere's rings. Bands of zero
+// height and the triangles meeting at a point on the axis are left out.
+fn lathe(profile: &[(f32, f32, Vec2)], sectors: u32) -> MeshData {
+    let sectors = sectors.max(3);
+
+    // V follows the distance along the profile, so the texture isn't squashed on the caps
+    let mut lengths = vec![0.];
+    for pair in profile.windows(2) {
+        let step = Vec2::new(pair[1].0 - pair[0].0, pair[1].1 - pair[0].1).length();
+        lengths.push(lengths.last().unwrap() + step);
+    }
+    let total = lengths.last().copied().unwrap_or(0.).max(f32::EPSILON);
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for (&(radius, height, normal), length) in profile.iter().zip(&lengths) {
+        let normal = normal.normalize();
+        for sector in 0..=sectors {
+            let phi = TAU * sector as f32 / sectors as f32;
+            positions.push(Vec3::new(radius * phi.cos(), height, radius * phi.sin()));
+            normals.push(Vec3::new(
+                normal.x * phi.cos(),
+                normal.y,
+                normal.x * phi.sin(),
+            ));
+            uvs.push(Vec2::new(sector as f32 / sectors as f32, length / total));
+        }
+    }
+
+    let mut indices = Vec::new();
+    let stride = sectors + 1;
+    for (row, pair) in profile.windows(2).enumerate() {
+        let ((top_radius, top_height, _), (bottom_radius, bottom_height, _)) = (pair[0], pair[1]);
+        if top_radius == bottom_radius && top_height == bottom_height {
+            continue;
+        }
+        for sector in 0..sectors {
+            let a = row as u32 * stride + sector;
+            let b = a + stride;
+            let c = b + 1;
+            let d = a + 1;
+            if top_radius != 0. {
+                indices.extend([a, d, b]);
+            }
+            if bottom_radius != 0. {
+                indices.extend([d, c, b]);
+            }
+        }
+    }
+
+    primitive(positions, normals, uvs, indices)
+}
+
+// Upright around the Y axis and centred on the origin, with flat caps that keep hard edges
+pub fn create_cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    let half = height / 2.;
+    lathe(
+        &[
+            (0., half, Vec2::Y),
+            (radius, half, Vec2::Y),
+            (radius, half, Vec2::X),
+            (radius, -half, Vec2::X),
+            (radius, -half, Vec2::NEG_Y),
+            (0., -half, Vec2::NEG_Y),
+        ],
+        segments,
+    )
+}
+
+// Cylinder of `height` between two hemispheres, so it is height + 2 * radius tall overall
+pub fn create_capsule(radius: f32, height: f32, segments: u32) -> MeshData {
+    let half = height / 2.;
+    let rings = (segments / 4).max(2);
+
+    let mut profile = Vec::new();
+    for (center, start) in [(half, 0.), (-half, PI / 2.)] {
+        for ring in 0..=rings {
+            let theta = start + PI / 2. * ring as f32 / rings as f32;
+            let normal = Vec2::new(theta.sin(), theta.cos());
+            profile.push((radius * normal.x, center + radius * normal.y, normal));
+        }
+    }
+    // The poles land on the axis exactly, so their triangles are skipped
+    profile[0].0 = 0.;
+    profile.last_mut().unwrap().0 = 0.;
+
+    lathe(&profile, segments)
+}
+
+// A flat strip from `a` to `b`, `offset` out to either side. Wound both ways so it shows
+// from above and below. The vertices go a, b, b, a.
+pub fn push_strip(mesh: &mut MeshData, (a, b): (Vec3, Vec3), offset: Vec3) {
+    let first = mesh.positions.len() as u32;
+    mesh.positions
+        .extend([a - offset, b - offset, b + offset, a + offset]);
+    mesh.indices
+        .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    mesh.indices
+        .extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+}
+
+// Two strips crossed along the line, so it shows edge on too
+pub fn push_crossed_strips(mesh: &mut MeshData, (a, b): (Vec3, Vec3), width: f32) {
+    let direction = (b - a).normalize_or_zero();
+    let helper = if direction.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let side = direction.cross(helper).normalize_or_zero() * width;
+    let up = direction.cross(side).normalize_or_zero() * width;
+    for offset in [side, up] {
+        push_strip(mesh, (a, b), offset);
+    }
+}
+
+// Every edge becomes two thin crossed strips, so it shows from any angle. Shared edges are
+// only emitted once.
+pub fn create_wireframe(mesh: &MeshData, width: f32) -> MeshData {
+    let mut edges = std::collections::HashSet::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        for (a, b) in [
+            (triangle[0], triangle[1]),
+            (triangle[1], triangle[2]),
+            (triangle[2], triangle[0]),
+        ] {
+            edges.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    let mut wireframe = MeshData::default();
+    for (a, b) in edges {
+        let (a, b) = (mesh.positions[a as usize], mesh.positions[b as usize]);
+        push_crossed_strips(&mut wireframe, (a, b), width);
+    }
+    // Unlit, but rend3 still wants a normal per vertex
+    wireframe.normals = vec![Vec3::Y; wireframe.positions.len()];
+    wireframe
+}
+
+// Box around all eight transformed corners, so it still contains the mesh after rotation
+pub fn transform_bounds((min, max): (Vec3, Vec3), transform: Mat4) -> (Vec3, Vec3) {
+    let mut corners = (0..8).map(|corner| {
+        transform.transform_point3(Vec3::new(
+            if corner & 1 == 0 { min.x } else { max.x },
+            if corner & 2 == 0 { min.y } else { max.y },
+            if corner & 4 == 0 { min.z } else { max.z },
+        ))
+    });
+    let first = corners.next().unwrap();
+    corners.fold((first, first), |(min, max), corner| {
+        (min.min(corner), max.max(corner))
+    })
+}
+
+// Area weighted face normals summed per position, so vertices split along UV seams
+// still end up with the same normal
+pub fn compute_smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut ids = HashMap::new();
+    let vertex_ids: Vec<usize> = positions
+        .iter()
+        .map(|position| {
+            let next_id = ids.len();
+            let key = [
+                position.x.to_bits(),
+                position.y.to_bits(),
+                position.z.to_bits(),
+            ];
+            *ids.entry(key).or_insert(next_id)
+        })
+        .collect();
+
+    let mut sums = vec![Vec3::ZERO; ids.len()];
+    for triangle in indices.chunks_exact(3) {
+        let (a, b, c) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        // Not normalized, so bigger faces count for more
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for index in [a, b, c] {
+            sums[vertex_ids[index]] += normal;
+        }
+    }
+
+    vertex_ids
+        .iter()
+        .map(|&id| sums[id].normalize_or_zero())
+        .collect()
+}
+
+// Vertex clustering on a grid with `cells` cells along the longest side. Every vertex in a
+// cell becomes one at their average, and triangles that collapse are dropped. Coarse, but
+// fast enough to run on the click of a button. Skinning isn't carried over.
+pub fn decimate(mesh: &MeshData, cells: u32) -> MeshData {
+    let (min, max) = match mesh.bounds() {
+        Some(bounds) => bounds,
+        None => return MeshData::default(),
+    };
+    let cell = (max - min).max_element() / cells.max(1) as f32;
+    if cell <= 0. {
+        return MeshData::default();
+    }
+
+    let mut out = MeshData {
+        uv_set: mesh.uv_set,
+        ..MeshData::default()
+    };
+    let mut clusters = HashMap::new();
+    // Sum of the positions in each cell and how many there were
+    let mut sums: Vec<(Vec3, f32)> = Vec::new();
+    let mut remap = Vec::with_capacity(mesh.positions.len());
+    for (index, &position) in mesh.positions.iter().enumerate() {
+        let key = ((position - min) / cell).floor();
+        let key = (key.x as i32, key.y as i32, key.z as i32);
+        // UVs come from the first vertex in the cell, there's no averaging across seams
+        let cluster = *clusters.entry(key).or_insert_with(|| {
+            sums.push((Vec3::ZERO, 0.));
+            out.uvs.extend(mesh.uvs.get(index));
+            out.uvs1.extend(mesh.uvs1.get(index));
+            sums.len() as u32 - 1
+        });
+        sums[cluster as usize].0 += position;
+        sums[cluster as usize].1 += 1.;
+        remap.push(cluster);
+    }
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| remap[triangle[corner] as usize]);
+        if a != b && b != c && c != a {
+            out.indices.extend([a, b, c]);
+        }
+    }
+    out.positions = sums.iter().map(|&(sum, count)| sum / count).collect();
+    out.normals = compute_smooth_normals(&out.positions, &out.indices);
+    out
+}
+
+fn point_in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let ab = (b - a).perp_dot(point - a);
+    let bc = (c - b).perp_dot(point - b);
+    let ca = (a - c).perp_dot(point - c);
+    (ab >= 0. && bc >= 0. && ca >= 0.) || (ab <= 0. && bc <= 0. && ca <= 0.)
+}
+
+// Ear clipping, so concave faces (which a plain fan folds over itself) come out right.
+// The polygon is projected onto its own plane first, so it works for any orientation.
+pub fn triangulate_polygon(positions: &[Vec3], polygon: &[u32]) -> Vec<[u32; 3]> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+    if polygon.len() == 3 {
+        return vec![[polygon[0], polygon[1], polygon[2]]];
+    }
+
+    // Newell's method, robust for concave and slightly non-planar polygons
+    let mut normal = Vec3::ZERO;
+    for (i, &index) in polygon.iter().enumerate() {
+        let current = positions[index as usize];
+        let next = positions[polygon[(i + 1) % polygon.len()] as usize];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    // Zero area, there is no plane to clip ears in so just fan it
+    if normal.length_squared() <= f32::EPSILON {
+        return (1..polygon.len() - 1)
+            .map(|i| [polygon[0], polygon[i], polygon[i + 1]])
+            .collect();
+    }
+    let normal = normal.normalize();
+
+    let helper = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+
+    let points: Vec<Vec2> = polygon
+        .iter()
+        .map(|&index| {
+            let position = positions[index as usize];
+            Vec2::new(position.dot(tangent), position.dot(bitangent))
+        })
+        .collect();
+
+    let mut area: f32 = 0.;
+    for i in 0..points.len() {
+        area += points[i].perp_dot(points[(i + 1) % points.len()]);
+    }
+    let winding = area.signum();
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::with_capacity(polygon.len() - 2);
+    let mut current = 0;
+    let mut attempts = 0;
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let prev = remaining[(current + count - 1) % count];
+        let ear = remaining[current % count];
+        let next = remaining[(current + 1) % count];
+
+        let (a, b, c) = (points[prev], points[ear], points[next]);
+        let convex = (b - a).perp_dot(c - b) * winding > 0.;
+        let is_ear = convex
+            && !remaining.iter().any(|&other| {
+                other != prev
+                    && other != ear
+                    && other != next
+                    && points[other] != a
+                    && points[other] != b
+                    && points[other] != c
+                    && point_in_triangle(points[other], a, b, c)
+            });
+
+        // A self-intersecting polygon can run out of ears, clip anyway rather than loop forever
+        if is_ear || attempts > count {
+            triangles.push([polygon[prev], polygon[ear], polygon[next]]);
+            remaining.remove(current % count);
+            attempts = 0;
+        } else {
+            current += 1;
+            attempts += 1;
+        }
+        current %= remaining.len();
+    }
+
+    triangles.push([
+        polygon[remaining[0]],
+        polygon[remaining[1]],
+        polygon[remaining[2]],
+    ]);
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Twice the area of each triangle along `normal`, negative when it's wound the other way
+    fn signed_areas(positions: &[Vec3], triangles: &[[u32; 3]], normal: Vec3) -> Vec<f32> {
+        triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let (a, b, c) = (
+                    positions[a as usize],
+                    positions[b as usize],
+                    positions[c as usize],
+                );
+                (b - a).cross(c - a).dot(normal)
+            })
+            .collect()
+    }
+
+    fn polygon(count: usize) -> Vec<u32> {
+        (0..count as u32).collect()
+    }
+
+    #[test]
+    fn convex_polygon() {
+        let positions = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(1., 1., 0.),
+            Vec3::new(0., 1., 0.),
+        ];
+        let triangles = triangulate_polygon(&positions, &polygon(4));
+        assert_eq!(triangles.len(), 2);
+        let areas = signed_areas(&positions, &triangles, Vec3::Z);
+        assert!(areas.iter().all(|&area| area > 0.));
+        assert!((areas.iter().sum::<f32>() - 2.).abs() < 1e-5);
+    }
+
+    // A fan from the first corner folds back over the notch
+    #[test]
+    fn concave_polygon() {
+        let positions = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(2., 1., 0.),
+            Vec3::new(0., 2., 0.),
+            Vec3::new(1., 1., 0.),
+        ];
+        let triangles = triangulate_polygon(&positions, &polygon(4));
+        assert_eq!(triangles.len(), 2);
+        let areas = signed_areas(&positions, &triangles, Vec3::Z);
+        assert!(areas.iter().all(|&area| area > 0.));
+        assert!((areas.iter().sum::<f32>() - 2.).abs() < 1e-5);
+    }
+
+    // The plane comes from the polygon, not the axes
+    #[test]
+    fn concave_polygon_facing_up() {
+        let positions = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(0., 0., 2.),
+            Vec3::new(2., 0., 2.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(1., 0., 1.),
+        ];
+        let triangles = triangulate_polygon(&positions, &polygon(5));
+        assert_eq!(triangles.len(), 3);
+        let areas = signed_areas(&positions, &triangles, Vec3::Y);
+        assert!(areas.iter().all(|&area| area > 0.));
+        assert!((areas.iter().sum::<f32>() - 6.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn collinear_corners() {
+        let positions = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(2., 2., 0.),
+            Vec3::new(0., 2., 0.),
+        ];
+        let triangles = triangulate_polygon(&positions, &polygon(5));
+        assert_eq!(triangles.len(), 3);
+        let areas = signed_areas(&positions, &triangles, Vec3::Z);
+        assert!(areas.iter().all(|&area| area > 0.));
+        assert!((areas.iter().sum::<f32>() - 8.).abs() < 1e-5);
+    }
+
+    // All on one line, there's nothing to clip but every corner is still used
+    #[test]
+    fn zero_area_polygon() {
+        let positions = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(3., 0., 0.),
+        ];
+        let triangles = triangulate_polygon(&positions, &polygon(4));
+        assert_eq!(triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    // Every triangle faces the way its vertex normals do, which is out of the shape, and
+    // nothing indexes past the vertices or has no area
+    #[test]
+    fn primitives_wind_outwards() {
+        for primitive in Primitive::ALL {
+            let mesh = primitive.mesh_data();
+            let name = primitive.name();
+            assert_eq!(mesh.normals.len(), mesh.positions.len(), "{}", name);
+            assert_eq!(mesh.uvs.len(), mesh.positions.len(), "{}", name);
+            assert_eq!(mesh.indices.len() % 3, 0, "{}", name);
+            assert!(!mesh.indices.is_empty(), "{}", name);
+            assert!(
+                mesh.indices
+                    .iter()
+                    .all(|&index| (index as usize) < mesh.positions.len()),
+                "{}",
+                name
+            );
+
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+                let face = (mesh.positions[b] - mesh.positions[a])
+                    .cross(mesh.positions[c] - mesh.positions[a]);
+                assert!(face.length() > 1e-6, "{} {:?}", name, triangle);
+                let normal = mesh.normals[a] + mesh.normals[b] + mesh.normals[c];
+                assert!(face.dot(normal) > 0., "{} {:?}", name, triangle);
+            }
+            for normal in &mesh.normals {
+                assert!((normal.length() - 1.).abs() < 1e-4, "{}", name);
+            }
+            for uv in &mesh.uvs {
+                assert!(
+                    (0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    // Both strips lie `width` off the line and cross at right angles, whichever way it runs
+    #[test]
+    fn crossed_strips() {
+        for direction in [Vec3::X, Vec3::Y, Vec3::Z, Vec3::ONE] {
+            let mut mesh = MeshData::default();
+            push_crossed_strips(&mut mesh, (Vec3::ZERO, direction * 2.), 0.1);
+            assert_eq!(mesh.positions.len(), 8);
+            assert_eq!(mesh.indices.len(), 24);
+            let side = mesh.positions[3] - mesh.positions[0];
+            let up = mesh.positions[7] - mesh.positions[4];
+            for across in [side, up] {
+                assert!((across.length() - 0.2).abs() < 1e-5, "{:?}", direction);
+                assert!(across.dot(direction).abs() < 1e-5, "{:?}", direction);
+            }
+            assert!(side.dot(up).abs() < 1e-5, "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn too_few_corners() {
+        let positions = [Vec3::ZERO, Vec3::X];
+        assert!(triangulate_polygon(&positions, &polygon(2)).is_empty());
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        assert_eq!(
+            triangulate_polygon(&positions, &polygon(3)),
+            vec![[0, 1, 2]]
+        );
+    }
+}
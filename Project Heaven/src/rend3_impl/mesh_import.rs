@@ -0,0 +1,798 @@
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::gltf_animation::NodeTree;
+use super::mesh_generator::{compute_smooth_normals, triangulate_polygon, MeshData};
+use super::mesh_importer::{GltfLoadError, GltfModel, GltfScene, NormalsMode};
+use super::scene::{PbrFactors, SceneObject};
+
+// The little material information OBJ and STL carry
+pub struct ImportedMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub roughness: f32,
+    pub metallic: f32,
+    // Decoded on the worker thread already, only the upload is left
+    pub albedo: Option<(PathBuf, image::RgbaImage)>,
+}
+
+impl ImportedMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            base_color: [0.8, 0.8, 0.8, 1.],
+            roughness: 0.8,
+            metallic: 0.,
+            albedo: None,
+        }
+    }
+
+    fn upload(&self, renderer: &rend3::Renderer) -> rend3::types::MaterialHandle {
+        use rend3_routine::pbr::{AlbedoComponent, PbrMaterial, Transparency};
+
+        let base_color = Vec4::from(self.base_color);
+        let albedo = match &self.albedo {
+            Some((path, image)) => AlbedoComponent::TextureValue {
+                texture: renderer.add_texture_2d(rend3::types::Texture {
+                    label: Some(path.display().to_string()),
+                    data: image.as_raw().clone(),
+                    format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+                    size: glam::UVec2::new(image.width(), image.height()),
+                    mip_count: rend3::types::MipmapCount::Maximum,
+                    mip_source: rend3::types::MipmapSource::Generated,
+                }),
+                value: base_color,
+            },
+            None => AlbedoComponent::Value(base_color),
+        };
+        renderer.add_material(PbrMaterial {
+            albedo,
+            roughness_factor: Some(self.roughness),
+            metallic_factor: Some(self.metallic),
+            transparency: if self.base_color[3] < 1. {
+                Transparency::Blend
+            } else {
+                Transparency::Opaque
+            },
+            ..PbrMaterial::default()
+        })
+    }
+}
+
+// One object of the file, split further wherever the material changes, or one glTF
+// primitive. OBJ and STL leave the normals empty when the file has none, the normals mode
+// decides what happens then.
+pub struct ImportedMesh {
+    pub name: String,
+    pub mesh_data: MeshData,
+    // Index into the file's materials, ImportedMeshes::materials for OBJ and STL. None for
+    // the plain default one.
+    pub material: Option<usize>,
+}
+
+// What OBJ and STL files are read into, the counterpart of the glTF document and buffers
+pub struct ImportedMeshes {
+    pub meshes: Vec<ImportedMesh>,
+    pub materials: Vec<ImportedMaterial>,
+}
+
+impl ImportedMeshes {
+    fn material<'a>(
+        &'a self,
+        default: &'a ImportedMaterial,
+        index: Option<usize>,
+    ) -> &'a ImportedMaterial {
+        index.map_or(default, |index| &self.materials[index])
+    }
+}
+
+fn parse_error(path: &Path, line: usize, what: &str) -> GltfLoadError {
+    GltfLoadError::Parse(path.to_path_buf(), format!("line {}: {}", line, what))
+}
+
+fn parse_floats<'a>(
+    path: &Path,
+    line: usize,
+    values: impl Iterator<Item = &'a str>,
+) -> Result<Vec<f32>, GltfLoadError> {
+    values
+        .map(|value| {
+            value
+                .parse::<f32>()
+                .map_err(|_| parse_error(path, line, &format!("\"{}\" isn't a number", value)))
+        })
+        .collect()
+}
+
+fn read_to_string(path: &Path) -> Result<String, GltfLoadError> {
+    std::fs::read_to_string(path).map_err(|ex| GltfLoadError::Io(path.to_path_buf(), ex))
+}
+
+// Options like `-s 1 1 1` can come before the file name, which is always last
+fn mtl_texture(directory: &Path, rest: &str) -> Option<PathBuf> {
+    rest.split_whitespace()
+        .last()
+        .map(|name| directory.join(name.replace('\\', "/")))
+}
+
+// Reads every material of an MTL file. Ns is a Phong exponent and is mapped onto roughness,
+// the PBR extension's Pr and Pm win when they are there.
+fn load_mtl(path: &Path, materials: &mut Vec<ImportedMaterial>) -> Result<(), GltfLoadError> {
+    let text = read_to_string(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut has_pbr_roughness = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        if keyword == "newmtl" {
+            materials.push(ImportedMaterial::new(rest));
+            has_pbr_roughness = false;
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+        let values = || parse_floats(path, number + 1, rest.split_whitespace());
+        match keyword {
+            "Kd" => {
+                let color = values()?;
+                if color.len() >= 3 {
+                    material.base_color[..3].copy_from_slice(&color[..3]);
+                }
+            }
+            "d" => material.base_color[3] = values()?.first().copied().unwrap_or(1.),
+            "Tr" => material.base_color[3] = 1. - values()?.first().copied().unwrap_or(0.),
+            "Ns" if !has_pbr_roughness => {
+                let exponent = values()?.first().copied().unwrap_or(0.).max(0.);
+                material.roughness = (2. / (exponent + 2.)).sqrt();
+            }
+            "Pr" => {
+                material.roughness = values()?.first().copied().unwrap_or(0.8);
+                has_pbr_roughness = true;
+            }
+            "Pm" => material.metallic = values()?.first().copied().unwrap_or(0.),
+            // A texture that can't be read leaves the material plain, like a missing library
+            "map_Kd" => {
+                if let Some(texture) = mtl_texture(directory, rest) {
+                    match image::open(&texture) {
+                        Ok(image) => material.albedo = Some((texture, image.to_rgba8())),
+                        Err(ex) => tracing::error!(
+                            "{}",
+                            GltfLoadError::TextureDecode(texture, ex.to_string())
+                        ),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// One `mtllib` can name several libraries
+fn mtl_libraries<'a>(directory: &'a Path, rest: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+    rest.split_whitespace()
+        .map(move |name| directory.join(name.replace('\\', "/")))
+}
+
+// MTL libraries an OBJ file names and the textures they name, for watching them
+pub fn obj_dependencies(path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let libraries: Vec<PathBuf> = text
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("mtllib "))
+        .flat_map(|rest| mtl_libraries(directory, rest))
+        .collect();
+    let mut paths = libraries.clone();
+    for library in libraries {
+        let text = std::fs::read_to_string(&library).unwrap_or_default();
+        let library_directory = library.parent().unwrap_or_else(|| Path::new(""));
+        paths.extend(text.lines().filter_map(|line| {
+            let line = line.trim();
+            let rest = line.strip_prefix("map_Kd ")?;
+            mtl_texture(library_directory, rest)
+        }));
+    }
+    paths
+}
+
+// Looks up a 1-based (or negative, counting back from the end) OBJ index
+fn obj_index(path: &Path, line: usize, value: &str, count: usize) -> Result<usize, GltfLoadError> {
+    let index: i64 = value
+        .parse()
+        .map_err(|_| parse_error(path, line, &format!("bad face index \"{}\"", value)))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved as usize >= count {
+        return Err(parse_error(
+            path,
+            line,
+            &format!("face index {} is out of range", index),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// The mesh being built from faces, until the object or material changes
+#[derive(Default)]
+struct ObjPart {
+    mesh_data: MeshData,
+    // (position, uv, normal) of the file to this part's vertex
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    missing_normals: bool,
+    has_uvs: bool,
+}
+
+impl ObjPart {
+    fn finish(mut self, name: String, material: Option<usize>) -> ImportedMesh {
+        if self.missing_normals {
+            self.mesh_data.normals.clear();
+        }
+        if !self.has_uvs {
+            self.mesh_data.uvs.clear();
+        }
+        ImportedMesh {
+            name,
+            mesh_data: self.mesh_data,
+            material,
+        }
+    }
+}
+
+pub fn import_obj(path: &Path) -> Result<ImportedMeshes, GltfLoadError> {
+    puffin::profile_function!();
+    let text = read_to_string(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials = Vec::new();
+    let mut meshes = Vec::new();
+
+    let default_name = path.file_stem().map_or_else(
+        || "OBJ".to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    );
+    let mut name = default_name;
+    let mut material = None;
+    let mut part = ObjPart::default();
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match keyword {
+            "v" => {
+                let values = parse_floats(path, number, rest.split_whitespace())?;
+                if values.len() < 3 {
+                    return Err(parse_error(path, number, "vertex needs three coordinates"));
+                }
+                positions.push(Vec3::new(values[0], values[1], values[2]));
+            }
+            "vt" => {
+                let values = parse_floats(path, number, rest.split_whitespace())?;
+                // OBJ puts v = 0 at the bottom of the image, rend3 at the top
+                uvs.push(Vec2::new(
+                    values.first().copied().unwrap_or(0.),
+                    1. - values.get(1).copied().unwrap_or(0.),
+                ));
+            }
+            "vn" => {
+                let values = parse_floats(path, number, rest.split_whitespace())?;
+                if values.len() < 3 {
+                    return Err(parse_error(path, number, "normal needs three components"));
+                }
+                normals.push(Vec3::new(values[0], values[1], values[2]).normalize_or_zero());
+            }
+            "f" => {
+                let mut polygon = Vec::new();
+                for corner in rest.split_whitespace() {
+                    let mut fields = corner.split('/');
+                    let position =
+                        obj_index(path, number, fields.next().unwrap_or(""), positions.len())?;
+                    let uv = match fields.next() {
+                        Some(value) if !value.is_empty() => {
+                            Some(obj_index(path, number, value, uvs.len())?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match fields.next() {
+                        Some(value) if !value.is_empty() => {
+                            Some(obj_index(path, number, value, normals.len())?)
+                        }
+                        _ => None,
+                    };
+
+                    let mesh_data = &mut part.mesh_data;
+                    let index = *part
+                        .vertices
+                        .entry((position, uv, normal))
+                        .or_insert_with(|| {
+                            mesh_data.positions.push(positions[position]);
+                            mesh_data
+                                .uvs
+                                .push(uv.map_or(Vec2::ZERO, |uv: usize| uvs[uv]));
+                            mesh_data
+                                .normals
+                                .push(normal.map_or(Vec3::ZERO, |normal: usize| normals[normal]));
+                            mesh_data.positions.len() as u32 - 1
+                        });
+                    part.has_uvs |= uv.is_some();
+                    part.missing_normals |= normal.is_none();
+                    polygon.push(index);
+                }
+                for triangle in triangulate_polygon(&part.mesh_data.positions, &polygon) {
+                    part.mesh_data.indices.extend(triangle);
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                if !part.mesh_data.indices.is_empty() {
+                    meshes.push(std::mem::take(&mut part).finish(name.clone(), material));
+                }
+                if keyword == "usemtl" {
+                    material = Some(
+                        match materials.iter().position(|known| known.name == rest) {
+                            Some(index) => index,
+                            // Unknown names still split the mesh, with the plain look
+                            None => {
+                                materials.push(ImportedMaterial::new(rest));
+                                materials.len() - 1
+                            }
+                        },
+                    );
+                } else if !rest.is_empty() {
+                    name = rest.to_string();
+                }
+            }
+            "mtllib" => {
+                // A missing library leaves the materials plain instead of failing the model
+                for library in mtl_libraries(directory, rest) {
+                    if let Err(ex) = load_mtl(&library, &mut materials) {
+                        tracing::error!("{}", ex);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !part.mesh_data.indices.is_empty() {
+        meshes.push(part.finish(name, material));
+    }
+
+    if meshes.is_empty() {
+        return Err(GltfLoadError::Unsupported(
+            path.to_path_buf(),
+            "file has no faces".to_string(),
+        ));
+    }
+    Ok(ImportedMeshes { meshes, materials })
+}
+
+// Every triangle keeps its own three vertices, STL doesn't share them
+fn push_facet(mesh_data: &mut MeshData, normal: Vec3, corners: [Vec3; 3]) {
+    for corner in corners {
+        mesh_data.positions.push(corner);
+        mesh_data.normals.push(normal);
+        mesh_data.indices.push(mesh_data.indices.len() as u32);
+    }
+}
+
+fn stl_meshes(path: &Path, name: String, mut mesh_data: MeshData) -> ImportedMeshes {
+    // Plenty of exporters write zero normals, those are recomputed from the winding instead
+    if mesh_data.normals.iter().any(|normal| *normal == Vec3::ZERO) {
+        mesh_data.normals.clear();
+    }
+    let name = if name.is_empty() {
+        path.file_stem().map_or_else(
+            || "STL".to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+    } else {
+        name
+    };
+    ImportedMeshes {
+        meshes: vec![ImportedMesh {
+            name,
+            mesh_data,
+            material: None,
+        }],
+        materials: Vec::new(),
+    }
+}
+
+fn read_vec3(bytes: &[u8]) -> Vec3 {
+    let float = |offset: usize| {
+        f32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    };
+    Vec3::new(float(0), float(4), float(8))
+}
+
+fn import_binary_stl(path: &Path, bytes: &[u8]) -> ImportedMeshes {
+    let mut mesh_data = MeshData::default();
+    // 80 byte header and a triangle count, then 50 bytes per triangle
+    for facet in bytes[84..].chunks_exact(50) {
+        push_facet(
+            &mut mesh_data,
+            read_vec3(&facet[0..12]).normalize_or_zero(),
+            [
+                read_vec3(&facet[12..24]),
+                read_vec3(&facet[24..36]),
+                read_vec3(&facet[36..48]),
+            ],
+        );
+    }
+    stl_meshes(path, String::new(), mesh_data)
+}
+
+fn import_ascii_stl(path: &Path, text: &str) -> Result<ImportedMeshes, GltfLoadError> {
+    let mut mesh_data = MeshData::default();
+    let mut name = String::new();
+    let mut normal = Vec3::ZERO;
+    let mut corners = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("solid") => name = words.collect::<Vec<_>>().join(" "),
+            Some("facet") => {
+                let values = parse_floats(path, number, words.skip(1))?;
+                if values.len() != 3 {
+                    return Err(parse_error(
+                        path,
+                        number,
+                        "facet normal needs three components",
+                    ));
+                }
+                normal = Vec3::new(values[0], values[1], values[2]).normalize_or_zero();
+                corners.clear();
+            }
+            Some("vertex") => {
+                let values = parse_floats(path, number, words)?;
+                if values.len() != 3 {
+                    return Err(parse_error(path, number, "vertex needs three coordinates"));
+                }
+                corners.push(Vec3::new(values[0], values[1], values[2]));
+            }
+            Some("endfacet") => {
+                if corners.len() != 3 {
+                    return Err(parse_error(
+                        path,
+                        number,
+                        "facet doesn't have three vertices",
+                    ));
+                }
+                push_facet(&mut mesh_data, normal, [corners[0], corners[1], corners[2]]);
+            }
+            _ => {}
+        }
+    }
+    Ok(stl_meshes(path, name, mesh_data))
+}
+
+// Binary files can start with "solid" too, so the size is what tells them apart
+pub fn import_stl(path: &Path) -> Result<ImportedMeshes, GltfLoadError> {
+    let bytes = std::fs::read(path).map_err(|ex| GltfLoadError::Io(path.to_path_buf(), ex))?;
+    import_stl_bytes(path, &bytes)
+}
+
+// `path` only names the file in errors
+pub fn import_stl_bytes(path: &Path, bytes: &[u8]) -> Result<ImportedMeshes, GltfLoadError> {
+    puffin::profile_function!();
+    let binary = bytes.len() >= 84 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        // A garbage count could overflow on 32 bit targets
+        count.checked_mul(50).and_then(|size| size.checked_add(84)) == Some(bytes.len())
+    };
+    let meshes = if binary {
+        import_binary_stl(path, bytes)
+    } else if bytes.starts_with(b"solid") {
+        let text = String::from_utf8_lossy(bytes);
+        import_ascii_stl(path, &text)?
+    } else {
+        return Err(GltfLoadError::Parse(
+            path.to_path_buf(),
+            "neither a binary nor an ASCII STL file".to_string(),
+        ));
+    };
+    if meshes.meshes[0].mesh_data.indices.is_empty() {
+        return Err(GltfLoadError::Unsupported(
+            path.to_path_buf(),
+            "file has no triangles".to_string(),
+        ));
+    }
+    Ok(meshes)
+}
+
+fn finish_normals(mesh_data: &MeshData, normals: NormalsMode) -> MeshData {
+    let mut mesh_data = mesh_data.clone();
+    if normals == NormalsMode::Recompute || mesh_data.normals.is_empty() {
+        mesh_data.normals = compute_smooth_normals(&mesh_data.positions, &mesh_data.indices);
+    }
+    mesh_data
+}
+
+// Geometry of the first mesh, like the glTF loader takes its first primitive
+pub fn first_mesh_data(meshes: &ImportedMeshes, normals: NormalsMode) -> MeshData {
+    finish_normals(&meshes.meshes[0].mesh_data, normals)
+}
+
+// The station only shows one mesh, so the rest of the file is left out
+pub fn finish_model(
+    renderer: &rend3::Renderer,
+    meshes: ImportedMeshes,
+    normals: NormalsMode,
+) -> GltfModel {
+    let mesh_data = first_mesh_data(&meshes, normals);
+    let default = ImportedMaterial::new("Default");
+    let material = meshes.material(&default, meshes.meshes[0].material);
+    GltfModel {
+        mesh: renderer.add_mesh(mesh_data.build()),
+        mesh_data,
+        material: material.upload(renderer),
+        base_color: material.base_color,
+        variants: Vec::new(),
+        cameras: Vec::new(),
+        extras: Vec::new(),
+    }
+}
+
+// Every mesh becomes an object under `root`. There is no hierarchy, so each object gets a
+// node of its own at the origin.
+pub fn finish_scene(
+    renderer: &rend3::Renderer,
+    meshes: ImportedMeshes,
+    normals: NormalsMode,
+    root: Mat4,
+) -> GltfScene {
+    // Only the materials something uses are uploaded, and each of those once
+    let default = ImportedMaterial::new("Default");
+    let mut materials = HashMap::new();
+    let objects: Vec<SceneObject> = meshes
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let mesh_data = finish_normals(&mesh.mesh_data, normals);
+            let material = meshes.material(&default, mesh.material);
+            let mut object = SceneObject::new(
+                renderer,
+                renderer.add_mesh(mesh_data.build()),
+                materials
+                    .entry(mesh.material)
+                    .or_insert_with(|| material.upload(renderer))
+                    .clone(),
+                root,
+            );
+            object.factors = PbrFactors {
+                base_color: material.base_color,
+                metallic: material.metallic,
+                roughness: material.roughness,
+                emissive: [0.; 3],
+            };
+            object.name = mesh.name.clone();
+            object.mesh_data = Some(mesh_data);
+            object
+        })
+        .collect();
+
+    let count = objects.len();
+    GltfScene {
+        objects,
+        object_nodes: (0..count).collect(),
+        nodes: NodeTree {
+            roots: (0..count).collect(),
+            children: vec![Vec::new(); count],
+            rest: vec![(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE); count],
+            skins: Vec::new(),
+            skins_of: vec![None; count],
+        },
+        animations: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test writes its files into a directory of its own, so they can run side by side
+    fn write_files(test: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("heaven_mesh_import_{}", test));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (name, contents) in files {
+            std::fs::write(directory.join(name), contents).unwrap();
+        }
+        directory
+    }
+
+    fn binary_stl(header: &[u8; 80], facets: &[[Vec3; 4]]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.extend((facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            for vector in facet {
+                bytes.extend(
+                    vector
+                        .to_array()
+                        .iter()
+                        .flat_map(|value| value.to_le_bytes()),
+                );
+            }
+            // Attribute byte count
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn obj_quad_is_triangulated_with_flipped_uvs() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n\
+            f 1/1/1 2/1/1 3/2/1 -1/2/1\n";
+        let directory = write_files("quad", &[("quad.obj", obj)]);
+        let imported = import_obj(&directory.join("quad.obj")).unwrap();
+
+        assert_eq!(imported.meshes.len(), 1);
+        let mesh = &imported.meshes[0];
+        assert_eq!(mesh.name, "quad");
+        assert_eq!(mesh.material, None);
+        assert_eq!(mesh.mesh_data.positions.len(), 4);
+        assert_eq!(mesh.mesh_data.indices.len(), 6);
+        assert_eq!(mesh.mesh_data.normals, vec![Vec3::Z; 4]);
+        assert_eq!(mesh.mesh_data.uvs[0], Vec2::new(0., 1.));
+        assert_eq!(mesh.mesh_data.uvs[2], Vec2::new(1., 0.));
+    }
+
+    #[test]
+    fn obj_without_normals_leaves_them_to_the_normals_mode() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let directory = write_files("no_normals", &[("triangle.obj", obj)]);
+        let imported = import_obj(&directory.join("triangle.obj")).unwrap();
+
+        assert!(imported.meshes[0].mesh_data.normals.is_empty());
+        assert!(imported.meshes[0].mesh_data.uvs.is_empty());
+    }
+
+    #[test]
+    fn obj_splits_on_objects_and_materials() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            o First\nusemtl Red\nf 1 2 3\nusemtl Blue\nf 1 3 2\no Second\nf 3 2 1\n";
+        let directory = write_files("split", &[("split.obj", obj)]);
+        let imported = import_obj(&directory.join("split.obj")).unwrap();
+
+        let names: Vec<_> = imported
+            .meshes
+            .iter()
+            .map(|mesh| mesh.name.as_str())
+            .collect();
+        assert_eq!(names, ["First", "First", "Second"]);
+        let materials: Vec<_> = imported.meshes.iter().map(|mesh| mesh.material).collect();
+        assert_eq!(materials, [Some(0), Some(1), Some(1)]);
+        assert_eq!(imported.materials[0].name, "Red");
+        assert_eq!(imported.materials[1].name, "Blue");
+    }
+
+    #[test]
+    fn obj_index_out_of_range_is_a_parse_error() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        let directory = write_files("out_of_range", &[("broken.obj", obj)]);
+        let result = import_obj(&directory.join("broken.obj"));
+
+        match result {
+            Err(GltfLoadError::Parse(_, what)) => assert!(what.starts_with("line 4:"), "{}", what),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn obj_without_faces_is_unsupported() {
+        let directory = write_files("no_faces", &[("points.obj", b"v 0 0 0\n")]);
+        let result = import_obj(&directory.join("points.obj"));
+
+        assert!(matches!(result, Err(GltfLoadError::Unsupported(..))));
+    }
+
+    #[test]
+    fn mtl_values_are_read() {
+        let mtl = b"newmtl Glass\nKd 0.1 0.2 0.3\nd 0.5\nNs 0\nPm 1\n\
+            newmtl Rough\nPr 0.3\nNs 1000\n";
+        let obj = b"mtllib glass.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Glass\nf 1 2 3\n";
+        let directory = write_files("mtl", &[("glass.mtl", mtl), ("glass.obj", obj)]);
+        let imported = import_obj(&directory.join("glass.obj")).unwrap();
+
+        let glass = &imported.materials[0];
+        assert_eq!(glass.base_color, [0.1, 0.2, 0.3, 0.5]);
+        assert_eq!(glass.roughness, 1.);
+        assert_eq!(glass.metallic, 1.);
+        // Pr wins over an Ns after it
+        assert_eq!(imported.materials[1].roughness, 0.3);
+        assert_eq!(imported.meshes[0].material, Some(0));
+    }
+
+    #[test]
+    fn several_mtl_libraries() {
+        let obj = b"mtllib red.mtl blue.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Blue\nf 1 2 3\n";
+        let directory = write_files(
+            "mtl_libraries",
+            &[
+                ("red.mtl", b"newmtl Red\nKd 1 0 0\n"),
+                ("blue.mtl", b"newmtl Blue\nKd 0 0 1\n"),
+                ("both.obj", obj),
+            ],
+        );
+        let path = directory.join("both.obj");
+        let imported = import_obj(&path).unwrap();
+
+        assert_eq!(imported.materials.len(), 2);
+        assert_eq!(imported.materials[1].base_color, [0., 0., 1., 1.]);
+        assert_eq!(imported.meshes[0].material, Some(1));
+        let dependencies = obj_dependencies(&path);
+        assert!(dependencies.contains(&directory.join("red.mtl")));
+        assert!(dependencies.contains(&directory.join("blue.mtl")));
+    }
+
+    #[test]
+    fn mtl_missing_texture_keeps_the_rest_of_the_material() {
+        let mtl = b"newmtl Painted\nmap_Kd -s 1 1 1 missing.png\nKd 1 0 0\n";
+        let directory = write_files("missing_texture", &[("painted.mtl", mtl)]);
+        let mut materials = Vec::new();
+        load_mtl(&directory.join("painted.mtl"), &mut materials).unwrap();
+
+        assert_eq!(materials.len(), 1);
+        assert!(materials[0].albedo.is_none());
+        assert_eq!(materials[0].base_color, [1., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn ascii_stl_is_read() {
+        let stl = b"solid Wedge\nfacet normal 0 0 2\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n\
+            vertex 0 1 0\nendloop\nendfacet\nendsolid Wedge\n";
+        let imported = import_stl_bytes(Path::new("wedge.stl"), stl).unwrap();
+
+        let mesh = &imported.meshes[0];
+        assert_eq!(mesh.name, "Wedge");
+        assert_eq!(mesh.mesh_data.indices, [0, 1, 2]);
+        assert_eq!(mesh.mesh_data.normals, vec![Vec3::Z; 3]);
+    }
+
+    #[test]
+    fn ascii_stl_facet_needs_three_vertices() {
+        let stl = b"solid\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0 0\nendfacet\n";
+        let result = import_stl_bytes(Path::new("broken.stl"), stl);
+
+        assert!(matches!(result, Err(GltfLoadError::Parse(..))));
+    }
+
+    #[test]
+    fn binary_stl_starting_with_solid_is_read_as_binary() {
+        let mut header = [0; 80];
+        header[..5].copy_from_slice(b"solid");
+        let facet = [Vec3::ZERO, Vec3::ZERO, Vec3::X, Vec3::Y];
+        let stl = binary_stl(&header, &[facet, facet]);
+        let imported = import_stl_bytes(Path::new("binary.stl"), &stl).unwrap();
+
+        let mesh = &imported.meshes[0];
+        assert_eq!(mesh.name, "binary");
+        assert_eq!(mesh.mesh_data.positions.len(), 6);
+        assert_eq!(mesh.mesh_data.positions[1], Vec3::X);
+        // Zero normals are recomputed later
+        assert!(mesh.mesh_data.normals.is_empty());
+    }
+
+    #[test]
+    fn stl_of_neither_kind_is_a_parse_error() {
+        let result = import_stl_bytes(Path::new("noise.stl"), b"not a mesh at all");
+
+        assert!(matches!(result, Err(GltfLoadError::Parse(..))));
+    }
+}
@@ -1,62 +1,892 @@
-pub fn load_gltf(
-    renderer: &rend3::Renderer,
-    path: &'static str,
-) -> (rend3::types::MeshHandle, rend3::types::MaterialHandle) {
-    let (doc, datas, _) = gltf::import(path).unwrap();
-    let mesh_data = doc.meshes().next().expect("no meshes in test.glb");
+use glam::{Mat4, Vec3A};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-    let primitive = mesh_data
-        .primitives()
-        .next()
-        .expect("no primitives in test.glb");
+use super::gltf_animation::{load_animations, Animation, NodeTree};
+use super::mesh_generator::{compute_smooth_normals, MeshData, Primitive};
+use super::mesh_import::{self, ImportedMesh, ImportedMeshes};
+use super::scene::{PbrFactors, SceneObject};
+
+// Other extensions a file uses are warned about on import
+const SUPPORTED_EXTENSIONS: [&str; 1] = ["KHR_materials_variants"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NormalsMode {
+    Authored,
+    Recompute,
+    RecomputeIfMissing,
+}
+
+impl NormalsMode {
+    pub const ALL: [NormalsMode; 3] = [
+        NormalsMode::RecomputeIfMissing,
+        NormalsMode::Authored,
+        NormalsMode::Recompute,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalsMode::Authored => "Use authored normals",
+            NormalsMode::Recompute => "Always recompute",
+            NormalsMode::RecomputeIfMissing => "Recompute only if missing",
+        }
+    }
+}
+
+// Every variant carries the file it came from, so messages make sense on their own
+#[derive(Debug)]
+pub enum GltfLoadError {
+    // The file or one of its buffers couldn't be read
+    Io(PathBuf, std::io::Error),
+    // Not valid glTF, or data that contradicts itself
+    Parse(PathBuf, String),
+    // Valid glTF that uses something this loader can't handle
+    Unsupported(PathBuf, String),
+    // An embedded or referenced image couldn't be decoded
+    TextureDecode(PathBuf, String),
+}
+
+impl fmt::Display for GltfLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfLoadError::Io(path, ex) => write!(f, "{}: {}", path.display(), ex),
+            GltfLoadError::Parse(path, ex) => write!(f, "{}: {}", path.display(), ex),
+            GltfLoadError::Unsupported(path, what) => {
+                write!(f, "{}: unsupported, {}", path.display(), what)
+            }
+            GltfLoadError::TextureDecode(path, ex) => {
+                write!(f, "{}: couldn't decode a texture, {}", path.display(), ex)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GltfLoadError {}
+
+fn import_error(path: &Path, ex: gltf::Error) -> GltfLoadError {
+    let path = path.to_path_buf();
+    match ex {
+        gltf::Error::Io(ex) => GltfLoadError::Io(path, ex),
+        gltf::Error::Image(ex) => GltfLoadError::TextureDecode(path, ex.to_string()),
+        gltf::Error::UnsupportedImageEncoding => {
+            GltfLoadError::TextureDecode(path, "unknown image encoding".to_string())
+        }
+        gltf::Error::UnsupportedScheme => {
+            GltfLoadError::Unsupported(path, "buffer or image URI scheme".to_string())
+        }
+        // Required extensions the crate doesn't know, Draco compression among them
+        gltf::Error::Validation(errors)
+            if errors
+                .iter()
+                .any(|(_, error)| *error == gltf::json::validation::Error::Unsupported) =>
+        {
+            let paths: Vec<_> = errors.iter().map(|(path, _)| path.as_str()).collect();
+            GltfLoadError::Unsupported(path, paths.join(", "))
+        }
+        ex => GltfLoadError::Parse(path, ex.to_string()),
+    }
+}
+
+pub struct GltfCamera {
+    pub name: String,
+    pub projection: rend3::types::CameraProjection,
+    // World transform of the camera node, relative to the model
+    pub transform: Mat4,
+}
+
+pub struct GltfModel {
+    pub mesh: rend3::types::MeshHandle,
+    pub mesh_data: MeshData,
+    pub material: rend3::types::MaterialHandle,
+    pub base_color: [f32; 4],
+    // KHR_materials_variants, named material alternatives for the same mesh
+    pub variants: Vec<(String, rend3::types::MaterialHandle)>,
+    pub cameras: Vec<GltfCamera>,
+    // Raw JSON of every `extras` in the file, labelled with what it was attached to
+    pub extras: Vec<(String, String)>,
+}
+
+pub struct GltfScene {
+    pub objects: Vec<SceneObject>,
+    // Node each object came from, lines up with `objects`
+    pub object_nodes: Vec<usize>,
+    pub nodes: NodeTree,
+    pub animations: Vec<Animation>,
+}
+
+fn visit_node(node: gltf::Node, parent: Mat4, transforms: &mut [Option<Mat4>]) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    transforms[node.index()] = Some(transform);
+    for child in node.children() {
+        visit_node(child, transform, transforms);
+    }
+}
+
+// World transform of every node in the default scene, None for nodes outside it
+fn node_transforms(doc: &gltf::Document) -> Vec<Option<Mat4>> {
+    let mut transforms = vec![None; doc.nodes().len()];
+    if let Some(scene) = doc.default_scene().or_else(|| doc.scenes().next()) {
+        for node in scene.nodes() {
+            visit_node(node, Mat4::IDENTITY, &mut transforms);
+        }
+    }
+    transforms
+}
+
+fn load_cameras(doc: &gltf::Document) -> Vec<GltfCamera> {
+    let transforms = node_transforms(doc);
+    doc.nodes()
+        .filter_map(|node| {
+            let camera = node.camera()?;
+            let projection = match camera.projection() {
+                gltf::camera::Projection::Perspective(perspective) => {
+                    rend3::types::CameraProjection::Perspective {
+                        vfov: perspective.yfov().to_degrees(),
+                        near: perspective.znear(),
+                    }
+                }
+                gltf::camera::Projection::Orthographic(orthographic) => {
+                    rend3::types::CameraProjection::Orthographic {
+                        size: Vec3A::new(
+                            orthographic.xmag() * 2.,
+                            orthographic.ymag() * 2.,
+                            orthographic.zfar() * 2.,
+                        ),
+                    }
+                }
+            };
+            let name = match camera.name().or_else(|| node.name()) {
+                Some(name) => name.to_string(),
+                None => format!("Camera {}", node.index()),
+            };
+            Some(GltfCamera {
+                name,
+                projection,
+                transform: transforms[node.index()].unwrap_or(Mat4::IDENTITY),
+            })
+        })
+        .collect()
+}
+
+fn label(kind: &str, name: Option<&str>, index: Option<usize>) -> String {
+    match (name, index) {
+        (Some(name), _) => format!("{} \"{}\"", kind, name),
+        (None, Some(index)) => format!("{} {}", kind, index),
+        (None, None) => format!("{} (default)", kind),
+    }
+}
+
+// The extras of what a scene object was made from, labelled like load_extras does
+fn object_extras(
+    node: &gltf::Node,
+    mesh: &gltf::Mesh,
+    material: &gltf::Material,
+) -> Vec<(String, String)> {
+    [
+        (
+            label("Node", node.name(), Some(node.index())),
+            node.extras(),
+        ),
+        (
+            label("Mesh", mesh.name(), Some(mesh.index())),
+            mesh.extras(),
+        ),
+        (
+            label("Material", material.name(), material.index()),
+            material.extras(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label, value.as_ref()?.get().to_string())))
+    .collect()
+}
+
+fn load_extras(doc: &gltf::Document) -> Vec<(String, String)> {
+    let mut extras = Vec::new();
+    let mut push = |label: String, value: &gltf::json::Extras| {
+        if let Some(value) = value {
+            extras.push((label, value.get().to_string()));
+        }
+    };
+    for node in doc.nodes() {
+        push(
+            label("Node", node.name(), Some(node.index())),
+            node.extras(),
+        );
+    }
+    for mesh in doc.meshes() {
+        push(
+            label("Mesh", mesh.name(), Some(mesh.index())),
+            mesh.extras(),
+        );
+    }
+    for material in doc.materials() {
+        push(
+            label("Material", material.name(), material.index()),
+            material.extras(),
+        );
+    }
+    extras
+}
+
+// Images of the file, uploaded the first time a material uses them. Colour textures are
+// sRGB and data textures (normals, metallic-roughness) linear, so an image used as both
+// is uploaded twice.
+struct ImageTextures<'a> {
+    renderer: &'a rend3::Renderer,
+    images: &'a [gltf::image::Data],
+    handles: HashMap<(usize, bool), rend3::types::TextureHandle>,
+}
+
+impl<'a> ImageTextures<'a> {
+    fn new(renderer: &'a rend3::Renderer, images: &'a [gltf::image::Data]) -> Self {
+        Self {
+            renderer,
+            images,
+            handles: HashMap::new(),
+        }
+    }
+
+    // None for images in a pixel format the renderer isn't given, the factor alone is used then
+    fn get(&mut self, texture: gltf::Texture, srgb: bool) -> Option<rend3::types::TextureHandle> {
+        let index = texture.source().index();
+        if let Some(handle) = self.handles.get(&(index, srgb)) {
+            return Some(handle.clone());
+        }
+
+        let image = self.images.get(index)?;
+        let channels = match image.format {
+            gltf::image::Format::R8 => 1,
+            gltf::image::Format::R8G8 => 2,
+            gltf::image::Format::R8G8B8 => 3,
+            gltf::image::Format::R8G8B8A8 => 4,
+            format => {
+                tracing::warn!(
+                    "image {} is {:?}, which isn't supported, the factor is used alone",
+                    index,
+                    format
+                );
+                return None;
+            }
+        };
+        let mut data = Vec::with_capacity(image.pixels.len() / channels * 4);
+        for pixel in image.pixels.chunks_exact(channels) {
+            data.extend_from_slice(&[
+                pixel[0],
+                pixel.get(1).copied().unwrap_or(0),
+                pixel.get(2).copied().unwrap_or(0),
+                pixel.get(3).copied().unwrap_or(255),
+            ]);
+        }
+
+        let handle = self.renderer.add_texture_2d(rend3::types::Texture {
+            label: texture.source().name().map(str::to_string),
+            data,
+            format: if srgb {
+                rend3::types::TextureFormat::Rgba8UnormSrgb
+            } else {
+                rend3::types::TextureFormat::Rgba8Unorm
+            },
+            size: glam::UVec2::new(image.width, image.height),
+            mip_count: rend3::types::MipmapCount::Maximum,
+            mip_source: rend3::types::MipmapSource::Generated,
+        });
+        self.handles.insert((index, srgb), handle.clone());
+        Some(handle)
+    }
+}
+
+fn pbr_factors(material: &gltf::Material) -> PbrFactors {
+    let metallic_roughness = material.pbr_metallic_roughness();
+    PbrFactors {
+        base_color: metallic_roughness.base_color_factor(),
+        metallic: metallic_roughness.metallic_factor(),
+        roughness: metallic_roughness.roughness_factor(),
+        emissive: material.emissive_factor(),
+    }
+}
+
+fn pbr_material(
+    material: &gltf::Material,
+    textures: &mut ImageTextures,
+) -> rend3_routine::pbr::PbrMaterial {
+    use rend3_routine::pbr::{
+        AlbedoComponent, AoMRTextures, MaterialComponent, NormalTexture, NormalTextureYDirection,
+        Transparency,
+    };
+
+    let metallic_roughness = material.pbr_metallic_roughness();
+    let base_color = glam::Vec4::from(metallic_roughness.base_color_factor());
+    let albedo = match metallic_roughness
+        .base_color_texture()
+        .and_then(|info| textures.get(info.texture(), true))
+    {
+        Some(texture) => AlbedoComponent::TextureValue {
+            texture,
+            value: base_color,
+        },
+        None => AlbedoComponent::Value(base_color),
+    };
+
+    let emissive_factor = glam::Vec3::from(material.emissive_factor());
+    let emissive = match material
+        .emissive_texture()
+        .and_then(|info| textures.get(info.texture(), true))
+    {
+        Some(texture) => MaterialComponent::TextureValue {
+            texture,
+            value: emissive_factor,
+        },
+        None => MaterialComponent::Value(emissive_factor),
+    };
+
+    // glTF normal maps point +Y up, like OpenGL
+    let normal = match material
+        .normal_texture()
+        .and_then(|info| textures.get(info.texture(), false))
+    {
+        Some(texture) => NormalTexture::Tricomponent(texture, NormalTextureYDirection::Up),
+        None => NormalTexture::None,
+    };
+
+    // Roughness is in green and metallic in blue, occlusion in red when it shares the image
+    let mr_texture = metallic_roughness
+        .metallic_roughness_texture()
+        .and_then(|info| textures.get(info.texture(), false));
+    let ao_texture = material
+        .occlusion_texture()
+        .and_then(|info| textures.get(info.texture(), false));
+    let aomr_textures = match (mr_texture, ao_texture) {
+        (None, None) => AoMRTextures::None,
+        (Some(mr), Some(ao)) if mr == ao => AoMRTextures::Combined { texture: Some(mr) },
+        (mr_texture, ao_texture) => AoMRTextures::Split {
+            mr_texture,
+            ao_texture,
+        },
+    };
+
+    let transparency = match material.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => Transparency::Opaque,
+        gltf::material::AlphaMode::Mask => Transparency::Cutout {
+            cutout: material.alpha_cutoff().unwrap_or(0.5),
+        },
+        gltf::material::AlphaMode::Blend => Transparency::Blend,
+    };
+
+    rend3_routine::pbr::PbrMaterial {
+        albedo,
+        emissive,
+        normal,
+        aomr_textures,
+        metallic_factor: Some(metallic_roughness.metallic_factor()),
+        roughness_factor: Some(metallic_roughness.roughness_factor()),
+        transparency,
+        ..Default::default()
+    }
+}
+
+fn read_mesh_data(
+    path: &Path,
+    primitive: &gltf::Primitive,
+    datas: &[gltf::buffer::Data],
+    normals: NormalsMode,
+) -> Result<MeshData, GltfLoadError> {
     let reader = primitive.reader(|b| Some(&datas.get(b.index())?.0[..b.length()]));
 
     let vertex_positions: Vec<_> = reader
         .read_positions()
-        .unwrap()
+        .ok_or_else(|| {
+            GltfLoadError::Unsupported(path.to_path_buf(), "mesh has no positions".to_string())
+        })?
         .map(glam::Vec3::from)
         .collect();
-    let vertex_normals: Vec<_> = reader
+    let authored_normals: Option<Vec<_>> = reader
         .read_normals()
-        .unwrap()
-        .map(glam::Vec3::from)
-        .collect();
-    let vertex_tangents: Vec<_> = reader
-        .read_tangents()
-        .unwrap()
-        .map(glam::Vec4::from)
-        .map(glam::Vec4::truncate)
-        .collect();
-    let vertex_uvs: Vec<_> = reader
-        .read_tex_coords(0)
-        .unwrap()
-        .into_f32()
-        .map(glam::Vec2::from)
-        .collect();
-    let indices = reader.read_indices().unwrap().into_u32().collect();
-
-    let mesh =
-        rend3::types::MeshBuilder::new(vertex_positions.to_vec(), rend3::types::Handedness::Right)
-            .with_vertex_normals(vertex_normals)
-            .with_vertex_tangents(vertex_tangents)
-            .with_vertex_uv0(vertex_uvs)
-            .with_indices(indices)
-            .with_flip_winding_order()
-            .build()
-            .unwrap();
+        .map(|normals| normals.map(glam::Vec3::from).collect());
+    // Missing tangents and UVs are left empty, rend3 generates tangents itself
+    let vertex_tangents: Vec<_> = reader.read_tangents().map_or_else(Vec::new, |tangents| {
+        tangents
+            .map(glam::Vec4::from)
+            .map(glam::Vec4::truncate)
+            .collect()
+    });
+    let vertex_uvs: Vec<_> = reader.read_tex_coords(0).map_or_else(Vec::new, |uvs| {
+        uvs.into_f32().map(glam::Vec2::from).collect()
+    });
+    let vertex_uvs1: Vec<_> = reader.read_tex_coords(1).map_or_else(Vec::new, |uvs| {
+        uvs.into_f32().map(glam::Vec2::from).collect()
+    });
+    let joints: Vec<[u16; 4]> = reader
+        .read_joints(0)
+        .map_or_else(Vec::new, |joints| joints.into_u16().collect());
+    let weights: Vec<_> = reader.read_weights(0).map_or_else(Vec::new, |weights| {
+        weights.into_f32().map(glam::Vec4::from).collect()
+    });
+    // Non-indexed primitives draw their vertices in order
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertex_positions.len() as u32).collect(),
+    };
+    if indices
+        .iter()
+        .any(|&index| index as usize >= vertex_positions.len())
+    {
+        return Err(GltfLoadError::Parse(
+            path.to_path_buf(),
+            "mesh has indices past the end of its vertices".to_string(),
+        ));
+    }
+
+    if normals == NormalsMode::Authored && authored_normals.is_none() {
+        tracing::warn!(
+            "{}: mesh has no normals, smooth ones are computed",
+            path.display()
+        );
+    }
+    let vertex_normals = match (normals, authored_normals) {
+        // Nothing authored to use, so that falls back to recomputing too
+        (NormalsMode::Recompute, _) | (_, None) => {
+            compute_smooth_normals(&vertex_positions, &indices)
+        }
+        (_, Some(normals)) => normals,
+    };
+
+    // Start on whichever set the base colour texture is authored against
+    let uv_set = primitive
+        .material()
+        .pbr_metallic_roughness()
+        .base_color_texture()
+        .filter(|info| info.tex_coord() == 1 && !vertex_uvs1.is_empty())
+        .map_or(0, |_| 1);
+
+    Ok(MeshData {
+        positions: vertex_positions,
+        normals: vertex_normals,
+        tangents: vertex_tangents,
+        uvs: vertex_uvs,
+        uvs1: vertex_uvs1,
+        indices,
+        joints,
+        weights,
+        uv_set,
+    })
+}
+
+// Everything read from disk and decoded, but nothing uploaded yet. Only needs the CPU,
+// so it can be made on another thread.
+pub enum Imported {
+    Gltf(
+        gltf::Document,
+        Vec<gltf::buffer::Data>,
+        Vec<gltf::image::Data>,
+    ),
+    // OBJ and STL, already turned into plain meshes
+    Meshes(ImportedMeshes),
+}
+
+// Picked by extension, the same for every way a file comes in
+pub fn import(path: &Path) -> Result<Imported, GltfLoadError> {
+    puffin::profile_function!();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => {
+            let (doc, datas, images) = gltf::import(path).map_err(|ex| import_error(path, ex))?;
+            warn_unsupported(path, &doc);
+            Ok(Imported::Gltf(doc, datas, images))
+        }
+        Some("obj") => mesh_import::import_obj(path).map(Imported::Meshes),
+        Some("stl") => mesh_import::import_stl(path).map(Imported::Meshes),
+        _ => Err(GltfLoadError::Unsupported(
+            path.to_path_buf(),
+            "not a .gltf, .glb, .obj or .stl file".to_string(),
+        )),
+    }
+}
+
+// For files fetched in the browser. glTF buffers and images have to be embedded, there is
+// nothing to resolve their URIs against, and OBJ files would need their material libraries.
+#[cfg(target_arch = "wasm32")]
+pub fn import_bytes(path: &Path, bytes: &[u8]) -> Result<Imported, GltfLoadError> {
+    puffin::profile_function!();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => {
+            let (doc, datas, images) =
+                gltf::import_slice(bytes).map_err(|ex| import_error(path, ex))?;
+            warn_unsupported(path, &doc);
+            Ok(Imported::Gltf(doc, datas, images))
+        }
+        Some("stl") => mesh_import::import_stl_bytes(path, bytes).map(Imported::Meshes),
+        _ => Err(GltfLoadError::Unsupported(
+            path.to_path_buf(),
+            "the browser build only opens .gltf, .glb and .stl files".to_string(),
+        )),
+    }
+}
+
+fn warn_unsupported(path: &Path, doc: &gltf::Document) {
+    for extension in doc
+        .extensions_used()
+        .filter(|extension| !SUPPORTED_EXTENSIONS.contains(extension))
+    {
+        tracing::warn!(
+            "{}: {} isn't supported and is ignored",
+            path.display(),
+            extension
+        );
+    }
+}
+
+// One primitive, read into the same plain mesh OBJ and STL files are. Its normals are
+// already what the normals mode asks for.
+fn read_primitive(
+    path: &Path,
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    datas: &[gltf::buffer::Data],
+    normals: NormalsMode,
+) -> Result<ImportedMesh, GltfLoadError> {
+    let mesh_name = match mesh.name() {
+        Some(name) => name.to_string(),
+        None => format!("Mesh {}", mesh.index()),
+    };
+    Ok(ImportedMesh {
+        name: if mesh.primitives().len() > 1 {
+            format!("{} #{}", mesh_name, primitive.index())
+        } else {
+            mesh_name
+        },
+        mesh_data: read_mesh_data(path, primitive, datas, normals)?,
+        material: primitive.material().index(),
+    })
+}
+
+fn first_primitive<'a>(
+    path: &Path,
+    doc: &'a gltf::Document,
+) -> Result<(gltf::Mesh<'a>, gltf::Primitive<'a>), GltfLoadError> {
+    doc.meshes()
+        .next()
+        .and_then(|mesh| Some((mesh.clone(), mesh.primitives().next()?)))
+        .ok_or_else(|| {
+            GltfLoadError::Unsupported(path.to_path_buf(), "file has no meshes".to_string())
+        })
+}
+
+// Only the geometry of a read file, for when the import options change. Nothing is uploaded.
+pub fn imported_mesh_data(
+    path: &Path,
+    imported: Imported,
+    normals: NormalsMode,
+) -> Result<MeshData, GltfLoadError> {
+    match imported {
+        Imported::Gltf(doc, datas, _) => {
+            let (mesh, primitive) = first_primitive(path, &doc)?;
+            Ok(read_primitive(path, &mesh, &primitive, &datas, normals)?.mesh_data)
+        }
+        Imported::Meshes(meshes) => Ok(mesh_import::first_mesh_data(&meshes, normals)),
+    }
+}
+
+// Stands in for a bundled model that failed to load, so the app still starts
+pub fn placeholder_model(renderer: &rend3::Renderer, shape: Primitive) -> GltfModel {
+    let mesh_data = shape.mesh_data();
+    let base_color = [1., 0., 1., 1.];
+    GltfModel {
+        mesh: renderer.add_mesh(mesh_data.build()),
+        mesh_data,
+        material: renderer.add_material(rend3_routine::pbr::PbrMaterial {
+            albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::from(base_color)),
+            ..rend3_routine::pbr::PbrMaterial::default()
+        }),
+        base_color,
+        variants: Vec::new(),
+        cameras: Vec::new(),
+        extras: Vec::new(),
+    }
+}
+
+pub fn load_gltf(
+    renderer: &rend3::Renderer,
+    path: &Path,
+    normals: NormalsMode,
+) -> Result<GltfModel, GltfLoadError> {
+    finish_gltf(renderer, path, import(path)?, normals)
+}
+
+// Nothing is added to the renderer unless the whole file reads fine
+pub fn finish_gltf(
+    renderer: &rend3::Renderer,
+    path: &Path,
+    imported: Imported,
+    normals: NormalsMode,
+) -> Result<GltfModel, GltfLoadError> {
+    puffin::profile_function!();
+    let (doc, datas, images) = match imported {
+        Imported::Gltf(doc, datas, images) => (doc, datas, images),
+        Imported::Meshes(meshes) => {
+            return Ok(mesh_import::finish_model(renderer, meshes, normals))
+        }
+    };
+    let (mesh, primitive) = first_primitive(path, &doc)?;
+    let mesh_data = read_primitive(path, &mesh, &primitive, &datas, normals)?.mesh_data;
 
     // Add mesh to renderer's world
-    let mesh_handle = renderer.add_mesh(mesh);
+    let mesh_handle = renderer.add_mesh(mesh_data.build());
 
-    // Add basic material with all defaults except a single color.
-    let material = primitive.material();
-    let metallic_roughness = material.pbr_metallic_roughness();
-    let material_handle = renderer.add_material(rend3_routine::pbr::PbrMaterial {
-        albedo: rend3_routine::pbr::AlbedoComponent::Value(
-            metallic_roughness.base_color_factor().into(),
-        ),
-        ..Default::default()
-    });
+    let mut textures = ImageTextures::new(renderer, &images);
+    let material_handle = renderer.add_material(pbr_material(&primitive.material(), &mut textures));
+
+    // Variants only swap materials, so every variant shares the mesh uploaded above
+    let mut variant_materials: Vec<(Option<usize>, rend3::types::MaterialHandle)> = Vec::new();
+    let mut variants = Vec::new();
+    if let Some(doc_variants) = doc.variants() {
+        for (variant_index, variant) in doc_variants.enumerate() {
+            let material = primitive
+                .mappings()
+                .find(|mapping| mapping.variants().contains(&(variant_index as u32)))
+                .map_or_else(|| primitive.material(), |mapping| mapping.material());
+
+            let handle = match variant_materials
+                .iter()
+                .find(|(index, _)| *index == material.index())
+            {
+                Some((_, handle)) => handle.clone(),
+                None => {
+                    let handle = renderer.add_material(pbr_material(&material, &mut textures));
+                    variant_materials.push((material.index(), handle.clone()));
+                    handle
+                }
+            };
+
+            variants.push((variant.name().to_string(), handle));
+        }
+    }
+
+    Ok(GltfModel {
+        mesh: mesh_handle,
+        mesh_data,
+        material: material_handle,
+        base_color: primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_factor(),
+        variants,
+        cameras: load_cameras(&doc),
+        extras: load_extras(&doc),
+    })
+}
+
+// Every primitive of every mesh node in the scene becomes its own object, placed by its
+// node's world transform under `root`. Meshes, materials and textures used more than once
+// are only uploaded once. Animations come along with the node hierarchy they move.
+pub fn finish_gltf_scene(
+    renderer: &rend3::Renderer,
+    path: &Path,
+    imported: Imported,
+    normals: NormalsMode,
+    root: Mat4,
+) -> Result<GltfScene, GltfLoadError> {
+    puffin::profile_function!();
+    let (doc, datas, images) = match imported {
+        Imported::Gltf(doc, datas, images) => (doc, datas, images),
+        Imported::Meshes(meshes) => {
+            return Ok(mesh_import::finish_scene(renderer, meshes, normals, root))
+        }
+    };
+    let transforms = node_transforms(&doc);
+
+    // Read all geometry first, so a broken primitive doesn't leave half a scene behind
+    let mut imported_meshes = HashMap::new();
+    for mesh in doc.meshes() {
+        for primitive in mesh.primitives() {
+            let imported = read_primitive(path, &mesh, &primitive, &datas, normals)?;
+            imported_meshes.insert((mesh.index(), primitive.index()), imported);
+        }
+    }
+
+    let nodes = NodeTree::load(&doc, &datas);
+    // Skeletons start out in the rest pose, until an animation moves them
+    let rest_pose = nodes.rest_pose();
+
+    let mut meshes = HashMap::new();
+    let mut materials = HashMap::new();
+    let mut textures = ImageTextures::new(renderer, &images);
+    let mut objects = Vec::new();
+    let mut object_nodes = Vec::new();
+    for node in doc.nodes() {
+        let (mesh, transform) = match (node.mesh(), transforms[node.index()]) {
+            (Some(mesh), Some(transform)) => (mesh, transform),
+            _ => continue,
+        };
+        for primitive in mesh.primitives() {
+            let imported = &imported_meshes[&(mesh.index(), primitive.index())];
+            let mesh_data = &imported.mesh_data;
+            let mesh_handle = meshes
+                .entry((mesh.index(), primitive.index()))
+                .or_insert_with(|| renderer.add_mesh(mesh_data.build()))
+                .clone();
+            let material = primitive.material();
+            let material_handle = materials
+                .entry(imported.material)
+                .or_insert_with(|| renderer.add_material(pbr_material(&material, &mut textures)))
+                .clone();
+
+            let skin = nodes.skins_of[node.index()].filter(|_| !mesh_data.joints.is_empty());
+            let mut object = match skin {
+                Some(skin) => SceneObject::new_skinned(
+                    renderer,
+                    mesh_handle,
+                    nodes.joint_matrices(skin, node.index(), &rest_pose),
+                    material_handle,
+                    root * transform,
+                ),
+                None => SceneObject::new(renderer, mesh_handle, material_handle, root * transform),
+            };
+            object.mesh_data = Some(mesh_data.clone());
+            object.factors = pbr_factors(&material);
+            object.extras = object_extras(&node, &mesh, &material);
+            let node_name = match node.name() {
+                Some(name) => name.to_string(),
+                None => format!("Node {}", node.index()),
+            };
+            object.name = if mesh.primitives().len() > 1 {
+                format!("{} #{}", node_name, primitive.index())
+            } else {
+                node_name
+            };
+            objects.push(object);
+            object_nodes.push(node.index());
+        }
+    }
+
+    Ok(GltfScene {
+        objects,
+        object_nodes,
+        nodes,
+        animations: load_animations(&doc, &datas),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test writes its file into a directory of its own, so they can run side by side
+    fn write_file(test: &str, name: &str, contents: &[u8]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("heaven_mesh_importer_{}", test));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    // Header, a JSON chunk and a binary chunk when there's anything to put in it
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let mut bin = bin.to_vec();
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+        let mut chunks = vec![(json, *b"JSON")];
+        if !bin.is_empty() {
+            chunks.push((bin, *b"BIN\0"));
+        }
+        let length = 12 + chunks.iter().map(|(data, _)| 8 + data.len()).sum::<usize>();
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend((length as u32).to_le_bytes());
+        for (data, kind) in chunks {
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(kind);
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    const EMPTY_ASSET: &str = r#"{"asset":{"version":"2.0"}}"#;
+
+    #[test]
+    fn valid_glb() {
+        let path = write_file("valid", "empty.glb", &glb(EMPTY_ASSET, &[]));
+        assert!(matches!(import(&path), Ok(Imported::Gltf(..))));
+    }
+
+    #[test]
+    fn truncated_glb() {
+        let bytes = glb(EMPTY_ASSET, &[]);
+        for cut in [8, 16, bytes.len() - 4] {
+            let path = write_file("truncated", "truncated.glb", &bytes[..cut]);
+            let result = import(&path);
+            assert!(
+                matches!(result, Err(GltfLoadError::Parse(..))),
+                "cut at {}",
+                cut
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_glb_json() {
+        let path = write_file("corrupt", "corrupt.glb", &glb(r#"{"asset":{"vers"#, &[]));
+        assert!(matches!(import(&path), Err(GltfLoadError::Parse(..))));
+    }
+
+    #[test]
+    fn corrupt_embedded_image() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 8}],
+            "bufferViews": [{"buffer": 0, "byteLength": 8}],
+            "images": [{"bufferView": 0, "mimeType": "image/png"}]
+        }"#;
+        let path = write_file("image", "image.glb", &glb(json, b"not png!"));
+        assert!(matches!(
+            import(&path),
+            Err(GltfLoadError::TextureDecode(..))
+        ));
+    }
+
+    #[test]
+    fn required_extension() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "extensionsUsed": ["KHR_draco_mesh_compression"],
+            "extensionsRequired": ["KHR_draco_mesh_compression"]
+        }"#;
+        let path = write_file("extension", "draco.glb", &glb(json, &[]));
+        assert!(matches!(import(&path), Err(GltfLoadError::Unsupported(..))));
+    }
+
+    #[test]
+    fn missing_buffer() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 4, "uri": "missing.bin"}]
+        }"#;
+        let path = write_file("buffer", "missing.gltf", json.as_bytes());
+        assert!(matches!(import(&path), Err(GltfLoadError::Io(..))));
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = std::env::temp_dir().join("heaven_mesh_importer_missing/none.glb");
+        assert!(matches!(import(&path), Err(GltfLoadError::Io(..))));
+    }
 
-    (mesh_handle, material_handle)
+    #[test]
+    fn unknown_extension() {
+        let path = write_file("unknown", "model.fbx", b"");
+        assert!(matches!(import(&path), Err(GltfLoadError::Unsupported(..))));
+    }
 }
@@ -0,0 +1,302 @@
+use glam::{Mat4, Vec3, Vec4};
+use rand::Rng;
+use rend3::types::{MaterialHandle, ObjectHandle};
+use rend3_routine::pbr::{AlbedoComponent, PbrMaterial, Transparency};
+
+use super::mesh_generator::MeshData;
+
+// Per emitter, it stops spawning there until some have died
+const MAX_PARTICLES: usize = 5000;
+// Longest step simulated at once, so a hitch doesn't spawn a burst all in one place
+const MAX_STEP: f32 = 0.1;
+
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    // Seconds since it spawned
+    age: f32,
+}
+
+pub struct Emitter {
+    pub name: String,
+    pub enabled: bool,
+    pub position: Vec3,
+    // Particles per second
+    pub rate: f32,
+    // Seconds every particle lives for
+    pub lifetime: f32,
+    pub velocity: Vec3,
+    // Speed of up to this much in a random direction, added on top of the velocity
+    pub spread: f32,
+    pub gravity: Vec3,
+    // Colour and size at birth and at death, blended over each particle's life
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub start_size: f32,
+    pub end_size: f32,
+    particles: Vec<Particle>,
+    // Fraction of a particle left over from the last step, so low rates still spawn
+    owed: f32,
+}
+
+impl Emitter {
+    // Rising embers that fade out, something to start tuning from
+    pub fn new(name: String, position: Vec3) -> Self {
+        Self {
+            name,
+            enabled: true,
+            position,
+            rate: 50.,
+            lifetime: 2.,
+            velocity: Vec3::Y * 2.,
+            spread: 0.5,
+            gravity: Vec3::Y * -0.5,
+            start_color: [1., 0.7, 0.2, 1.],
+            end_color: [0.8, 0.1, 0., 0.],
+            start_size: 0.3,
+            end_size: 0.05,
+            particles: Vec::new(),
+            owed: 0.,
+        }
+    }
+
+    fn step(&mut self, rng: &mut impl Rng, elapsed: f32) {
+        for particle in &mut self.particles {
+            particle.age += elapsed;
+            particle.velocity += self.gravity * elapsed;
+            particle.position += particle.velocity * elapsed;
+        }
+        self.particles
+            .retain(|particle| particle.age < self.lifetime);
+
+        if !self.enabled {
+            self.owed = 0.;
+            return;
+        }
+        self.owed += self.rate * elapsed;
+        while self.owed >= 1. {
+            self.owed -= 1.;
+            if self.particles.len() >= MAX_PARTICLES {
+                continue;
+            }
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            )
+            .normalize_or_zero();
+            self.particles.push(Particle {
+                position: self.position,
+                velocity: self.velocity + direction * self.spread * rng.gen::<f32>(),
+                age: 0.,
+            });
+        }
+    }
+
+    fn color(&self, life: f32) -> [u8; 4] {
+        let color = Vec4::from(self.start_color).lerp(Vec4::from(self.end_color), life);
+        (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.)
+            .to_array()
+            .map(|channel| channel as u8)
+    }
+
+    fn size(&self, life: f32) -> f32 {
+        self.start_size + (self.end_size - self.start_size) * life
+    }
+}
+
+// Emitters simulated on the CPU and drawn together as one mesh of camera facing quads
+pub struct ParticleSystem {
+    pub open: bool,
+    pub emitters: Vec<Emitter>,
+    // Index into emitters shown in the inspector
+    selected: Option<usize>,
+    material: MaterialHandle,
+    // Remade every frame, dropped while there is nothing to draw
+    object: Option<ObjectHandle>,
+    last_update: Option<f32>,
+}
+
+impl ParticleSystem {
+    pub fn new(renderer: &rend3::Renderer) -> Self {
+        Self {
+            open: false,
+            emitters: Vec::new(),
+            selected: None,
+            material: renderer.add_material(PbrMaterial {
+                albedo: AlbedoComponent::Vertex { srgb: true },
+                unlit: true,
+                transparency: Transparency::Blend,
+                ..PbrMaterial::default()
+            }),
+            object: None,
+            last_update: None,
+        }
+    }
+
+    // Whether the next frame has anything to move
+    pub fn active(&self) -> bool {
+        self.emitters
+            .iter()
+            .any(|emitter| emitter.enabled || !emitter.particles.is_empty())
+    }
+
+    // Advances the simulation to `now` (seconds since start) and turns every particle
+    // towards the camera `view` looks from
+    pub fn update(&mut self, renderer: &rend3::Renderer, now: f32, view: Mat4) {
+        let elapsed = (now - self.last_update.unwrap_or(now)).min(MAX_STEP);
+        self.last_update = Some(now);
+        let mut rng = rand::thread_rng();
+        for emitter in &mut self.emitters {
+            emitter.step(&mut rng, elapsed);
+        }
+
+        let camera = view.inverse();
+        let (right, up) = (camera.x_axis.truncate(), camera.y_axis.truncate());
+        let camera_position = camera.w_axis.truncate();
+        // Blended, so they're drawn from the back to the front
+        let mut quads: Vec<(f32, Vec3, f32, [u8; 4])> = self
+            .emitters
+            .iter()
+            .flat_map(|emitter| {
+                emitter.particles.iter().map(move |particle| {
+                    let life = particle.age / emitter.lifetime;
+                    (
+                        camera_position.distance_squared(particle.position),
+                        particle.position,
+                        emitter.size(life),
+                        emitter.color(life),
+                    )
+                })
+            })
+            .collect();
+        if quads.is_empty() {
+            self.object = None;
+            return;
+        }
+        quads.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // A middle vertex in the particle's colour and transparent corners, which softens
+        // the square into a round looking blob
+        let mut mesh = MeshData::default();
+        let mut colors = Vec::new();
+        for (_, position, size, color) in quads {
+            let (right, up) = (right * size / 2., up * size / 2.);
+            let first = mesh.positions.len() as u32;
+            mesh.positions.extend([
+                position,
+                position - right - up,
+                position + right - up,
+                position + right + up,
+                position - right + up,
+            ]);
+            let clear = [color[0], color[1], color[2], 0];
+            colors.extend([color, clear, clear, clear, clear]);
+            for corner in 0..4 {
+                let (a, b) = (first + 1 + corner, first + 1 + (corner + 1) % 4);
+                // Wound both ways, the camera can be on either side of the plane it's in
+                mesh.indices.extend([first, a, b, first, b, a]);
+            }
+        }
+        mesh.normals = vec![-camera.z_axis.truncate(); mesh.positions.len()];
+        let mesh = renderer.add_mesh(mesh.build_with_colors(colors));
+        self.object = Some(renderer.add_object(rend3::types::Object {
+            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh),
+            material: self.material.clone(),
+            transform: Mat4::IDENTITY,
+        }));
+    }
+}
+
+fn vec3_row(ui: &mut egui::Ui, label: &str, value: &mut Vec3, speed: f32) {
+    ui.label(label);
+    let mut values = value.to_array();
+    for value in &mut values {
+        ui.add(egui::DragValue::new(value).speed(speed));
+    }
+    *value = Vec3::from(values);
+    ui.end_row();
+}
+
+fn emitter_ui(ui: &mut egui::Ui, emitter: &mut Emitter) {
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut emitter.name);
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut emitter.enabled, "Emitting");
+        ui.label(format!("{} alive", emitter.particles.len()));
+    });
+    egui::Grid::new("emitter").show(ui, |ui| {
+        vec3_row(ui, "Position", &mut emitter.position, 0.1);
+        vec3_row(ui, "Velocity", &mut emitter.velocity, 0.1);
+        vec3_row(ui, "Gravity", &mut emitter.gravity, 0.1);
+    });
+    ui.add(
+        egui::Slider::new(&mut emitter.rate, 0.0..=1000.0)
+            .logarithmic(true)
+            .text("Per second"),
+    );
+    ui.add(egui::Slider::new(&mut emitter.lifetime, 0.1..=20.0).text("Lifetime"));
+    ui.add(egui::Slider::new(&mut emitter.spread, 0.0..=10.0).text("Spread"));
+
+    egui::Grid::new("over_life").show(ui, |ui| {
+        ui.label("");
+        ui.label("Start");
+        ui.label("End");
+        ui.end_row();
+        ui.label("Colour");
+        ui.color_edit_button_rgba_unmultiplied(&mut emitter.start_color);
+        ui.color_edit_button_rgba_unmultiplied(&mut emitter.end_color);
+        ui.end_row();
+        ui.label("Size");
+        ui.add(
+            egui::DragValue::new(&mut emitter.start_size)
+                .speed(0.01)
+                .clamp_range(0.0..=100.0),
+        );
+        ui.add(
+            egui::DragValue::new(&mut emitter.end_size)
+                .speed(0.01)
+                .clamp_range(0.0..=100.0),
+        );
+        ui.end_row();
+    });
+}
+
+// New emitters start at `spawn_at`, the caller puts them in front of the camera
+pub fn particles_window(ctx: &egui::CtxRef, particles: &mut ParticleSystem, spawn_at: Vec3) {
+    let mut open = particles.open;
+    egui::Window::new("Particles")
+        .open(&mut open)
+        .default_width(320.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Add emitter").clicked() {
+                    let name = format!("Emitter {}", particles.emitters.len() + 1);
+                    particles.emitters.push(Emitter::new(name, spawn_at));
+                    particles.selected = Some(particles.emitters.len() - 1);
+                }
+                if let Some(index) = particles.selected {
+                    if ui.button("Remove").clicked() {
+                        particles.emitters.remove(index);
+                        particles.selected = None;
+                    }
+                }
+            });
+            for (index, emitter) in particles.emitters.iter().enumerate() {
+                let selected = particles.selected == Some(index);
+                if ui.selectable_label(selected, &emitter.name).clicked() {
+                    particles.selected = (!selected).then(|| index);
+                }
+            }
+            if let Some(emitter) = particles
+                .selected
+                .and_then(|index| particles.emitters.get_mut(index))
+            {
+                ui.separator();
+                emitter_ui(ui, emitter);
+            }
+        });
+    particles.open &= open;
+}
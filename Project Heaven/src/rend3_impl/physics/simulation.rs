@@ -1 +1,190 @@
+use glam::{Mat4, Quat, Vec3};
+use rapier3d::prelude::*;
+use std::collections::HashSet;
 
+use super::super::scene::SceneObject;
+
+// Seconds per step, the simulation runs at this rate whatever the frame rate is
+pub const TIMESTEP: f32 = 1. / 60.;
+// A long hitch is dropped instead of being caught up on all at once
+const MAX_CATCH_UP: f32 = 0.25;
+
+const GRAVITY: f32 = -9.81;
+
+pub struct Simulation {
+    pub gravity: bool,
+    accumulator: f32,
+
+    pipeline: PhysicsPipeline,
+    parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+}
+
+impl Simulation {
+    pub fn new(gravity: bool) -> Self {
+        Self {
+            gravity,
+            accumulator: 0.,
+            pipeline: PhysicsPipeline::new(),
+            parameters: IntegrationParameters {
+                dt: TIMESTEP,
+                ..IntegrationParameters::default()
+            },
+            islands: IslandManager::new(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            impulse_joints: ImpulseJointSet::new(),
+            multibody_joints: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+        }
+    }
+
+    // Infinite floor facing up at `height`
+    pub fn add_ground(&mut self, height: f32) {
+        let collider = ColliderBuilder::halfspace(Vector::y_axis())
+            .translation(vector![0., height, 0.])
+            .build();
+        self.colliders.insert(collider);
+    }
+
+    // Fixed collider matching the object's triangles where it stands now, scale included.
+    // Objects without mesh data are left out.
+    pub fn add_static(&mut self, object: &SceneObject) {
+        let mesh = match &object.mesh_data {
+            Some(mesh) if mesh.indices.len() >= 3 => mesh,
+            _ => return,
+        };
+        let vertices = mesh
+            .positions
+            .iter()
+            .map(|&position| {
+                let world = object.transform.transform_point3(position);
+                point![world.x, world.y, world.z]
+            })
+            .collect();
+        let indices = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        self.colliders
+            .insert(ColliderBuilder::trimesh(vertices, indices).build());
+    }
+
+    fn add_body(&mut self, transform: Mat4, collider: Collider) -> RigidBodyHandle {
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        let body = RigidBodyBuilder::new_dynamic()
+            .position(Isometry::from_parts(
+                vector![translation.x, translation.y, translation.z].into(),
+                UnitQuaternion::from_quaternion(Quaternion::new(
+                    rotation.w, rotation.x, rotation.y, rotation.z,
+                )),
+            ))
+            .build();
+        let handle = self.bodies.insert(body);
+        self.colliders
+            .insert_with_parent(collider, handle, &mut self.bodies);
+        handle
+    }
+
+    // Box shaped body, its half extents taken from the transform's scale like the unit cube
+    // primitive's are
+    pub fn add_cube(&mut self, transform: Mat4) -> RigidBodyHandle {
+        let (scale, _, _) = transform.to_scale_rotation_translation();
+        let collider = ColliderBuilder::cuboid(scale.x.abs(), scale.y.abs(), scale.z.abs())
+            .restitution(0.3)
+            .build();
+        self.add_body(transform, collider)
+    }
+
+    // Body where the object stands, with the convex hull of its mesh for a collider. Bodies
+    // don't scale, so the scale goes into the hull. None for objects without mesh data or
+    // with one too flat to have a hull.
+    pub fn add_dynamic(&mut self, object: &SceneObject) -> Option<RigidBodyHandle> {
+        let mesh = object.mesh_data.as_ref()?;
+        let (scale, _, _) = object.transform.to_scale_rotation_translation();
+        let points: Vec<Point<Real>> = mesh
+            .positions
+            .iter()
+            .map(|&position| {
+                let scaled = position * scale;
+                point![scaled.x, scaled.y, scaled.z]
+            })
+            .collect();
+        let collider = ColliderBuilder::convex_hull(&points)?
+            .restitution(0.3)
+            .build();
+        Some(self.add_body(object.transform, collider))
+    }
+
+    // Bodies of objects deleted since would go on colliding with everything else
+    pub fn retain_bodies(&mut self, live: &HashSet<RigidBodyHandle>) {
+        let gone: Vec<RigidBodyHandle> = self
+            .bodies
+            .iter()
+            .map(|(handle, _)| handle)
+            .filter(|handle| !live.contains(handle))
+            .collect();
+        for handle in gone {
+            self.bodies.remove(
+                handle,
+                &mut self.islands,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        }
+    }
+
+    // Runs as many fixed steps as `delta_time` covers, returns whether anything moved
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        self.accumulator = (self.accumulator + delta_time).min(MAX_CATCH_UP);
+        let gravity = if self.gravity {
+            vector![0., GRAVITY, 0.]
+        } else {
+            Vector::zeros()
+        };
+        let mut stepped = false;
+        while self.accumulator >= TIMESTEP {
+            self.pipeline.step(
+                &gravity,
+                &self.parameters,
+                &mut self.islands,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                &mut self.ccd_solver,
+                &(),
+                &(),
+            );
+            self.accumulator -= TIMESTEP;
+            stepped = true;
+        }
+        stepped
+    }
+
+    // Where the body is now, keeping the scale the object had. Bodies don't scale.
+    pub fn transform(&self, handle: RigidBodyHandle, scale: Vec3) -> Option<Mat4> {
+        let position = self.bodies.get(handle)?.position();
+        let translation = position.translation.vector;
+        let rotation = position.rotation;
+        Some(Mat4::from_scale_rotation_translation(
+            scale,
+            Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w),
+            Vec3::new(translation.x, translation.y, translation.z),
+        ))
+    }
+}
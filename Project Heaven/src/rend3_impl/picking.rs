@@ -0,0 +1,53 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use super::mesh_generator;
+use super::scene::SceneObject;
+
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+// Ray from the near plane through `cursor`, given in pixels from the top left of a view
+// `resolution` pixels big. rend3 uses reverse Z, so the near plane is at depth 1.
+pub fn cursor_ray(view: Mat4, projection: Mat4, cursor: Vec2, resolution: Vec2) -> Option<Ray> {
+    let ndc = Vec2::new(
+        cursor.x / resolution.x * 2. - 1.,
+        1. - cursor.y / resolution.y * 2.,
+    );
+    let inverse = (projection * view).inverse();
+    let near = inverse.project_point3(ndc.extend(1.));
+    let far = inverse.project_point3(ndc.extend(0.5));
+    let direction = (far - near).normalize();
+    direction.is_finite().then(|| Ray {
+        origin: near,
+        direction,
+    })
+}
+
+// Distance along the ray to where it enters the box, None when it misses
+pub fn intersect_bounds(ray: &Ray, (min, max): (Vec3, Vec3)) -> Option<f32> {
+    let inverse = ray.direction.recip();
+    let a = (min - ray.origin) * inverse;
+    let b = (max - ray.origin) * inverse;
+    let enter = a.min(b).max_element().max(0.);
+    let exit = a.max(b).min_element();
+    (exit >= enter).then(|| enter)
+}
+
+// Index of the closest object whose world space bounding box the ray hits.
+// Objects without mesh data can't be picked.
+pub fn pick<'a>(ray: &Ray, objects: impl Iterator<Item = &'a SceneObject>) -> Option<usize> {
+    objects
+        .enumerate()
+        .filter_map(|(index, object)| {
+            let bounds = object.mesh_data.as_ref()?.bounds()?;
+            let distance = intersect_bounds(
+                ray,
+                mesh_generator::transform_bounds(bounds, object.transform),
+            )?;
+            Some((index, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(index, _)| index)
+}
@@ -0,0 +1,378 @@
+use glam::UVec2;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use super::tonemapping::HDR_FORMAT;
+
+// Matches Params in the shader
+const PARAMS_SIZE: u64 = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PostFxSettings {
+    pub bloom: bool,
+    // Brightness above which pixels start to glow, before exposure
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub vignette: bool,
+    pub vignette_intensity: f32,
+    // Distance from the center, with the corners at one, where the darkening starts
+    pub vignette_radius: f32,
+}
+
+impl Default for PostFxSettings {
+    fn default() -> Self {
+        Self {
+            bloom: false,
+            bloom_threshold: 1.,
+            bloom_intensity: 0.5,
+            vignette: false,
+            vignette_intensity: 0.4,
+            vignette_radius: 0.5,
+        }
+    }
+}
+
+impl PostFxSettings {
+    fn params(&self) -> [u8; PARAMS_SIZE as usize] {
+        let values = [
+            self.bloom_threshold,
+            self.bloom_intensity,
+            self.vignette_intensity,
+            self.vignette_radius,
+        ];
+        let mut bytes = [0; PARAMS_SIZE as usize];
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+// Half resolution ping-pong textures for the bloom blur, and what reads from them
+struct BloomTargets {
+    // The HDR view the bind groups were made for, rebuilt when the scene texture is
+    hdr: Arc<wgpu::TextureView>,
+    views: [wgpu::TextureView; 2],
+    hdr_bind_group: wgpu::BindGroup,
+    bind_groups: [wgpu::BindGroup; 2],
+    _textures: [wgpu::Texture; 2],
+}
+
+// Runs between the PBR routine and tonemapping, straight on the HDR scene texture
+pub struct PostFx {
+    params: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    threshold: wgpu::RenderPipeline,
+    blur_x: wgpu::RenderPipeline,
+    blur_y: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+    vignette: wgpu::RenderPipeline,
+    targets: Option<BloomTargets>,
+}
+
+fn pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    blend: Option<wgpu::BlendState>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        multiview: None,
+    })
+}
+
+// Alpha is left as the scene had it
+fn blend(src_factor: wgpu::BlendFactor, dst_factor: wgpu::BlendFactor) -> wgpu::BlendState {
+    wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+    }
+}
+
+fn draw(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
+
+impl PostFx {
+    pub fn new(renderer: &rend3::Renderer) -> Self {
+        let device = &renderer.device;
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post fx params"),
+            size: PARAMS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post fx"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..wgpu::SamplerDescriptor::default()
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post fx"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("post fx"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../data/shaders/post_fx.wgsl"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post fx"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let additive = blend(wgpu::BlendFactor::One, wgpu::BlendFactor::One);
+        let multiply = blend(wgpu::BlendFactor::Zero, wgpu::BlendFactor::Src);
+
+        Self {
+            threshold: pipeline(device, &pipeline_layout, &shader, "fs_threshold", None),
+            blur_x: pipeline(device, &pipeline_layout, &shader, "fs_blur_x", None),
+            blur_y: pipeline(device, &pipeline_layout, &shader, "fs_blur_y", None),
+            composite: pipeline(
+                device,
+                &pipeline_layout,
+                &shader,
+                "fs_composite",
+                Some(additive),
+            ),
+            vignette: pipeline(
+                device,
+                &pipeline_layout,
+                &shader,
+                "fs_vignette",
+                Some(multiply),
+            ),
+            params,
+            sampler,
+            layout,
+            targets: None,
+        }
+    }
+
+    fn bind_group(&self, device: &wgpu::Device, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn update_targets(&mut self, device: &wgpu::Device, hdr: &Arc<wgpu::TextureView>, size: UVec2) {
+        if let Some(targets) = &self.targets {
+            if Arc::ptr_eq(&targets.hdr, hdr) {
+                return;
+            }
+        }
+        let half = (size / 2).max(UVec2::ONE);
+        let textures = [(); 2].map(|_| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("bloom"),
+                size: wgpu::Extent3d {
+                    width: half.x,
+                    height: half.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            })
+        });
+        let views = [0, 1]
+            .map(|index| textures[index].create_view(&wgpu::TextureViewDescriptor::default()));
+        self.targets = Some(BloomTargets {
+            hdr: Arc::clone(hdr),
+            hdr_bind_group: self.bind_group(device, hdr),
+            bind_groups: [
+                self.bind_group(device, &views[0]),
+                self.bind_group(device, &views[1]),
+            ],
+            views,
+            _textures: textures,
+        });
+    }
+
+    // Draws the enabled effects into the HDR texture, once the scene has been rendered to it
+    pub fn apply(
+        &mut self,
+        renderer: &rend3::Renderer,
+        hdr: &Arc<wgpu::TextureView>,
+        size: UVec2,
+        settings: &PostFxSettings,
+    ) {
+        if !settings.bloom && !settings.vignette {
+            return;
+        }
+        self.update_targets(&renderer.device, hdr, size);
+        renderer
+            .queue
+            .write_buffer(&self.params, 0, &settings.params());
+
+        let targets = self.targets.as_ref().unwrap();
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("post fx"),
+            });
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        if settings.bloom {
+            draw(
+                &mut encoder,
+                &self.threshold,
+                &targets.hdr_bind_group,
+                &targets.views[0],
+                clear,
+            );
+            // Twice over for a wider glow than one pass of the kernel reaches
+            for _ in 0..2 {
+                draw(
+                    &mut encoder,
+                    &self.blur_x,
+                    &targets.bind_groups[0],
+                    &targets.views[1],
+                    clear,
+                );
+                draw(
+                    &mut encoder,
+                    &self.blur_y,
+                    &targets.bind_groups[1],
+                    &targets.views[0],
+                    clear,
+                );
+            }
+            draw(
+                &mut encoder,
+                &self.composite,
+                &targets.bind_groups[0],
+                hdr,
+                wgpu::LoadOp::Load,
+            );
+        }
+        if settings.vignette {
+            // Reads nothing, any bind group that doesn't sample the HDR texture will do
+            draw(
+                &mut encoder,
+                &self.vignette,
+                &targets.bind_groups[0],
+                hdr,
+                wgpu::LoadOp::Load,
+            );
+        }
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+pub fn post_fx_ui(ui: &mut egui::Ui, settings: &mut PostFxSettings) {
+    ui.checkbox(&mut settings.bloom, "Bloom");
+    if settings.bloom {
+        ui.add(
+            egui::Slider::new(&mut settings.bloom_threshold, 0.1..=10.0)
+                .logarithmic(true)
+                .text("Threshold"),
+        );
+        ui.add(egui::Slider::new(&mut settings.bloom_intensity, 0.0..=2.0).text("Bloom intensity"));
+    }
+    ui.checkbox(&mut settings.vignette, "Vignette");
+    if settings.vignette {
+        ui.add(
+            egui::Slider::new(&mut settings.vignette_intensity, 0.0..=1.0)
+                .text("Vignette intensity"),
+        );
+        ui.add(
+            egui::Slider::new(&mut settings.vignette_radius, 0.0..=0.95).text("Vignette radius"),
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use glam::{Mat4, Vec4};
+use rend3::types::CameraProjection;
+
+// Matrices are edited row by row in the UI, glam stores them column by column
+pub fn from_rows(rows: &[f32; 16]) -> Mat4 {
+    Mat4::from_cols_array(rows).transpose()
+}
+
+pub fn to_rows(matrix: Mat4) -> [f32; 16] {
+    matrix.transpose().to_cols_array()
+}
+
+// Same projection rend3 builds for CameraProjection::Perspective
+pub fn perspective(vfov: f32, aspect: f32, near: f32) -> Mat4 {
+    Mat4::perspective_infinite_reverse_lh(vfov.to_radians(), aspect, near)
+}
+
+// The matrix rend3 builds for any camera projection
+pub fn matrix(projection: CameraProjection, aspect: f32) -> Mat4 {
+    match projection {
+        CameraProjection::Orthographic { size } => {
+            let half = size * 0.5;
+            Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, half.z, -half.z)
+        }
+        CameraProjection::Perspective { vfov, near } => perspective(vfov, aspect, near),
+        CameraProjection::Raw(matrix) => matrix,
+    }
+}
+
+pub fn validate_projection(matrix: Mat4) -> Result<Mat4, String> {
+    if !matrix.is_finite() {
+        return Err("Projection contains non-finite values".to_string());
+    }
+
+    if matrix.determinant().abs() < 1e-6 {
+        return Err("Projection is degenerate (determinant is zero)".to_string());
+    }
+
+    // A point straight ahead of the camera has to end up in front of it
+    let ahead = matrix * Vec4::new(0., 0., 1., 1.);
+    if ahead.w <= 0. {
+        return Err("Projection puts the view direction behind the camera".to_string());
+    }
+
+    Ok(matrix)
+}
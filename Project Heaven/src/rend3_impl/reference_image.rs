@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+pub struct ReferenceImage {
+    pub texture: egui::TextureId,
+    pub size: egui::Vec2,
+
+    pub visible: bool,
+    pub opacity: f32,
+    pub scale: f32,
+    // While locked the image ignores the mouse and can't be dragged around
+    pub locked: bool,
+}
+
+pub fn load(
+    egui_routine: &mut rend3_egui::EguiRenderRoutine,
+    renderer: &Arc<rend3::Renderer>,
+    path: &str,
+) -> Result<ReferenceImage, String> {
+    let image = image::open(path).map_err(|ex| format!("{}: {}", path, ex))?;
+    let image_rgba = image.to_rgba8();
+    let dimensions = image_rgba.dimensions();
+
+    let texture = rend3_egui::EguiRenderRoutine::create_egui_texture(
+        &mut egui_routine.internal,
+        renderer,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        &image_rgba,
+        dimensions,
+        Some("reference_image"),
+    );
+
+    Ok(ReferenceImage {
+        texture,
+        size: egui::Vec2::new(dimensions.0 as f32, dimensions.1 as f32),
+        visible: true,
+        opacity: 0.5,
+        scale: 1.,
+        locked: false,
+    })
+}
+
+// Background order puts it on top of the 3D scene but under every window and panel
+pub fn draw(ctx: &egui::CtxRef, image: &ReferenceImage) {
+    if !image.visible {
+        return;
+    }
+    egui::Area::new("reference_image")
+        .order(egui::Order::Background)
+        .default_pos([0., 0.])
+        .movable(!image.locked)
+        .interactable(!image.locked)
+        .show(ctx, |ui| {
+            ui.add(
+                egui::Image::new(image.texture, image.size * image.scale).tint(
+                    egui::Color32::from_white_alpha((image.opacity * 255.) as u8),
+                ),
+            );
+        });
+}
+
+pub fn reference_ui(
+    ui: &mut egui::Ui,
+    egui_routine: &mut rend3_egui::EguiRenderRoutine,
+    renderer: &Arc<rend3::Renderer>,
+    path: &mut String,
+    image: &mut Option<ReferenceImage>,
+    error: &mut Option<String>,
+) {
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(path);
+        if ui.button("Load").clicked() {
+            match load(egui_routine, renderer, path) {
+                Ok(loaded) => {
+                    *image = Some(loaded);
+                    *error = None;
+                }
+                Err(ex) => *error = Some(ex),
+            }
+        }
+    });
+    if let Some(error) = error {
+        ui.label(error.as_str());
+    }
+
+    if let Some(loaded) = image {
+        ui.checkbox(&mut loaded.visible, "Show");
+        ui.add(egui::Slider::new(&mut loaded.opacity, 0.0..=1.0).text("Opacity"));
+        ui.add(
+            egui::Slider::new(&mut loaded.scale, 0.1..=4.0)
+                .logarithmic(true)
+                .text("Scale"),
+        );
+        ui.checkbox(&mut loaded.locked, "Lock");
+        if ui.button("Remove").clicked() {
+            *image = None;
+        }
+    }
+}
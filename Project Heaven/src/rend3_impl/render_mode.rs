@@ -0,0 +1,244 @@
+use glam::{Mat4, Vec3};
+use rend3::types::MaterialHandle;
+use rend3_routine::pbr::{AlbedoComponent, PbrMaterial};
+use std::borrow::Cow;
+
+use super::mesh_generator::{self, MeshData};
+use super::scene::{DebugLook, SceneObject};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Shaded,
+    Wireframe,
+    Normals,
+    UvChecker,
+    Depth,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Shaded,
+        RenderMode::Wireframe,
+        RenderMode::Normals,
+        RenderMode::UvChecker,
+        RenderMode::Depth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "Shaded",
+            RenderMode::Wireframe => "Wireframe",
+            RenderMode::Normals => "Normals",
+            RenderMode::UvChecker => "UV checker",
+            RenderMode::Depth => "Depth",
+        }
+    }
+
+    // The hotkey steps through them in order, back round to shaded
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// How far the camera has to move, as a part of the depth range, before the depth colours are
+// made again. No distance changes by more than the camera moved, so no shade is off by more
+// than a few steps of 255 in between.
+const DEPTH_REFRESH: f32 = 0.01;
+
+// Made once and shared by every object drawn in a debug mode. All unlit, so what they
+// show isn't mixed up with the lighting.
+pub struct DebugMaterials {
+    wireframe: MaterialHandle,
+    vertex_colors: MaterialHandle,
+    uv_checker: MaterialHandle,
+}
+
+impl DebugMaterials {
+    pub fn new(renderer: &rend3::Renderer) -> Self {
+        let (width, height, checker) = checker_texture();
+        let checker = renderer.add_texture_2d(rend3::types::Texture {
+            label: Some("UV checker".to_string()),
+            data: checker,
+            format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+            size: glam::UVec2::new(width, height),
+            mip_count: rend3::types::MipmapCount::Maximum,
+            mip_source: rend3::types::MipmapSource::Generated,
+        });
+        Self {
+            // Dark fill for the edge overlay to stand out against
+            wireframe: renderer.add_material(PbrMaterial {
+                albedo: AlbedoComponent::Value(glam::Vec4::new(0.02, 0.02, 0.02, 1.)),
+                unlit: true,
+                ..PbrMaterial::default()
+            }),
+            vertex_colors: renderer.add_material(PbrMaterial {
+                albedo: AlbedoComponent::Vertex { srgb: false },
+                unlit: true,
+                ..PbrMaterial::default()
+            }),
+            uv_checker: renderer.add_material(PbrMaterial {
+                albedo: AlbedoComponent::Texture(checker),
+                unlit: true,
+                ..PbrMaterial::default()
+            }),
+        }
+    }
+}
+
+// Eight by eight squares. The light ones go redder along u and greener along v, so
+// flipped or mirrored islands show up as well as stretching.
+fn checker_texture() -> (u32, u32, Vec<u8>) {
+    const SIZE: u32 = 256;
+    const SQUARE: u32 = SIZE / 8;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if (x / SQUARE + y / SQUARE) % 2 == 0 {
+                data.extend_from_slice(&[(128 + x / 2) as u8, (128 + y / 2) as u8, 160, 255]);
+            } else {
+                data.extend_from_slice(&[40, 40, 40, 255]);
+            }
+        }
+    }
+    (SIZE, SIZE, data)
+}
+
+pub fn effective_mode(object: &SceneObject, global: RenderMode) -> RenderMode {
+    object.render_mode.unwrap_or(global)
+}
+
+// Whether the mode wants the edge overlay on, whatever the wireframe checkbox says
+pub fn shows_edges(object: &SceneObject) -> bool {
+    object
+        .debug
+        .as_ref()
+        .map_or(false, |debug| debug.mode == RenderMode::Wireframe)
+}
+
+fn stale(object: &SceneObject, global: RenderMode) -> bool {
+    match &object.debug {
+        Some(debug) => debug.mode != effective_mode(object, global) || debug.source != object.mesh,
+        None => effective_mode(object, global) != RenderMode::Shaded,
+    }
+}
+
+// Object space, so it holds still while the object is moved around
+fn normal_colors(mesh: &MeshData) -> Vec<[u8; 4]> {
+    let normals = if mesh.normals.len() == mesh.positions.len() {
+        Cow::Borrowed(&mesh.normals)
+    } else {
+        Cow::Owned(mesh_generator::compute_smooth_normals(
+            &mesh.positions,
+            &mesh.indices,
+        ))
+    };
+    normals
+        .iter()
+        .map(|normal| {
+            let color = (normal.normalize_or_zero() * 0.5 + 0.5) * 255.;
+            [color.x as u8, color.y as u8, color.z as u8, 255]
+        })
+        .collect()
+}
+
+// White at the nearest point of everything in depth mode, black at the farthest
+fn depth_colors(
+    mesh: &MeshData,
+    transform: Mat4,
+    camera: Vec3,
+    (near, far): (f32, f32),
+) -> Vec<[u8; 4]> {
+    mesh.positions
+        .iter()
+        .map(|&position| {
+            let distance = transform.transform_point3(position).distance(camera);
+            let value = (1. - (distance - near) / (far - near).max(0.0001)).clamp(0., 1.);
+            let value = (value * 255.) as u8;
+            [value, value, value, 255]
+        })
+        .collect()
+}
+
+fn depth_range((min, max): (Vec3, Vec3), camera: Vec3) -> (f32, f32) {
+    let near = camera.clamp(min, max).distance(camera);
+    let far = (camera - min).abs().max((camera - max).abs()).length();
+    (near, far)
+}
+
+// Objects without mesh_data have nothing to colour, the vertex colour material draws them white
+fn debug_look(
+    renderer: &rend3::Renderer,
+    materials: &DebugMaterials,
+    object: &SceneObject,
+    mode: RenderMode,
+    camera: Vec3,
+    range: (f32, f32),
+) -> Option<DebugLook> {
+    let material = match mode {
+        RenderMode::Shaded => return None,
+        RenderMode::Wireframe => &materials.wireframe,
+        RenderMode::UvChecker => &materials.uv_checker,
+        RenderMode::Normals | RenderMode::Depth => &materials.vertex_colors,
+    };
+    let mesh = match (mode, &object.mesh_data) {
+        (RenderMode::Normals, Some(mesh)) => Some(mesh.build_with_colors(normal_colors(mesh))),
+        (RenderMode::Depth, Some(mesh)) => {
+            Some(mesh.build_with_colors(depth_colors(mesh, object.transform, camera, range)))
+        }
+        _ => None,
+    };
+    Some(DebugLook {
+        mode,
+        material: material.clone(),
+        mesh: mesh.map(|mesh| renderer.add_mesh(mesh)),
+        source: object.mesh.clone(),
+    })
+}
+
+// Rebuilds the objects whose mode or mesh changed since their look was made. Depth colours
+// depend on the camera and on everything else in depth mode, so those are all redone
+// together whenever either changes, the camera once it's moved far enough from `depth_camera`,
+// where they were last made from.
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    renderer: &rend3::Renderer,
+    materials: &DebugMaterials,
+    objects: &mut [&mut SceneObject],
+    global: RenderMode,
+    clay: Option<&MaterialHandle>,
+    wireframe: bool,
+    camera: Vec3,
+    depth_camera: &mut Vec3,
+) {
+    let in_depth = |object: &SceneObject| effective_mode(object, global) == RenderMode::Depth;
+    let range = objects
+        .iter()
+        .filter(|object| in_depth(object))
+        .filter_map(|object| {
+            let bounds = object.mesh_data.as_ref()?.bounds()?;
+            Some(mesh_generator::transform_bounds(bounds, object.transform))
+        })
+        .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)))
+        .map_or((0., 1.), |bounds| depth_range(bounds, camera));
+    let camera_moved = camera.distance(*depth_camera) > (range.1 - range.0) * DEPTH_REFRESH;
+    let redo_depth = objects
+        .iter()
+        .any(|object| in_depth(object) && (stale(object, global) || camera_moved));
+    if redo_depth {
+        *depth_camera = camera;
+    }
+
+    for object in objects.iter_mut() {
+        let mode = effective_mode(object, global);
+        if !stale(object, global) && !(mode == RenderMode::Depth && redo_depth) {
+            continue;
+        }
+        let had_edges = shows_edges(object);
+        object.debug = debug_look(renderer, materials, object, mode, camera, range);
+        object.refresh(renderer, clay);
+        if shows_edges(object) != had_edges {
+            object.set_wireframe(renderer, wireframe || shows_edges(object));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use rend3::types::CameraProjection;
+use serde::{Deserialize, Serialize};
+
+// Scene units are taken to be meters, like glTF
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Unit {
+    Meters,
+    Centimeters,
+    Feet,
+}
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit::Meters
+    }
+}
+
+impl Unit {
+    pub const ALL: [Unit; 3] = [Unit::Meters, Unit::Centimeters, Unit::Feet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Meters => "Meters",
+            Unit::Centimeters => "Centimeters",
+            Unit::Feet => "Feet",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Meters => "m",
+            Unit::Centimeters => "cm",
+            Unit::Feet => "ft",
+        }
+    }
+
+    pub fn per_meter(self) -> f32 {
+        match self {
+            Unit::Meters => 1.,
+            Unit::Centimeters => 100.,
+            Unit::Feet => 3.28084,
+        }
+    }
+}
+
+pub fn format_length(meters: f32, unit: Unit) -> String {
+    format!("{:.2} {}", meters * unit.per_meter(), unit.symbol())
+}
+
+// How many screen points one meter covers at the given distance from the camera
+pub fn points_per_meter(
+    projection: CameraProjection,
+    distance: f32,
+    screen_height: f32,
+) -> Option<f32> {
+    let y_scale = match projection {
+        CameraProjection::Orthographic { size } => return Some(screen_height / size.y),
+        CameraProjection::Perspective { vfov, .. } => 1. / (vfov.to_radians() / 2.).tan(),
+        // Assumed to be a perspective projection, anything else has no meaningful distance
+        CameraProjection::Raw(matrix) => matrix.y_axis.y,
+    };
+    (distance > 0.).then(|| y_scale * screen_height / 2. / distance)
+}
+
+// Largest 1, 2 or 5 times a power of ten that fits below the limit
+fn nice_length(limit: f32) -> f32 {
+    let magnitude = 10f32.powf(limit.log10().floor());
+    [5., 2., 1.]
+        .iter()
+        .map(|step| step * magnitude)
+        .find(|length| *length <= limit)
+        .unwrap_or(magnitude)
+}
+
+pub fn draw(ctx: &egui::CtxRef, unit: Unit, points_per_meter: f32) {
+    const TARGET_WIDTH: f32 = 150.;
+    let points_per_unit = points_per_meter / unit.per_meter();
+    if !points_per_unit.is_finite() || points_per_unit <= 0. {
+        return;
+    }
+    let length = nice_length(TARGET_WIDTH / points_per_unit);
+    let width = length * points_per_unit;
+
+    let screen = ctx.input().screen_rect();
+    let start = egui::pos2(screen.left() + 20., screen.bottom() - 20.);
+    let end = start + egui::vec2(width, 0.);
+    let stroke = egui::Stroke::new(2., egui::Color32::WHITE);
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    painter.line_segment([start, end], stroke);
+    painter.line_segment([start, start - egui::vec2(0., 6.)], stroke);
+    painter.line_segment([end, end - egui::vec2(0., 6.)], stroke);
+    painter.text(
+        start - egui::vec2(0., 8.),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{} {}", length, unit.symbol()),
+        egui::TextStyle::Body,
+        egui::Color32::WHITE,
+    );
+}
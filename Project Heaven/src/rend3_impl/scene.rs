@@ -0,0 +1,477 @@
+use glam::{Mat4, Vec3};
+use rend3::types::{MaterialHandle, MeshHandle, ObjectHandle, SkeletonHandle};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::lod::Lod;
+use super::material_animation::{ship_material, SurfaceParams, TransparencyMode};
+use super::mesh_diagnostics::MeshReport;
+use super::mesh_generator::{create_wireframe, MeshData, Primitive};
+use super::render_mode::RenderMode;
+
+// Makes an object glow through a bright emissive material of its own
+pub struct LightMesh {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub material: MaterialHandle,
+}
+
+// What a debug render mode draws the object with in place of its own look
+pub struct DebugLook {
+    pub mode: RenderMode,
+    pub material: MaterialHandle,
+    // Copy of the mesh carrying vertex colours, for the modes that need one
+    pub mesh: Option<MeshHandle>,
+    // Mesh the look was made for, a rebuilt mesh needs a new copy
+    pub source: MeshHandle,
+}
+
+// Factors the object's material was made from, for exporting. Textures aren't kept.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PbrFactors {
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+}
+
+// The same as clay_material
+impl Default for PbrFactors {
+    fn default() -> Self {
+        Self {
+            base_color: [0.8, 0.8, 0.8, 1.],
+            metallic: 0.,
+            roughness: 0.8,
+            emissive: [0.; 3],
+        }
+    }
+}
+
+// Hands out SceneObject ids
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub struct SceneObject {
+    // Stays with the object while it's renamed, reloaded or renumbered in the scene panel
+    id: u64,
+    // What the scene panel lists it as
+    pub name: String,
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub factors: PbrFactors,
+    // The rest of what the material editor last set, None while the object still has the
+    // material it came with. A reloaded file doesn't replace an edited material.
+    pub surface: Option<SurfaceParams>,
+    pub transform: Mat4,
+    pub handle: ObjectHandle,
+    // Skinned objects are drawn through their skeleton instead of straight from the mesh
+    pub skeleton: Option<SkeletonHandle>,
+    // Last pose the skeleton was given, a rebuilt mesh needs a new skeleton in the same pose
+    joint_matrices: Vec<Mat4>,
+
+    pub light: Option<LightMesh>,
+
+    // Only kept for meshes that can be diagnosed and rebuilt
+    pub mesh_data: Option<MeshData>,
+    pub report: Option<MeshReport>,
+
+    // glTF extras from the source asset, shown read-only
+    pub extras: Vec<(String, String)>,
+
+    // Set while the physics simulation moves the object
+    pub body: Option<rapier3d::prelude::RigidBodyHandle>,
+
+    // Overrides the global render mode for this object when set
+    pub render_mode: Option<RenderMode>,
+    pub debug: Option<DebugLook>,
+
+    // Coarser meshes swapped in with distance, `mesh` is whichever is drawn
+    pub lod: Option<Lod>,
+
+    // Edge overlay drawn on top of the shaded object, built from mesh_data
+    wireframe: Option<ObjectHandle>,
+}
+
+fn add_object(
+    renderer: &rend3::Renderer,
+    mesh: &MeshHandle,
+    skeleton: Option<&SkeletonHandle>,
+    material: &MaterialHandle,
+    transform: Mat4,
+) -> ObjectHandle {
+    renderer.add_object(rend3::types::Object {
+        mesh_kind: match skeleton {
+            Some(skeleton) => rend3::types::ObjectMeshKind::Animated(skeleton.clone()),
+            None => rend3::types::ObjectMeshKind::Static(mesh.clone()),
+        },
+        material: material.clone(),
+        transform,
+    })
+}
+
+impl SceneObject {
+    pub fn new(
+        renderer: &rend3::Renderer,
+        mesh: MeshHandle,
+        material: MaterialHandle,
+        transform: Mat4,
+    ) -> Self {
+        let handle = add_object(renderer, &mesh, None, &material, transform);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: String::new(),
+            mesh,
+            material,
+            factors: PbrFactors::default(),
+            surface: None,
+            transform,
+            handle,
+            skeleton: None,
+            joint_matrices: Vec::new(),
+            light: None,
+            mesh_data: None,
+            report: None,
+            extras: Vec::new(),
+            body: None,
+            render_mode: None,
+            debug: None,
+            lod: None,
+            wireframe: None,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    // Every skinned object gets a skeleton of its own, even when they share a skin
+    pub fn new_skinned(
+        renderer: &rend3::Renderer,
+        mesh: MeshHandle,
+        joint_matrices: Vec<Mat4>,
+        material: MaterialHandle,
+        transform: Mat4,
+    ) -> Self {
+        let mut object = Self::new(renderer, mesh, material, transform);
+        object.joint_matrices = joint_matrices;
+        object.set_mesh(renderer, object.mesh.clone(), None);
+        object
+    }
+
+    // A skinned object's skeleton is tied to its mesh, so it's made again for the new one
+    pub fn set_mesh(
+        &mut self,
+        renderer: &rend3::Renderer,
+        mesh: MeshHandle,
+        clay: Option<&MaterialHandle>,
+    ) {
+        if !self.joint_matrices.is_empty() {
+            self.skeleton = Some(renderer.add_skeleton(rend3::types::Skeleton {
+                joint_matrices: self.joint_matrices.clone(),
+                mesh: mesh.clone(),
+            }));
+        }
+        self.mesh = mesh;
+        self.refresh(renderer, clay);
+    }
+
+    // Geometry from elsewhere, the material stays. Fixes from the diagnostics panel go with
+    // the old mesh.
+    pub fn set_mesh_data(
+        &mut self,
+        renderer: &rend3::Renderer,
+        mesh: MeshData,
+        clay: Option<&MaterialHandle>,
+    ) {
+        let handle = renderer.add_mesh(mesh.build());
+        self.mesh_data = Some(mesh);
+        self.report = None;
+        self.set_mesh(renderer, handle, clay);
+    }
+
+    // What the material editor starts from, the material's own values until it's edited
+    pub fn surface(&self) -> SurfaceParams {
+        self.surface.clone().unwrap_or_else(|| SurfaceParams {
+            emissive: self.factors.emissive,
+            transparency: if self.factors.base_color[3] < 1. {
+                TransparencyMode::Blend
+            } else {
+                TransparencyMode::Opaque
+            },
+            ..SurfaceParams::default()
+        })
+    }
+
+    // Made from the factors and `surface` the way the ship's is, textures and all replaced.
+    // The first edit gives the object a material of its own, the one it came with can be
+    // shared with other objects from the same file.
+    pub fn set_material(
+        &mut self,
+        renderer: &rend3::Renderer,
+        surface: SurfaceParams,
+        clay: Option<&MaterialHandle>,
+    ) {
+        self.factors.emissive = surface.emissive;
+        let factors = self.factors;
+        let material = ship_material(
+            factors.base_color,
+            factors.roughness,
+            factors.metallic,
+            &surface,
+            None,
+        );
+        if self.surface.is_some() {
+            renderer.update_material(&self.material, material);
+        } else {
+            self.material = renderer.add_material(material);
+            self.refresh(renderer, clay);
+        }
+        self.surface = Some(surface);
+    }
+
+    // The geometry of a reloaded file's version of the object, and its material unless that
+    // was edited. Where it was moved, how it's drawn and its light stay.
+    pub fn reload_from(
+        &mut self,
+        renderer: &rend3::Renderer,
+        reloaded: SceneObject,
+        clay: Option<&MaterialHandle>,
+    ) {
+        if self.surface.is_none() {
+            self.material = reloaded.material;
+            self.factors = reloaded.factors;
+        }
+        self.mesh_data = reloaded.mesh_data;
+        self.report = None;
+        self.extras = reloaded.extras;
+        self.lod = reloaded.lod;
+        self.skeleton = None;
+        self.joint_matrices = reloaded.joint_matrices;
+        self.set_mesh(renderer, reloaded.mesh, clay);
+    }
+
+    pub fn set_joint_matrices(&mut self, renderer: &rend3::Renderer, joint_matrices: Vec<Mat4>) {
+        if let Some(skeleton) = &self.skeleton {
+            renderer.set_skeleton_joint_matrices(skeleton, joint_matrices.clone());
+        }
+        self.joint_matrices = joint_matrices;
+    }
+
+    // Objects can't change material in place, so a new object sharing the same mesh
+    // replaces the old one. Dropping the old handle removes it from the renderer.
+    //
+    // A debug look wins over clay, which wins over the light mesh material, which wins
+    // over the object's own material.
+    pub fn refresh(&mut self, renderer: &rend3::Renderer, clay: Option<&MaterialHandle>) {
+        let material = match (&self.debug, clay, &self.light) {
+            (Some(debug), _, _) => &debug.material,
+            (None, Some(clay), _) => clay,
+            (None, None, Some(light)) => &light.material,
+            (None, None, None) => &self.material,
+        };
+        self.handle = match self.debug.as_ref().and_then(|debug| debug.mesh.as_ref()) {
+            // Drawn in the rest pose, the coloured copy has no skeleton of its own
+            Some(mesh) => add_object(renderer, mesh, None, material, self.transform),
+            None => add_object(
+                renderer,
+                &self.mesh,
+                self.skeleton.as_ref(),
+                material,
+                self.transform,
+            ),
+        };
+
+        // The mesh may have been rebuilt, so the overlay is too
+        if self.wireframe.is_some() {
+            self.set_wireframe(renderer, true);
+        }
+    }
+
+    // Objects without mesh_data have nothing to build the overlay from and stay as they are
+    pub fn set_wireframe(&mut self, renderer: &rend3::Renderer, enabled: bool) {
+        self.wireframe = match (&self.mesh_data, enabled) {
+            (Some(mesh), true) => {
+                let width = mesh
+                    .bounds()
+                    .map_or(0.01, |(min, max)| (max - min).length() * 0.0005);
+                let mesh = renderer.add_mesh(create_wireframe(mesh, width).build());
+                let material = renderer.add_material(rend3_routine::pbr::PbrMaterial {
+                    albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(
+                        0., 1., 0.5, 1.,
+                    )),
+                    unlit: true,
+                    ..rend3_routine::pbr::PbrMaterial::default()
+                });
+                // Drawn in the rest pose, the overlay doesn't follow the skeleton
+                Some(add_object(renderer, &mesh, None, &material, self.transform))
+            }
+            _ => None,
+        };
+    }
+
+    // Every transform update is its own renderer instruction, so skip the ones
+    // that wouldn't change anything
+    pub fn set_transform(&mut self, renderer: &rend3::Renderer, transform: Mat4) {
+        if transform != self.transform {
+            renderer.set_object_transform(&self.handle, transform);
+            if let Some(wireframe) = &self.wireframe {
+                renderer.set_object_transform(wireframe, transform);
+            }
+            self.transform = transform;
+        }
+    }
+}
+
+// Every object but the ship and the station, each kind kept in a flat Vec of its own
+#[derive(Default)]
+pub struct Scene {
+    // Whole glTF scenes opened from the menu, one object per primitive
+    pub objects: Vec<SceneObject>,
+    // glTF node each of `objects` came from
+    pub object_nodes: Vec<usize>,
+    // Primitives added from the menu, kept apart so opening a scene doesn't remove them
+    pub spawned: Vec<SceneObject>,
+    // What each spawned object was made from, lines up with `spawned`
+    pub spawned_primitives: Vec<Primitive>,
+    // Cubes dropped into the physics simulation, they go away with it
+    pub falling_cubes: Vec<SceneObject>,
+    // From spawn_grid, all sharing one mesh and material. Only the handles are kept, there
+    // are too many for a SceneObject each.
+    pub instances: Vec<ObjectHandle>,
+    pub triangles_per_instance: usize,
+}
+
+impl Scene {
+    // Count × count × count copies of one primitive in place of the last grid, centred on
+    // `center` and `spacing` apart on every axis. Sharing the mesh means rend3 only uploads
+    // the geometry once.
+    pub fn spawn_grid(
+        &mut self,
+        renderer: &rend3::Renderer,
+        primitive: Primitive,
+        count: u32,
+        spacing: f32,
+        center: Vec3,
+    ) {
+        // The old grid goes first so the two never have to fit in memory together
+        self.instances.clear();
+        let mesh_data = primitive.mesh_data();
+        let mesh = renderer.add_mesh(mesh_data.build());
+        let material = renderer.add_material(clay_material([0.6, 0.7, 0.9, 1.]));
+
+        let offset = (count.max(1) - 1) as f32 * spacing / 2.;
+        self.instances
+            .extend((0..count * count * count).map(|index| {
+                let cell = Vec3::new(
+                    (index % count) as f32,
+                    (index / count % count) as f32,
+                    (index / (count * count)) as f32,
+                );
+                renderer.add_object(rend3::types::Object {
+                    mesh_kind: rend3::types::ObjectMeshKind::Static(mesh.clone()),
+                    material: material.clone(),
+                    transform: Mat4::from_translation(
+                        center + cell * spacing - Vec3::splat(offset),
+                    ),
+                })
+            }));
+        self.triangles_per_instance = mesh_data.indices.len() / 3;
+    }
+
+    pub fn instance_triangles(&self) -> usize {
+        self.instances.len() * self.triangles_per_instance
+    }
+}
+
+// Neutral matte material used to judge form without textures or colours getting in the way
+pub fn clay_material(color: [f32; 4]) -> rend3_routine::pbr::PbrMaterial {
+    rend3_routine::pbr::PbrMaterial {
+        albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::from(color)),
+        metallic_factor: Some(0.),
+        roughness_factor: Some(0.8),
+        ..rend3_routine::pbr::PbrMaterial::default()
+    }
+}
+
+pub fn light_mesh_material(color: [f32; 3], intensity: f32) -> rend3_routine::pbr::PbrMaterial {
+    rend3_routine::pbr::PbrMaterial {
+        albedo: rend3_routine::pbr::AlbedoComponent::Value(Vec3::from(color).extend(1.)),
+        emissive: rend3_routine::pbr::MaterialComponent::Value(Vec3::from(color) * intensity),
+        ..rend3_routine::pbr::PbrMaterial::default()
+    }
+}
+
+pub fn light_mesh_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    label: &str,
+    object: &mut SceneObject,
+    clay: Option<&MaterialHandle>,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = object.light.is_some();
+        if ui.checkbox(&mut enabled, label).changed() {
+            object.light = if enabled {
+                let color = [1., 1., 1.];
+                let intensity = 10.;
+                Some(LightMesh {
+                    color,
+                    intensity,
+                    material: renderer.add_material(light_mesh_material(color, intensity)),
+                })
+            } else {
+                None
+            };
+            object.refresh(renderer, clay);
+        }
+
+        if let Some(light) = &mut object.light {
+            let color_changed = ui.color_edit_button_rgb(&mut light.color).changed();
+            let intensity_changed = ui
+                .add(egui::Slider::new(&mut light.intensity, 0.0..=100.0).text("Intensity"))
+                .changed();
+            if color_changed || intensity_changed {
+                renderer.update_material(
+                    &light.material,
+                    light_mesh_material(light.color, light.intensity),
+                );
+            }
+        }
+    });
+}
+
+pub fn extras_ui(ui: &mut egui::Ui, label: &str, object: &SceneObject) {
+    ui.label(label);
+    if object.extras.is_empty() {
+        ui.label("No extras");
+    }
+    for (source, value) in &object.extras {
+        ui.horizontal(|ui| {
+            ui.label(source);
+            ui.monospace(value);
+        });
+    }
+}
+
+// rend3 samples every texture through uv0, so picking a set means rebuilding the mesh
+// with that set in the uv0 slot
+pub fn uv_set_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    label: &str,
+    object: &mut SceneObject,
+    clay: Option<&MaterialHandle>,
+) {
+    let mesh = match &mut object.mesh_data {
+        Some(mesh) if !mesh.uvs1.is_empty() => mesh,
+        _ => return,
+    };
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(label);
+        changed |= ui.radio_value(&mut mesh.uv_set, 0, "TEXCOORD_0").changed();
+        changed |= ui.radio_value(&mut mesh.uv_set, 1, "TEXCOORD_1").changed();
+    });
+    if changed {
+        object.mesh = renderer.add_mesh(mesh.build());
+        object.refresh(renderer, clay);
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::bookmarks::CameraBookmark;
+use super::material_animation::SurfaceParams;
+use super::mesh_generator::Primitive;
+use super::terrain::TerrainParams;
+
+pub const SCENE_STATE_PATH: &str = "scene_state.json";
+
+// A primitive added from the menu
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpawnedState {
+    pub name: String,
+    pub primitive: Primitive,
+    pub transform: [f32; 16],
+}
+
+// Noise terrain when there's no heightmap
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerrainState {
+    pub params: TerrainParams,
+    pub heightmap: Option<PathBuf>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LightState {
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub distance: f32,
+}
+
+// Everything needed to put the scene back together: what gets tweaked from the menu,
+// and where the assets came from so they can be loaded again.
+// Missing fields fall back to their defaults like the settings do.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneState {
+    pub color: [f32; 4],
+    pub roughness: f32,
+    pub metallic: f32,
+    pub surface: SurfaceParams,
+    pub albedo_texture: Option<PathBuf>,
+
+    pub sun_direction: [f32; 3],
+    pub sun_intensity: f32,
+    pub sun_color: [f32; 3],
+    pub sun_distance: f32,
+    pub extra_lights: Vec<LightState>,
+
+    pub orbit_camera: bool,
+    pub orbit_pitch: f32,
+    pub orbit_yaw: f32,
+    pub orbit_distance: f32,
+
+    pub station_primitive: Option<Primitive>,
+    // None keeps whatever model and transform the station has
+    pub station_model: Option<PathBuf>,
+    pub station_transform: Option<[f32; 16]>,
+    // glTF extras as (what they were attached to, raw JSON), like the importer labels them
+    pub station_extras: Vec<(String, String)>,
+    // glTF file opened as a scene, and the transform and extras of every object made from it
+    pub scene: Option<PathBuf>,
+    pub scene_transforms: Vec<[f32; 16]>,
+    pub scene_extras: Vec<Vec<(String, String)>>,
+    pub spawned: Vec<SpawnedState>,
+    pub terrain: Option<TerrainState>,
+
+    pub free_camera: bool,
+    // None keeps the camera and the ship where they are
+    pub camera_location: Option<[f32; 3]>,
+    pub camera_rotation: Option<[f32; 4]>,
+    pub ship_location: Option<[f32; 3]>,
+    pub ship_rotation: Option<[f32; 4]>,
+    pub bookmarks: Vec<CameraBookmark>,
+
+    pub orthographic: bool,
+    pub vfov: f32,
+    pub near: f32,
+    pub ortho_size: f32,
+    // Column major, like the transforms
+    pub projection_override: Option<[f32; 16]>,
+}
+
+impl Default for SceneState {
+    fn default() -> Self {
+        let sun = super::lighting::sun();
+        Self {
+            color: [0., 0.5, 0.5, 1.],
+            roughness: 1.,
+            metallic: 0.,
+            surface: SurfaceParams::default(),
+            albedo_texture: None,
+            sun_direction: sun.direction.to_array(),
+            sun_intensity: sun.intensity,
+            sun_color: sun.color.to_array(),
+            sun_distance: sun.distance,
+            extra_lights: Vec::new(),
+            orbit_camera: false,
+            orbit_pitch: 0.3,
+            orbit_yaw: 0.,
+            orbit_distance: 10.,
+            station_primitive: None,
+            station_model: None,
+            station_transform: None,
+            station_extras: Vec::new(),
+            scene: None,
+            scene_transforms: Vec::new(),
+            scene_extras: Vec::new(),
+            spawned: Vec::new(),
+            terrain: None,
+            free_camera: false,
+            camera_location: None,
+            camera_rotation: None,
+            ship_location: None,
+            ship_rotation: None,
+            bookmarks: Vec::new(),
+            orthographic: false,
+            vfov: 60.,
+            near: 0.1,
+            ortho_size: 50.,
+            projection_override: None,
+        }
+    }
+}
+
+pub fn save(path: &Path, state: &SceneState) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(state).map_err(|ex| ex.to_string())?;
+    std::fs::write(path, text).map_err(|ex| format!("{}: {}", path.display(), ex))
+}
+
+pub fn load(path: &Path) -> Result<SceneState, String> {
+    let text = std::fs::read_to_string(path).map_err(|ex| format!("{}: {}", path.display(), ex))?;
+    serde_json::from_str(&text).map_err(|ex| format!("{}: {}", path.display(), ex))
+}
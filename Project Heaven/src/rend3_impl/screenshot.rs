@@ -0,0 +1,136 @@
+use glam::UVec2;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Offscreen copy of the surface, the frame is rendered here instead when a screenshot is taken
+pub struct ScreenshotTarget {
+    pub size: UVec2,
+    pub view: Arc<wgpu::TextureView>,
+    texture: wgpu::Texture,
+    format: wgpu::TextureFormat,
+}
+
+impl ScreenshotTarget {
+    pub fn new(renderer: &rend3::Renderer, size: UVec2, format: wgpu::TextureFormat) -> Self {
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("screenshot"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Same format as the surface so the tonemapping and egui pipelines can draw to it
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = Arc::new(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        Self {
+            size,
+            view,
+            texture,
+            format,
+        }
+    }
+
+    // Writes a PNG named after the current time
+    pub fn save(&self, renderer: &rend3::Renderer) -> Result<PathBuf, String> {
+        // Neither the clock nor blocking on the readback work in the browser
+        if cfg!(target_arch = "wasm32") {
+            return Err("screenshots can't be saved from the browser".to_string());
+        }
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_millis();
+        let path = PathBuf::from(format!("screenshot-{}.png", millis));
+        self.save_as(renderer, &path)?;
+        Ok(path)
+    }
+
+    // Reads the texture back and writes it as a PNG.
+    // Has to run after the frame that drew into it was executed.
+    //
+    // The file holds the bytes the window shows, which a display takes as sRGB whether or
+    // not the surface format is an sRGB one, so it's tagged sRGB.
+    pub fn save_as(&self, renderer: &rend3::Renderer, path: &Path) -> Result<(), String> {
+        let bytes_per_row = self.size.x * 4;
+        // Buffer copies need every row to start on a 256 byte boundary
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (bytes_per_row + align - 1) / align * align;
+
+        let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screenshot readback"),
+            size: (padded_bytes_per_row * self.size.y) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("screenshot"),
+            });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        renderer.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        renderer.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping)
+            .map_err(|e| format!("Failed to read back screenshot: {}", e))?;
+
+        let mut pixels = Vec::with_capacity((bytes_per_row * self.size.y) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..bytes_per_row as usize]);
+        }
+        buffer.unmap();
+
+        match self.format {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {}
+            format => return Err(format!("Can't save a {:?} surface as PNG", format)),
+        }
+
+        let failed =
+            |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+        let file = std::fs::File::create(path).map_err(|e| failed(&e))?;
+        let mut encoder =
+            png::Encoder::new(std::io::BufWriter::new(file), self.size.x, self.size.y);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(&pixels)?;
+                writer.finish()
+            })
+            .map_err(|e| failed(&e))
+    }
+}
@@ -0,0 +1,417 @@
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use rhai::{Dynamic, Engine, EvalAltResult};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::cli::CameraPose;
+use super::mesh_generator::Primitive;
+use super::{asset_loader, file_dialog, hierarchy_object, hierarchy_objects};
+use super::{refresh_ship_material, settings, spawn_primitive, RenderingData};
+
+// Runs on the UI thread, so a script that never ends is stopped with an error instead of
+// freezing the app. Plenty for anything that only queues commands.
+const MAX_OPERATIONS: u64 = 5_000_000;
+
+// Shown in an empty console
+const EXAMPLE: &str = r#"// spawn("cube", "Crate", 0, 1, 0);
+// set_transform("Crate", 0, 2, 0, 45, 0, 0, 1.5);
+// set_color("Crate", 1, 0.5, 0);
+// move_camera(0, 3, -10, -10, 0);
+// load_gltf("models/scene.glb");
+"#;
+
+pub struct ScriptConsole {
+    pub open: bool,
+    pub source: String,
+    // File the source was opened from, relative glTF paths are found next to it
+    pub path: Option<PathBuf>,
+    pub output: Vec<String>,
+}
+
+impl Default for ScriptConsole {
+    fn default() -> Self {
+        Self {
+            open: false,
+            source: EXAMPLE.to_string(),
+            path: None,
+            output: Vec::new(),
+        }
+    }
+}
+
+// Scene changes a script asked for. The engine can't hold on to the scene while it runs, so
+// they are queued and made in order once it has finished.
+enum ScriptCommand {
+    Spawn {
+        primitive: Primitive,
+        name: String,
+        position: Vec3,
+    },
+    LoadGltf(PathBuf),
+    SetTransform {
+        name: String,
+        transform: Mat4,
+    },
+    SetColor {
+        name: String,
+        color: [f32; 4],
+    },
+    MoveCamera(CameraPose),
+    Print(String),
+}
+
+type Queue = Rc<RefCell<Vec<ScriptCommand>>>;
+
+// Scripts can write 1 as well as 1.0
+fn number(value: &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    value
+        .as_float()
+        .map(|value| value as f32)
+        .or_else(|_| value.as_int().map(|value| value as f32))
+        .map_err(|_| format!("expected a number, got {}", value.type_name()).into())
+}
+
+fn vec3(x: &Dynamic, y: &Dynamic, z: &Dynamic) -> Result<Vec3, Box<EvalAltResult>> {
+    Ok(Vec3::new(number(x)?, number(y)?, number(z)?))
+}
+
+fn primitive(kind: &str) -> Result<Primitive, Box<EvalAltResult>> {
+    Primitive::ALL
+        .into_iter()
+        .find(|primitive| primitive.name().eq_ignore_ascii_case(kind))
+        .ok_or_else(|| {
+            let kinds: Vec<&str> = Primitive::ALL.iter().map(|kind| kind.name()).collect();
+            format!(
+                "no primitive called {}, try one of {}",
+                kind,
+                kinds.join(", ")
+            )
+            .into()
+        })
+}
+
+fn engine(queue: &Queue, base: Option<&Path>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let commands = queue.clone();
+    engine.on_print(move |text| {
+        commands
+            .borrow_mut()
+            .push(ScriptCommand::Print(text.to_string()))
+    });
+
+    let commands = queue.clone();
+    engine.register_result_fn("spawn", move |kind: &str, name: &str| {
+        commands.borrow_mut().push(ScriptCommand::Spawn {
+            primitive: primitive(kind)?,
+            name: name.to_string(),
+            position: Vec3::ZERO,
+        });
+        Ok(())
+    });
+    let commands = queue.clone();
+    engine.register_result_fn(
+        "spawn",
+        move |kind: &str, name: &str, x: Dynamic, y: Dynamic, z: Dynamic| {
+            commands.borrow_mut().push(ScriptCommand::Spawn {
+                primitive: primitive(kind)?,
+                name: name.to_string(),
+                position: vec3(&x, &y, &z)?,
+            });
+            Ok(())
+        },
+    );
+
+    let commands = queue.clone();
+    let base = base.map(Path::to_path_buf);
+    engine.register_fn("load_gltf", move |path: &str| {
+        let path = match &base {
+            Some(base) => base.join(path),
+            None => PathBuf::from(path),
+        };
+        commands.borrow_mut().push(ScriptCommand::LoadGltf(path));
+    });
+
+    let commands = queue.clone();
+    engine.register_result_fn(
+        "set_transform",
+        move |name: &str, x: Dynamic, y: Dynamic, z: Dynamic| {
+            commands.borrow_mut().push(ScriptCommand::SetTransform {
+                name: name.to_string(),
+                transform: Mat4::from_translation(vec3(&x, &y, &z)?),
+            });
+            Ok(())
+        },
+    );
+    // Angles in degrees, turned in the same order the scene panel shows them
+    let commands = queue.clone();
+    engine.register_result_fn(
+        "set_transform",
+        move |name: &str,
+              x: Dynamic,
+              y: Dynamic,
+              z: Dynamic,
+              yaw: Dynamic,
+              pitch: Dynamic,
+              roll: Dynamic,
+              scale: Dynamic| {
+            let rotation = Quat::from_euler(
+                EulerRot::YXZ,
+                number(&yaw)?.to_radians(),
+                number(&pitch)?.to_radians(),
+                number(&roll)?.to_radians(),
+            );
+            commands.borrow_mut().push(ScriptCommand::SetTransform {
+                name: name.to_string(),
+                transform: Mat4::from_scale_rotation_translation(
+                    Vec3::splat(number(&scale)?),
+                    rotation,
+                    vec3(&x, &y, &z)?,
+                ),
+            });
+            Ok(())
+        },
+    );
+
+    let commands = queue.clone();
+    engine.register_result_fn(
+        "set_color",
+        move |name: &str, r: Dynamic, g: Dynamic, b: Dynamic| {
+            let color = vec3(&r, &g, &b)?;
+            commands.borrow_mut().push(ScriptCommand::SetColor {
+                name: name.to_string(),
+                color: color.extend(1.).to_array(),
+            });
+            Ok(())
+        },
+    );
+
+    let commands = queue.clone();
+    engine.register_result_fn("move_camera", move |x: Dynamic, y: Dynamic, z: Dynamic| {
+        commands
+            .borrow_mut()
+            .push(ScriptCommand::MoveCamera(CameraPose {
+                location: vec3(&x, &y, &z)?,
+                angles: None,
+            }));
+        Ok(())
+    });
+    // Same angles as --camera, looking up and turning right are positive
+    let commands = queue.clone();
+    engine.register_result_fn(
+        "move_camera",
+        move |x: Dynamic, y: Dynamic, z: Dynamic, pitch: Dynamic, yaw: Dynamic| {
+            commands
+                .borrow_mut()
+                .push(ScriptCommand::MoveCamera(CameraPose {
+                    location: vec3(&x, &y, &z)?,
+                    angles: Some((number(&pitch)?, number(&yaw)?)),
+                }));
+            Ok(())
+        },
+    );
+
+    engine
+}
+
+// By the name the scene panel lists it under. The ship isn't there, the flight controls
+// set its transform every frame, so only its colour can be set.
+fn find_object(data: &RenderingData, name: &str) -> Result<usize, String> {
+    hierarchy_objects(data)
+        .position(|object| object.name == name)
+        .ok_or(format!("no object called {}", name))
+}
+
+fn apply(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    command: ScriptCommand,
+) -> Result<(), String> {
+    match command {
+        ScriptCommand::Spawn {
+            primitive,
+            name,
+            position,
+        } => spawn_primitive(
+            data,
+            renderer,
+            primitive,
+            name,
+            Mat4::from_translation(position),
+        ),
+        // Loads finish on a later frame, so the rest of the script can't move what they add
+        ScriptCommand::LoadGltf(path) => {
+            if data
+                .pending_loads
+                .iter()
+                .any(|pending| pending.target == asset_loader::LoadTarget::Scene)
+            {
+                return Err(format!(
+                    "{} skipped, a scene load is already running",
+                    path.display()
+                ));
+            }
+            data.pending_loads.push(asset_loader::PendingLoad::start(
+                path,
+                asset_loader::LoadTarget::Scene,
+                false,
+            ));
+        }
+        ScriptCommand::SetTransform { name, transform } => {
+            let index = find_object(data, &name)?;
+            hierarchy_object(data, index)
+                .unwrap()
+                .set_transform(renderer, transform);
+        }
+        ScriptCommand::SetColor { name, color } if name == "Ship" => {
+            data.color = color;
+            refresh_ship_material(data, renderer);
+        }
+        // Takes the place of the object's own material, textures and all, the same as an
+        // edit in the material editor
+        ScriptCommand::SetColor { name, color } => {
+            let index = find_object(data, &name)?;
+            let clay = data.clay_mode.then(|| data.clay_material_handle.clone());
+            let object = hierarchy_object(data, index).unwrap();
+            object.factors.base_color = color;
+            let surface = object.surface();
+            object.set_material(renderer, surface, clay.as_ref());
+        }
+        ScriptCommand::MoveCamera(pose) => {
+            data.camtype = true;
+            data.orbit_camera = false;
+            data.gltf_camera = None;
+            data.fly_to = None;
+            data.camera_location = pose.location.into();
+            data.camera_rotation = pose.orientation().inverse();
+        }
+        ScriptCommand::Print(text) => data.script_console.output.push(text),
+    }
+    Ok(())
+}
+
+// Whatever the script queued before an error still happens, the same as if it had been
+// making the changes as it went
+pub fn run_script(
+    data: &mut RenderingData,
+    renderer: &rend3::Renderer,
+    source: &str,
+    base: Option<&Path>,
+) {
+    let queue = Queue::default();
+    let result = engine(&queue, base).run(source);
+    for command in queue.take() {
+        if let Err(ex) = apply(data, renderer, command) {
+            data.script_console.output.push(format!("ERROR -> {}", ex));
+        }
+    }
+    if let Err(ex) = result {
+        data.script_console.output.push(format!("ERROR -> {}", ex));
+    }
+}
+
+pub fn run_file(data: &mut RenderingData, renderer: &rend3::Renderer, path: &Path) {
+    match std::fs::read_to_string(path) {
+        Ok(source) => {
+            data.script_console.source = source.clone();
+            data.script_console.path = Some(path.to_path_buf());
+            run_script(data, renderer, &source, path.parent());
+        }
+        Err(ex) => {
+            tracing::error!("{}: {}", path.display(), ex);
+            data.script_console
+                .output
+                .push(format!("ERROR -> {}: {}", path.display(), ex));
+        }
+    }
+}
+
+pub fn console_window(ctx: &egui::CtxRef, data: &mut RenderingData, renderer: &rend3::Renderer) {
+    let mut open = data.script_console.open;
+    let mut run = false;
+    let mut opened = None;
+    let mut on_start_changed = false;
+    let console = &mut data.script_console;
+    let startup_script = &mut data.settings.startup_script;
+    egui::Window::new("Script console")
+        .open(&mut open)
+        .default_width(450.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                run = ui.button("▶ Run").clicked();
+                if ui.button("Open…").clicked() {
+                    opened = file_dialog::open("Rhai script", &["rhai"]);
+                }
+                if let Some(path) = &console.path {
+                    if ui.button("Save").clicked() {
+                        if let Err(ex) = std::fs::write(path, &console.source) {
+                            console
+                                .output
+                                .push(format!("ERROR -> {}: {}", path.display(), ex));
+                        }
+                    }
+                }
+                if ui.button("Clear output").clicked() {
+                    console.output.clear();
+                }
+            });
+            if let Some(path) = &console.path {
+                ui.horizontal(|ui| {
+                    let mut on_start = startup_script.as_ref() == Some(path);
+                    if ui.checkbox(&mut on_start, "Run on start").changed() {
+                        *startup_script = on_start.then(|| path.clone());
+                        on_start_changed = true;
+                    }
+                    ui.label(path.display().to_string());
+                });
+            }
+            ui.add(
+                egui::TextEdit::multiline(&mut console.source)
+                    .code_editor()
+                    .desired_rows(12)
+                    .desired_width(f32::INFINITY),
+            );
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(150.)
+                .show(ui, |ui| {
+                    for line in &console.output {
+                        let text = egui::RichText::new(line).monospace();
+                        if line.starts_with("ERROR") {
+                            ui.colored_label(egui::Color32::from_rgb(255, 80, 80), text);
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                });
+        });
+    data.script_console.open &= open;
+    if on_start_changed {
+        settings::save(&data.settings);
+    }
+
+    if let Some(path) = opened {
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                data.script_console.source = source;
+                data.script_console.path = Some(path);
+            }
+            Err(ex) => {
+                data.script_console
+                    .output
+                    .push(format!("ERROR -> {}: {}", path.display(), ex))
+            }
+        }
+    }
+    if run {
+        let source = data.script_console.source.clone();
+        let base = data
+            .script_console
+            .path
+            .as_ref()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+        run_script(data, renderer, &source, base.as_deref());
+    }
+}
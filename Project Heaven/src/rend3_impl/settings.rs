@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::file_dialog;
+use super::scale_bar::Unit;
+
+const CONFIG_NAME: &str = "config.toml";
+// Where settings lived before config.toml, read once if there is no config yet
+const LEGACY_PATH: &str = "settings.json";
+// Per list in the File menu
+const MAX_RECENT: usize = 8;
+// What the UI scale slider goes between
+const MIN_UI_SCALE: f32 = 0.5;
+const MAX_UI_SCALE: f32 = 3.0;
+
+// The platform's config directory, or the working directory where there isn't one
+pub fn config_path(name: &str) -> PathBuf {
+    match directories::ProjectDirs::from("", "Jovian Entertainment Studios", "Project Heaven") {
+        Some(dirs) => dirs.config_dir().join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+// Creates the config directory on first use
+pub fn write_config(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(|ex| ex.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|ex| ex.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSensitivity {
+    // Radians per pixel of mouse movement
+    pub mouse_look: f32,
+    // Radians per second while the look keys are held
+    pub keyboard_look: f32,
+    // Radians per pixel dragged in orbit mode
+    pub orbit: f32,
+    // Fraction of the orbit distance per scroll wheel line
+    pub zoom: f32,
+    pub invert_y: bool,
+}
+
+impl Default for CameraSensitivity {
+    fn default() -> Self {
+        Self {
+            mouse_look: 1. / 2000.,
+            keyboard_look: 1.,
+            orbit: 0.005,
+            zoom: 0.1,
+            invert_y: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PresentMode {
+    // Waits for vblank, never tears
+    Vsync,
+    // Presents right away, can tear
+    Immediate,
+    // Newest frame at vblank without waiting, falls back to vsync where unsupported
+    Mailbox,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] = [
+        PresentMode::Vsync,
+        PresentMode::Immediate,
+        PresentMode::Mailbox,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentMode::Vsync => "Vsync",
+            PresentMode::Immediate => "Off",
+            PresentMode::Mailbox => "Mailbox",
+        }
+    }
+
+    pub fn wgpu(self) -> rend3::types::PresentMode {
+        match self {
+            PresentMode::Vsync => rend3::types::PresentMode::Fifo,
+            PresentMode::Immediate => rend3::types::PresentMode::Immediate,
+            PresentMode::Mailbox => rend3::types::PresentMode::Mailbox,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    // Borderless, restored at startup
+    pub fullscreen: bool,
+    pub present_mode: PresentMode,
+    // Fraction of the window resolution the scene is rendered at, egui stays sharp
+    pub render_scale: f32,
+    pub msaa: Msaa,
+    // Frames per second at most, unlimited when None
+    pub fps_limit: Option<u32>,
+    // Waits for input when the window is in the background or nothing has happened for a
+    // while, instead of drawing as fast as it can
+    pub idle_mode: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Msaa {
+    Off,
+    X4,
+}
+
+impl Msaa {
+    pub const ALL: [Msaa; 2] = [Msaa::Off, Msaa::X4];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Msaa::Off => "1x",
+            Msaa::X4 => "4x",
+        }
+    }
+
+    pub fn sample_count(self) -> rend3::types::SampleCount {
+        match self {
+            Msaa::Off => rend3::types::SampleCount::One,
+            Msaa::X4 => rend3::types::SampleCount::Four,
+        }
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            fullscreen: false,
+            present_mode: PresentMode::Mailbox,
+            render_scale: 1.,
+            msaa: Msaa::X4,
+            fps_limit: None,
+            idle_mode: true,
+        }
+    }
+}
+
+// Volumes from 0 to 1, music and effects are scaled by the master volume
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.6,
+            sfx: 1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    // The black and red look the app has always had
+    Heaven,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Heaven, Theme::Dark, Theme::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Heaven => "Project Heaven",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+
+    // `heaven` is the app's own style, built in setup
+    pub fn visuals(self, heaven: &egui::Visuals) -> egui::Visuals {
+        match self {
+            Theme::Heaven => heaven.clone(),
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+// Outer position and inner size in physical pixels, from the last time the app was closed
+// outside fullscreen
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Missing fields fall back to their defaults, so older files keep loading as settings are added.
+// Plain values have to come before the tables for TOML.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub unit: Unit,
+    pub theme: Theme,
+    // On top of the window's own scale factor
+    pub ui_scale: f32,
+    // TTF or OTF file the UI is drawn in, the built in font when None
+    pub font: Option<PathBuf>,
+    // Free camera fly speed, running is twice that
+    pub camera_speed: f32,
+    // Seconds a camera bookmark takes to fly to
+    pub fly_duration: f32,
+    // Run once everything is loaded at startup
+    pub startup_script: Option<PathBuf>,
+    // Most recent first
+    pub recent_models: Vec<PathBuf>,
+    pub recent_scenes: Vec<PathBuf>,
+    pub sensitivity: CameraSensitivity,
+    pub graphics: GraphicsSettings,
+    pub audio: AudioSettings,
+    pub window: Option<WindowGeometry>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            unit: Unit::default(),
+            theme: Theme::Heaven,
+            ui_scale: 1.,
+            font: None,
+            camera_speed: 10.,
+            fly_duration: 1.,
+            startup_script: None,
+            recent_models: Vec::new(),
+            recent_scenes: Vec::new(),
+            sensitivity: CameraSensitivity::default(),
+            graphics: GraphicsSettings::default(),
+            audio: AudioSettings::default(),
+            window: None,
+        }
+    }
+}
+
+// Moves `path` to the front of a recent files list
+pub fn remember(recent: &mut Vec<PathBuf>, path: &Path) {
+    recent.retain(|recent| recent != path);
+    recent.insert(0, path.to_path_buf());
+    recent.truncate(MAX_RECENT);
+}
+
+pub fn load() -> Settings {
+    let path = config_path(CONFIG_NAME);
+    let mut settings = match std::fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).unwrap_or_else(|ex| {
+            tracing::error!("{}: {}", path.display(), ex);
+            Settings::default()
+        }),
+        Err(_) => match std::fs::read_to_string(LEGACY_PATH) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|ex| {
+                tracing::error!("{}: {}", LEGACY_PATH, ex);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        },
+    };
+    // A hand edited file can ask for a scale egui can't lay out at
+    settings.ui_scale = if settings.ui_scale.is_finite() {
+        settings.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+    } else {
+        Settings::default().ui_scale
+    };
+    settings
+}
+
+pub fn save(settings: &Settings) {
+    // The browser build has no config directory, settings last as long as the page
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    let path = config_path(CONFIG_NAME);
+    let result = toml::to_string_pretty(settings)
+        .map_err(|ex| ex.to_string())
+        .and_then(|text| write_config(&path, &text));
+    if let Err(ex) = result {
+        tracing::error!("{}: {}", path.display(), ex);
+    }
+}
+
+// A slider changes its value on every frame it's dragged, so a change only counts once the
+// drag is let go. `sliders` is the union of their responses.
+pub fn settled(changed: bool, sliders: &egui::Response) -> bool {
+    (changed && !sliders.dragged()) || sliders.drag_released()
+}
+
+pub fn sensitivity_ui(ui: &mut egui::Ui, settings: &mut Settings) {
+    let previous = settings.sensitivity;
+    let sensitivity = &mut settings.sensitivity;
+
+    let mut sliders = ui.add(
+        egui::Slider::new(&mut sensitivity.mouse_look, 0.0001..=0.005)
+            .logarithmic(true)
+            .text("Mouse look"),
+    );
+    sliders |=
+        ui.add(egui::Slider::new(&mut sensitivity.keyboard_look, 0.1..=5.0).text("Keyboard look"));
+    sliders |= ui.add(
+        egui::Slider::new(&mut sensitivity.orbit, 0.001..=0.02)
+            .logarithmic(true)
+            .text("Orbit"),
+    );
+    sliders |= ui.add(egui::Slider::new(&mut sensitivity.zoom, 0.01..=0.5).text("Zoom"));
+    ui.checkbox(&mut sensitivity.invert_y, "Invert Y");
+    if ui.button("Reset to defaults").clicked() {
+        *sensitivity = CameraSensitivity::default();
+    }
+
+    if settled(settings.sensitivity != previous, &sliders) {
+        save(settings);
+    }
+}
+
+// Fullscreen is toggled by the caller, which owns the window. Returns true when the
+// present mode changed, since the surface has to be configured again for it.
+pub fn graphics_ui(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let previous = settings.graphics;
+    let graphics = &mut settings.graphics;
+
+    egui::ComboBox::from_label("Present mode")
+        .selected_text(graphics.present_mode.name())
+        .show_ui(ui, |ui| {
+            for mode in PresentMode::ALL {
+                ui.selectable_value(&mut graphics.present_mode, mode, mode.name());
+            }
+        });
+    let mut sliders =
+        ui.add(egui::Slider::new(&mut graphics.render_scale, 0.25..=2.0).text("Render scale"));
+    egui::ComboBox::from_label("MSAA")
+        .selected_text(graphics.msaa.name())
+        .show_ui(ui, |ui| {
+            for msaa in Msaa::ALL {
+                ui.selectable_value(&mut graphics.msaa, msaa, msaa.name());
+            }
+        });
+    let fps_slider = ui.horizontal(|ui| {
+        let mut limited = graphics.fps_limit.is_some();
+        if ui.checkbox(&mut limited, "Limit FPS").changed() {
+            graphics.fps_limit = limited.then(|| 60);
+        }
+        graphics
+            .fps_limit
+            .as_mut()
+            .map(|fps| ui.add(egui::Slider::new(fps, 10..=240).suffix(" fps")))
+    });
+    if let Some(fps_slider) = fps_slider.inner {
+        sliders |= fps_slider;
+    }
+    ui.checkbox(&mut graphics.idle_mode, "Sleep when unfocused or idle");
+
+    if settled(settings.graphics != previous, &sliders) {
+        save(settings);
+    }
+    settings.graphics.present_mode != previous.present_mode
+}
+
+pub fn audio_ui(ui: &mut egui::Ui, settings: &mut Settings) {
+    let previous = settings.audio;
+    let audio = &mut settings.audio;
+    let mut sliders = ui.add(egui::Slider::new(&mut audio.master, 0.0..=1.0).text("Master"));
+    sliders |= ui.add(egui::Slider::new(&mut audio.music, 0.0..=1.0).text("Music"));
+    sliders |= ui.add(egui::Slider::new(&mut audio.sfx, 0.0..=1.0).text("Effects"));
+    if settled(settings.audio != previous, &sliders) {
+        save(settings);
+    }
+}
+
+// Returns true when the theme changed, the caller hands the new visuals to egui
+pub fn theme_ui(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let previous = settings.theme;
+    egui::ComboBox::from_label("Theme")
+        .selected_text(settings.theme.name())
+        .show_ui(ui, |ui| {
+            for theme in Theme::ALL {
+                ui.selectable_value(&mut settings.theme, theme, theme.name());
+            }
+        });
+    if settings.theme != previous {
+        save(settings);
+        return true;
+    }
+    false
+}
+
+// Returns true when the scale should be applied. Not while the slider is dragged, it would
+// move out from under the cursor.
+pub fn ui_scale_ui(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let response = ui.add(
+        egui::Slider::new(&mut settings.ui_scale, MIN_UI_SCALE..=MAX_UI_SCALE)
+            .text("UI scale")
+            .suffix("×"),
+    );
+    let apply = settled(response.changed(), &response);
+    if apply {
+        save(settings);
+    }
+    apply
+}
+
+// Returns true when the font changed, the caller hands egui the new font definitions
+pub fn font_ui(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let previous = settings.font.clone();
+    ui.horizontal(|ui| {
+        ui.label("Font");
+        match &settings.font {
+            Some(path) => ui.label(path.display().to_string()),
+            None => ui.label("Built in"),
+        };
+        if ui.button("Open…").clicked() {
+            if let Some(path) = file_dialog::open("Font", &["ttf", "otf"]) {
+                settings.font = Some(path);
+            }
+        }
+        if settings.font.is_some() && ui.button("Built in").clicked() {
+            settings.font = None;
+        }
+    });
+    if settings.font != previous {
+        save(settings);
+        return true;
+    }
+    false
+}
@@ -0,0 +1,129 @@
+use glam::Vec3;
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+// Cube faces in the order rend3 expects them
+const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+const EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+pub const DEFAULT_SKYBOX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data/skybox");
+
+// Loads px/nx/py/ny/pz/nz images from a folder into a cubemap
+pub fn load_cubemap(
+    renderer: &rend3::Renderer,
+    folder: &Path,
+) -> Result<rend3::types::TextureHandle, String> {
+    let mut data = Vec::new();
+    let mut size = None;
+    for face in FACES {
+        let path = EXTENSIONS
+            .iter()
+            .map(|extension| folder.join(face).with_extension(extension))
+            .find(|path| path.exists())
+            .ok_or_else(|| format!("{}: no {} image", folder.display(), face))?;
+        let image = image::open(&path)
+            .map_err(|ex| format!("{}: {}", path.display(), ex))?
+            .to_rgba8();
+
+        let dimensions = image.dimensions();
+        if dimensions.0 != dimensions.1 || size.map_or(false, |size| size != dimensions) {
+            return Err(format!(
+                "{}: faces have to be square and all the same size",
+                path.display()
+            ));
+        }
+        size = Some(dimensions);
+        data.extend_from_slice(&image);
+    }
+    let (width, height) = size.unwrap();
+
+    Ok(renderer.add_texture_cube(rend3::types::Texture {
+        label: Some(folder.display().to_string()),
+        data,
+        format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+        size: glam::UVec2::new(width, height),
+        mip_count: rend3::types::MipmapCount::ONE,
+        mip_source: rend3::types::MipmapSource::Uploaded,
+    }))
+}
+
+// Decoded HDR panorama, kept so a new exposure can be baked without reading the file again
+pub struct Equirectangular {
+    pub image: image::Rgb32FImage,
+}
+
+impl Equirectangular {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|ex| format!("{}: {}", path.display(), ex))?
+            .to_rgb32f();
+        if (image.width() as i64 - 2 * image.height() as i64).abs() > 1 {
+            return Err(format!(
+                "{}: an equirectangular panorama is twice as wide as it is high",
+                path.display()
+            ));
+        }
+        Ok(Self { image })
+    }
+
+    // Bilinear, wrapping around horizontally
+    fn sample(&self, direction: Vec3) -> Vec3 {
+        let (width, height) = (self.image.width(), self.image.height());
+        let u = (0.5 + direction.x.atan2(-direction.z) / TAU) * width as f32 - 0.5;
+        let v = (direction.y.clamp(-1., 1.).acos() / PI) * height as f32 - 0.5;
+
+        let (x0, y0) = (u.floor(), v.floor());
+        let (fx, fy) = (u - x0, v - y0);
+        let pixel = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(width as i64) as u32;
+            let y = (y as i64).clamp(0, height as i64 - 1) as u32;
+            Vec3::from(self.image.get_pixel(x, y).0)
+        };
+        let top = pixel(x0, y0).lerp(pixel(x0 + 1., y0), fx);
+        let bottom = pixel(x0, y0 + 1.).lerp(pixel(x0 + 1., y0 + 1.), fx);
+        top.lerp(bottom, fy)
+    }
+
+    // Bakes the panorama into a half float cubemap, scaled by 2^exposure. The tonemapper
+    // has no exposure input, so it is applied to the sky itself.
+    pub fn to_cubemap(
+        &self,
+        renderer: &rend3::Renderer,
+        exposure: f32,
+    ) -> rend3::types::TextureHandle {
+        let size = (self.image.width() / 4).clamp(64, 1024);
+        let scale = 2f32.powf(exposure);
+
+        let mut data = Vec::with_capacity((size * size * 6 * 8) as usize);
+        for face in 0..FACES.len() {
+            for y in 0..size {
+                for x in 0..size {
+                    // Texel centre, -1 to 1 across the face with v going down
+                    let u = (x as f32 + 0.5) / size as f32 * 2. - 1.;
+                    let v = (y as f32 + 0.5) / size as f32 * 2. - 1.;
+                    let direction = match face {
+                        0 => Vec3::new(1., -v, -u),
+                        1 => Vec3::new(-1., -v, u),
+                        2 => Vec3::new(u, 1., v),
+                        3 => Vec3::new(u, -1., -v),
+                        4 => Vec3::new(u, -v, 1.),
+                        _ => Vec3::new(-u, -v, -1.),
+                    };
+                    let color = self.sample(direction.normalize()) * scale;
+                    for channel in [color.x, color.y, color.z, 1.] {
+                        data.extend_from_slice(&half::f16::from_f32(channel).to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        renderer.add_texture_cube(rend3::types::Texture {
+            label: Some("equirectangular skybox".to_string()),
+            data,
+            format: rend3::types::TextureFormat::Rgba16Float,
+            size: glam::UVec2::new(size, size),
+            mip_count: rend3::types::MipmapCount::ONE,
+            mip_source: rend3::types::MipmapSource::Uploaded,
+        })
+    }
+}
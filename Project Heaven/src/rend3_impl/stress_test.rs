@@ -0,0 +1,78 @@
+use glam::Vec3;
+use instant::Instant;
+use std::time::Duration;
+
+use super::frame_stats::FrameStats;
+use super::mesh_generator::Primitive;
+use super::scene::Scene;
+
+// Instances along each side are capped so the cube of them stays around thirty thousand
+const MAX_COUNT: u32 = 32;
+
+pub struct StressTest {
+    pub primitive: Primitive,
+    // Instances along each side
+    pub count: u32,
+    pub spacing: f32,
+    // How long handing every object of the last grid to the renderer took
+    pub spawn_time: Option<Duration>,
+}
+
+impl Default for StressTest {
+    fn default() -> Self {
+        Self {
+            primitive: Primitive::Cube,
+            count: 10,
+            spacing: 3.,
+            spawn_time: None,
+        }
+    }
+}
+
+pub fn stress_test_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    stress_test: &mut StressTest,
+    scene: &mut Scene,
+    frame_stats: &FrameStats,
+) {
+    egui::ComboBox::from_label("Instance")
+        .selected_text(stress_test.primitive.name())
+        .show_ui(ui, |ui| {
+            for kind in Primitive::ALL {
+                ui.selectable_value(&mut stress_test.primitive, kind, kind.name());
+            }
+        });
+    ui.add(egui::Slider::new(&mut stress_test.count, 1..=MAX_COUNT).text("Per side"));
+    ui.add(egui::Slider::new(&mut stress_test.spacing, 1.0..=20.0).text("Spacing"));
+    ui.label(format!("{} instances", stress_test.count.pow(3)));
+
+    ui.horizontal(|ui| {
+        if ui.button("Spawn grid").clicked() {
+            let start = Instant::now();
+            scene.spawn_grid(
+                renderer,
+                stress_test.primitive,
+                stress_test.count,
+                stress_test.spacing,
+                Vec3::ZERO,
+            );
+            stress_test.spawn_time = Some(start.elapsed());
+        }
+        // Dropping the handles removes every instance from the renderer
+        if !scene.instances.is_empty() && ui.button("Clear").clicked() {
+            scene.instances.clear();
+            stress_test.spawn_time = None;
+        }
+    });
+
+    if let (false, Some(spawn_time)) = (scene.instances.is_empty(), stress_test.spawn_time) {
+        ui.label(format!(
+            "{} objects, {} triangles, spawned in {:.1} ms",
+            scene.instances.len(),
+            scene.instance_triangles(),
+            spawn_time.as_secs_f32() * 1000.
+        ));
+        ui.label(format!("{:.2} ms per frame", frame_stats.average()));
+    }
+}
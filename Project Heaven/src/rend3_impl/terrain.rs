@@ -0,0 +1,354 @@
+use glam::{Mat4, Vec2, Vec3};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rend3::types::MaterialHandle;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::file_dialog;
+use super::mesh_generator::MeshData;
+use super::scene::{clay_material, PbrFactors, SceneObject};
+
+// Grassy green
+const COLOR: [f32; 4] = [0.45, 0.5, 0.35, 1.];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainParams {
+    pub seed: u64,
+    // Height of the tallest hills, in world units
+    pub amplitude: f32,
+    // Noise features per world unit
+    pub frequency: f32,
+    pub octaves: u32,
+    // World units along each side of the whole terrain
+    pub size: f32,
+    pub chunks: u32,
+    // Quads along each side of a chunk
+    pub chunk_resolution: u32,
+    // Height of the terrain's zero level, so it can sit under the scene
+    pub elevation: f32,
+}
+
+impl Default for TerrainParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            amplitude: 20.,
+            frequency: 0.01,
+            octaves: 5,
+            size: 400.,
+            chunks: 4,
+            chunk_resolution: 64,
+            elevation: -30.,
+        }
+    }
+}
+
+// Classic 2D gradient noise over a shuffled permutation table
+struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    fn new(seed: u64) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = table[i % 256];
+        }
+        Self { permutation }
+    }
+
+    fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+        match hash & 7 {
+            0 => x + y,
+            1 => -x + y,
+            2 => x - y,
+            3 => -x - y,
+            4 => x,
+            5 => -x,
+            6 => y,
+            _ => -y,
+        }
+    }
+
+    // Roughly -1 to 1, zero on every integer point
+    fn noise(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (xi, yi) = ((x0 as i32 & 255) as usize, (y0 as i32 & 255) as usize);
+        let (x, y) = (x - x0, y - y0);
+
+        let fade = |t: f32| t * t * t * (t * (t * 6. - 15.) + 10.);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let (u, v) = (fade(x), fade(y));
+
+        let p = &self.permutation;
+        let (a, b) = (p[xi] as usize + yi, p[xi + 1] as usize + yi);
+        lerp(
+            lerp(
+                Self::gradient(p[a], x, y),
+                Self::gradient(p[b], x - 1., y),
+                u,
+            ),
+            lerp(
+                Self::gradient(p[a + 1], x, y - 1.),
+                Self::gradient(p[b + 1], x - 1., y - 1.),
+                u,
+            ),
+            v,
+        )
+    }
+
+    // Octaves of halving strength and doubling frequency, scaled back to about -1 to 1
+    fn fractal(&self, x: f32, y: f32, octaves: u32) -> f32 {
+        let (mut sum, mut total, mut strength, mut scale) = (0., 0., 1., 1.);
+        for _ in 0..octaves.max(1) {
+            sum += self.noise(x * scale, y * scale) * strength;
+            total += strength;
+            strength *= 0.5;
+            scale *= 2.;
+        }
+        sum / total
+    }
+}
+
+enum HeightSource {
+    Noise(Perlin),
+    // Stretched over the whole terrain, black at zero and white at the amplitude
+    Heightmap(image::ImageBuffer<image::Luma<u16>, Vec<u16>>),
+}
+
+impl HeightSource {
+    fn height(&self, params: &TerrainParams, x: f32, z: f32) -> f32 {
+        match self {
+            HeightSource::Noise(perlin) => {
+                perlin.fractal(x * params.frequency, z * params.frequency, params.octaves)
+                    * params.amplitude
+            }
+            HeightSource::Heightmap(image) => {
+                let (width, height) = image.dimensions();
+                let u = (x / params.size + 0.5).clamp(0., 1.) * (width - 1) as f32;
+                let v = (z / params.size + 0.5).clamp(0., 1.) * (height - 1) as f32;
+                let (x0, y0) = (u.floor() as u32, v.floor() as u32);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let (fx, fy) = (u.fract(), v.fract());
+                let pixel = |x, y| image.get_pixel(x, y).0[0] as f32 / u16::MAX as f32;
+                let top = pixel(x0, y0) * (1. - fx) + pixel(x1, y0) * fx;
+                let bottom = pixel(x0, y1) * (1. - fx) + pixel(x1, y1) * fx;
+                (top * (1. - fy) + bottom * fy) * params.amplitude
+            }
+        }
+    }
+}
+
+// One chunk of the grid, in terrain space. Heights and normals come straight from the
+// height function, so neighbouring chunks meet without seams.
+fn create_chunk(
+    params: &TerrainParams,
+    source: &HeightSource,
+    chunk_x: u32,
+    chunk_z: u32,
+) -> MeshData {
+    let resolution = params.chunk_resolution.max(1);
+    let chunk_size = params.size / params.chunks as f32;
+    let step = chunk_size / resolution as f32;
+    let origin = Vec2::new(
+        chunk_x as f32 * chunk_size - params.size / 2.,
+        chunk_z as f32 * chunk_size - params.size / 2.,
+    );
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for j in 0..=resolution {
+        for i in 0..=resolution {
+            let (x, z) = (origin.x + i as f32 * step, origin.y + j as f32 * step);
+            positions.push(Vec3::new(x, source.height(params, x, z), z));
+            let dx = source.height(params, x - step, z) - source.height(params, x + step, z);
+            let dz = source.height(params, x, z - step) - source.height(params, x, z + step);
+            normals.push(Vec3::new(dx, 2. * step, dz).normalize());
+            uvs.push(Vec2::new(x / params.size + 0.5, z / params.size + 0.5));
+        }
+    }
+
+    // Same winding as the plane, counter-clockwise seen from above
+    let mut indices = Vec::new();
+    let stride = resolution + 1;
+    for j in 0..resolution {
+        for i in 0..resolution {
+            let a = j * stride + i;
+            let b = a + 1;
+            let c = b + stride;
+            let d = a + stride;
+            indices.extend([a, c, b, a, d, c]);
+        }
+    }
+
+    MeshData {
+        positions,
+        normals,
+        uvs,
+        indices,
+        ..MeshData::default()
+    }
+}
+
+pub struct Terrain {
+    pub params: TerrainParams,
+    pub heightmap: Option<PathBuf>,
+    pub error: Option<String>,
+    source: HeightSource,
+    material: rend3::types::MaterialHandle,
+    pub chunks: Vec<SceneObject>,
+    // What the chunks were last made from
+    generated: TerrainParams,
+}
+
+impl Terrain {
+    pub fn new(
+        renderer: &rend3::Renderer,
+        params: TerrainParams,
+        clay: Option<&MaterialHandle>,
+        wireframe: bool,
+    ) -> Self {
+        let mut terrain = Self {
+            source: HeightSource::Noise(Perlin::new(params.seed)),
+            generated: params.clone(),
+            params,
+            heightmap: None,
+            error: None,
+            material: renderer.add_material(clay_material(COLOR)),
+            chunks: Vec::new(),
+        };
+        terrain.regenerate(renderer, clay, wireframe);
+        terrain
+    }
+
+    // Dropping the old chunks removes them from the renderer. The new ones are drawn the way
+    // the clay and wireframe checkboxes say, like every other object.
+    pub fn regenerate(
+        &mut self,
+        renderer: &rend3::Renderer,
+        clay: Option<&MaterialHandle>,
+        wireframe: bool,
+    ) {
+        self.generated = self.params.clone();
+        let transform = Mat4::from_translation(Vec3::new(0., self.params.elevation, 0.));
+        let chunks = self.params.chunks.max(1);
+        self.chunks = (0..chunks * chunks)
+            .map(|index| {
+                let mesh_data =
+                    create_chunk(&self.params, &self.source, index % chunks, index / chunks);
+                let mut chunk = SceneObject::new(
+                    renderer,
+                    renderer.add_mesh(mesh_data.build()),
+                    self.material.clone(),
+                    transform,
+                );
+                chunk.name = format!("Terrain {}", index);
+                chunk.factors = PbrFactors {
+                    base_color: COLOR,
+                    ..PbrFactors::default()
+                };
+                chunk.mesh_data = Some(mesh_data);
+                if clay.is_some() {
+                    chunk.refresh(renderer, clay);
+                }
+                chunk.set_wireframe(renderer, wireframe);
+                chunk
+            })
+            .collect();
+    }
+
+    pub fn load_heightmap(&mut self, path: &Path) -> Result<(), String> {
+        let image = image::open(path)
+            .map_err(|ex| format!("{}: {}", path.display(), ex))?
+            .to_luma16();
+        if image.width() < 2 || image.height() < 2 {
+            return Err(format!("{}: heightmap is too small", path.display()));
+        }
+        self.source = HeightSource::Heightmap(image);
+        self.heightmap = Some(path.to_path_buf());
+        Ok(())
+    }
+}
+
+// The chunks are only made again once a slider is let go, a big terrain takes a while
+pub fn terrain_ui(
+    ui: &mut egui::Ui,
+    renderer: &rend3::Renderer,
+    terrain: &mut Option<Terrain>,
+    clay: Option<&MaterialHandle>,
+    wireframe: bool,
+) {
+    let mut enabled = terrain.is_some();
+    if ui.checkbox(&mut enabled, "Terrain").changed() {
+        *terrain =
+            enabled.then(|| Terrain::new(renderer, TerrainParams::default(), clay, wireframe));
+    }
+    let terrain = match terrain {
+        Some(terrain) => terrain,
+        None => return,
+    };
+
+    let previous_seed = terrain.params.seed;
+    let params = &mut terrain.params;
+    let mut responses = Vec::new();
+    if terrain.heightmap.is_none() {
+        responses.push(ui.add(egui::DragValue::new(&mut params.seed).prefix("Seed ")));
+        responses.push(
+            ui.add(
+                egui::Slider::new(&mut params.frequency, 0.001..=0.1)
+                    .logarithmic(true)
+                    .text("Frequency"),
+            ),
+        );
+        responses.push(ui.add(egui::Slider::new(&mut params.octaves, 1..=8).text("Octaves")));
+    }
+    responses.push(ui.add(egui::Slider::new(&mut params.amplitude, 0.0..=200.0).text("Amplitude")));
+    responses.push(
+        ui.add(
+            egui::Slider::new(&mut params.size, 10.0..=5000.0)
+                .logarithmic(true)
+                .text("Size"),
+        ),
+    );
+    responses.push(ui.add(egui::Slider::new(&mut params.chunks, 1..=8).text("Chunks")));
+    responses.push(
+        ui.add(egui::Slider::new(&mut params.chunk_resolution, 4..=128).text("Chunk resolution")),
+    );
+    responses
+        .push(ui.add(egui::Slider::new(&mut params.elevation, -500.0..=500.0).text("Elevation")));
+    let dragging = responses.iter().any(egui::Response::dragged);
+
+    // Only a table, the terrain is made from it once the drag is over
+    if terrain.params.seed != previous_seed {
+        terrain.source = HeightSource::Noise(Perlin::new(terrain.params.seed));
+    }
+    let mut source_changed = false;
+    ui.horizontal(|ui| {
+        if ui.button("Load heightmap…").clicked() {
+            if let Some(path) = file_dialog::open("Image", &["png"]) {
+                terrain.error = terrain.load_heightmap(&path).err();
+                source_changed |= terrain.error.is_none();
+            }
+        }
+        if terrain.heightmap.is_some() && ui.button("Use noise").clicked() {
+            terrain.heightmap = None;
+            terrain.source = HeightSource::Noise(Perlin::new(terrain.params.seed));
+            source_changed = true;
+        }
+    });
+    if let Some(path) = &terrain.heightmap {
+        ui.label(path.display().to_string());
+    }
+    if let Some(error) = &terrain.error {
+        ui.colored_label(egui::Color32::from_rgb(255, 0, 0), error);
+    }
+
+    if source_changed || (terrain.params != terrain.generated && !dragging) {
+        terrain.regenerate(renderer, clay, wireframe);
+    }
+}
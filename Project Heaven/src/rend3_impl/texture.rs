@@ -0,0 +1,21 @@
+use std::path::Path;
+
+// Colour texture with its mip chain generated by rend3
+pub fn load_texture_2d(
+    renderer: &rend3::Renderer,
+    path: &Path,
+) -> Result<rend3::types::TextureHandle, String> {
+    let image = image::open(path)
+        .map_err(|ex| format!("{}: {}", path.display(), ex))?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+
+    Ok(renderer.add_texture_2d(rend3::types::Texture {
+        label: Some(path.display().to_string()),
+        data: image.into_raw(),
+        format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+        size: glam::UVec2::new(width, height),
+        mip_count: rend3::types::MipmapCount::Maximum,
+        mip_source: rend3::types::MipmapSource::Generated,
+    }))
+}
@@ -0,0 +1,424 @@
+use glam::UVec2;
+use rend3::graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use super::post_fx::{self, PostFxSettings};
+
+// The scene is rendered into this first, so nothing above one is lost before tonemapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Matches Params in the shaders, padded to a multiple of 16 bytes for uniform buffers
+const PARAMS_SIZE: u64 = 32;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operator {
+    // Cuts off at one, what rend3's own blit did
+    Clamp,
+    Reinhard,
+    Aces,
+    Filmic,
+}
+
+impl Operator {
+    pub const ALL: [Operator; 4] = [
+        Operator::Clamp,
+        Operator::Reinhard,
+        Operator::Aces,
+        Operator::Filmic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operator::Clamp => "Clamp",
+            Operator::Reinhard => "Reinhard",
+            Operator::Aces => "ACES",
+            Operator::Filmic => "Filmic",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct TonemappingSettings {
+    pub operator: Operator,
+    // Stops, each one doubles the brightness
+    pub exposure: f32,
+    pub gamma: f32,
+    // Scales the frame so its average luminance lands on `key`
+    pub auto_exposure: bool,
+    pub key: f32,
+    // How quickly auto exposure follows a change in brightness, per second
+    pub adaptation_speed: f32,
+}
+
+impl Default for TonemappingSettings {
+    fn default() -> Self {
+        Self {
+            operator: Operator::Clamp,
+            exposure: 0.,
+            gamma: 1.,
+            auto_exposure: false,
+            key: 0.18,
+            adaptation_speed: 2.,
+        }
+    }
+}
+
+impl TonemappingSettings {
+    fn params(&self, delta_time: f32) -> [u8; PARAMS_SIZE as usize] {
+        let operator = match self.operator {
+            Operator::Clamp => 0u32,
+            Operator::Reinhard => 1,
+            Operator::Aces => 2,
+            Operator::Filmic => 3,
+        };
+        let values = [
+            operator.to_ne_bytes(),
+            2f32.powf(self.exposure).to_ne_bytes(),
+            (1. / self.gamma.max(0.01)).to_ne_bytes(),
+            (self.auto_exposure as u32).to_ne_bytes(),
+            self.key.to_ne_bytes(),
+            (1. - (-delta_time * self.adaptation_speed).exp()).to_ne_bytes(),
+        ];
+        let mut bytes = [0; PARAMS_SIZE as usize];
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value);
+        }
+        bytes
+    }
+}
+
+// The HDR texture and the bind groups that read it, remade when the output is resized
+struct HdrTarget {
+    size: UVec2,
+    view: Arc<wgpu::TextureView>,
+    tonemap_bind_group: wgpu::BindGroup,
+    exposure_bind_group: wgpu::BindGroup,
+    _texture: wgpu::Texture,
+}
+
+// Replaces rend3's tonemapping. The base rendergraph draws into an HDR texture with a
+// straight copy, and the tonemapping pass takes it from there to the output.
+pub struct Tonemapper {
+    // rend3's blit, built for the HDR format it only copies
+    pub copy: rend3_routine::tonemapping::TonemappingRoutine,
+    params: wgpu::Buffer,
+    // Adapted scene luminance, only ever touched on the GPU
+    exposure: wgpu::Buffer,
+    tonemap_layout: wgpu::BindGroupLayout,
+    exposure_layout: wgpu::BindGroupLayout,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    exposure_pipeline: wgpu::ComputePipeline,
+    target: Option<HdrTarget>,
+    auto_exposure: bool,
+}
+
+fn bind_group_layout(
+    device: &wgpu::Device,
+    label: &str,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+impl Tonemapper {
+    // `output_format` is what the surface, viewport and screenshot targets use
+    pub fn new(
+        renderer: &rend3::Renderer,
+        interfaces: &rend3_routine::common::WholeFrameInterfaces,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        let device = &renderer.device;
+        let copy =
+            rend3_routine::tonemapping::TonemappingRoutine::new(renderer, interfaces, HDR_FORMAT);
+
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tonemapping params"),
+            size: PARAMS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let exposure = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("exposure"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let tonemap_layout =
+            bind_group_layout(device, "tonemapping", wgpu::ShaderStages::FRAGMENT, true);
+        let exposure_layout =
+            bind_group_layout(device, "exposure", wgpu::ShaderStages::COMPUTE, false);
+
+        let tonemap_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("tonemapping"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../data/shaders/tonemapping.wgsl"
+            ))),
+        });
+        let tonemap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tonemapping"),
+                bind_group_layouts: &[&tonemap_layout],
+                push_constant_ranges: &[],
+            });
+        let tonemap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tonemapping"),
+            layout: Some(&tonemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &tonemap_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &tonemap_shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+
+        let exposure_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("exposure"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../data/shaders/exposure.wgsl"
+            ))),
+        });
+        let exposure_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("exposure"),
+                bind_group_layouts: &[&exposure_layout],
+                push_constant_ranges: &[],
+            });
+        let exposure_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("exposure"),
+            layout: Some(&exposure_pipeline_layout),
+            module: &exposure_shader,
+            entry_point: "main",
+        });
+
+        Self {
+            copy,
+            params,
+            exposure,
+            tonemap_layout,
+            exposure_layout,
+            tonemap_pipeline,
+            exposure_pipeline,
+            target: None,
+            auto_exposure: false,
+        }
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.exposure.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // Sizes the HDR texture to the output and uploads this frame's settings. The returned
+    // view is what the base rendergraph renders into.
+    pub fn prepare(
+        &mut self,
+        renderer: &rend3::Renderer,
+        size: UVec2,
+        settings: &TonemappingSettings,
+        delta_time: f32,
+    ) -> Arc<wgpu::TextureView> {
+        if self.target.as_ref().map(|target| target.size) != Some(size) {
+            let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("hdr scene"),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.target = Some(HdrTarget {
+                size,
+                tonemap_bind_group: self.bind_group(&renderer.device, &self.tonemap_layout, &view),
+                exposure_bind_group: self.bind_group(
+                    &renderer.device,
+                    &self.exposure_layout,
+                    &view,
+                ),
+                view: Arc::new(view),
+                _texture: texture,
+            });
+        }
+
+        renderer
+            .queue
+            .write_buffer(&self.params, 0, &settings.params(delta_time));
+        self.auto_exposure = settings.auto_exposure;
+        Arc::clone(&self.target.as_ref().unwrap().view)
+    }
+
+    // Measures the HDR frame once it has been rendered, before it is tonemapped
+    pub fn update_exposure(&self, renderer: &rend3::Renderer) {
+        let target = match (&self.target, self.auto_exposure) {
+            (Some(target), true) => target,
+            _ => return,
+        };
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("exposure"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("exposure"),
+            });
+            pass.set_pipeline(&self.exposure_pipeline);
+            pass.set_bind_group(0, &target.exposure_bind_group, &[]);
+            pass.dispatch(1, 1, 1);
+        }
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Tonemaps the HDR texture to `output`. Has to come after prepare().
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        output: RenderTargetHandle,
+    ) {
+        if self.target.is_none() {
+            return;
+        }
+        let mut builder = graph.add_node("tonemapping");
+
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: wgpu::Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(
+            move |pt, _renderer, encoder_or_pass, _temps, _ready, _graph_data| {
+                let this = pt.get(pt_handle);
+                let rpass = encoder_or_pass.get_rpass(rpass_handle);
+                let target = this.target.as_ref().unwrap();
+
+                rpass.set_pipeline(&this.tonemap_pipeline);
+                rpass.set_bind_group(0, &target.tonemap_bind_group, &[]);
+                rpass.draw(0..3, 0..1);
+            },
+        );
+    }
+}
+
+pub fn post_processing_window(
+    ctx: &egui::CtxRef,
+    settings: &mut TonemappingSettings,
+    post_fx: &mut PostFxSettings,
+    open: &mut bool,
+) {
+    egui::Window::new("Post Processing")
+        .open(open)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Tonemapping")
+                .selected_text(settings.operator.name())
+                .show_ui(ui, |ui| {
+                    for operator in Operator::ALL {
+                        ui.selectable_value(&mut settings.operator, operator, operator.name());
+                    }
+                });
+            ui.add(egui::Slider::new(&mut settings.exposure, -8.0..=8.0).text("Exposure (stops)"));
+            ui.add(egui::Slider::new(&mut settings.gamma, 0.5..=2.5).text("Gamma"));
+            ui.checkbox(&mut settings.auto_exposure, "Automatic exposure");
+            if settings.auto_exposure {
+                ui.add(
+                    egui::Slider::new(&mut settings.key, 0.02..=1.0)
+                        .logarithmic(true)
+                        .text("Target luminance"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.adaptation_speed, 0.1..=10.0)
+                        .logarithmic(true)
+                        .text("Adaptation speed"),
+                );
+            }
+            ui.separator();
+            post_fx::post_fx_ui(ui, post_fx);
+            if ui.button("Reset to defaults").clicked() {
+                *settings = TonemappingSettings::default();
+                *post_fx = PostFxSettings::default();
+            }
+        });
+}
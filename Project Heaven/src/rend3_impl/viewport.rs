@@ -0,0 +1,112 @@
+use glam::UVec2;
+use std::sync::Arc;
+
+// Offscreen texture the scene renders into while it is shown inside an egui window
+pub struct ViewportTarget {
+    pub size: UVec2,
+    pub view: Arc<wgpu::TextureView>,
+    _texture: wgpu::Texture,
+}
+
+#[derive(Default)]
+pub struct ViewportWidget {
+    pub enabled: bool,
+    // Physical pixel size the window had room for last frame
+    pub requested_size: UVec2,
+    pub target: Option<ViewportTarget>,
+    texture_id: Option<egui::TextureId>,
+}
+
+impl ViewportWidget {
+    // Resizes the target to whatever the window asked for, after the UI has run and
+    // before the scene is rendered
+    pub fn prepare(
+        &mut self,
+        renderer: &rend3::Renderer,
+        egui_routine: &mut rend3_egui::EguiRenderRoutine,
+        format: wgpu::TextureFormat,
+    ) -> Option<&ViewportTarget> {
+        if !self.enabled || self.requested_size.x == 0 || self.requested_size.y == 0 {
+            return None;
+        }
+
+        if self.target.as_ref().map(|target| target.size) != Some(self.requested_size) {
+            let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("viewport widget"),
+                size: wgpu::Extent3d {
+                    width: self.requested_size.x,
+                    height: self.requested_size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                // The tonemapping routine was built for the surface format, so the target matches it
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            });
+            let view = Arc::new(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+            // Reuse the egui id so resizing doesn't leak a texture slot every time
+            self.texture_id = Some(match self.texture_id {
+                Some(id) => {
+                    egui_routine
+                        .internal
+                        .update_egui_texture_from_wgpu_texture(
+                            &renderer.device,
+                            &texture,
+                            wgpu::FilterMode::Linear,
+                            id,
+                        )
+                        .unwrap();
+                    id
+                }
+                None => egui_routine.internal.egui_texture_from_wgpu_texture(
+                    &renderer.device,
+                    &texture,
+                    wgpu::FilterMode::Linear,
+                ),
+            });
+
+            self.target = Some(ViewportTarget {
+                size: self.requested_size,
+                view,
+                _texture: texture,
+            });
+        }
+
+        self.target.as_ref()
+    }
+}
+
+// Returns how far the mouse was dragged over the image this frame, in points
+pub fn viewport_ui(ctx: &egui::CtxRef, widget: &mut ViewportWidget) -> Option<egui::Vec2> {
+    if !widget.enabled {
+        return None;
+    }
+
+    let mut drag = None;
+    egui::Window::new("Viewport")
+        .resizable(true)
+        .default_size([640., 360.])
+        .show(ctx, |ui| {
+            let size = ui.available_size();
+            let pixels = size * ctx.pixels_per_point();
+            widget.requested_size = UVec2::new(pixels.x as u32, pixels.y as u32);
+
+            match (&widget.target, widget.texture_id) {
+                (Some(_), Some(texture_id)) => {
+                    let response =
+                        ui.add(egui::Image::new(texture_id, size).sense(egui::Sense::drag()));
+                    if response.dragged() {
+                        drag = Some(response.drag_delta());
+                    }
+                }
+                _ => {
+                    ui.allocate_space(size);
+                }
+            }
+        });
+    drag
+}
@@ -0,0 +1,58 @@
+// Stand-ins for what the browser build can't do the native way
+use std::path::{Path, PathBuf};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use super::asset_loader::LoadTarget;
+use super::mesh_importer::GltfLoadError;
+
+fn fetch_error(path: &Path, what: String) -> GltfLoadError {
+    GltfLoadError::Io(
+        path.to_path_buf(),
+        std::io::Error::new(std::io::ErrorKind::Other, what),
+    )
+}
+
+// Paths are URLs relative to the page
+pub async fn fetch(path: &Path) -> Result<Vec<u8>, GltfLoadError> {
+    let window = web_sys::window().ok_or_else(|| fetch_error(path, "no window".to_string()))?;
+    let response = JsFuture::from(window.fetch_with_str(&path.to_string_lossy()))
+        .await
+        .map_err(|ex| fetch_error(path, format!("{:?}", ex)))?;
+    let response: web_sys::Response = response
+        .dyn_into()
+        .map_err(|ex| fetch_error(path, format!("{:?}", ex)))?;
+    if !response.ok() {
+        return Err(fetch_error(
+            path,
+            format!("the server answered {}", response.status()),
+        ));
+    }
+    let buffer = response
+        .array_buffer()
+        .map_err(|ex| fetch_error(path, format!("{:?}", ex)))?;
+    let buffer = JsFuture::from(buffer)
+        .await
+        .map_err(|ex| fetch_error(path, format!("{:?}", ex)))?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+// Nothing is watched, so nothing beside the file matters
+pub fn dependencies(path: &Path) -> Vec<PathBuf> {
+    vec![path.to_path_buf()]
+}
+
+// Files can't be watched, so turning hot reload on fails with a message instead
+pub struct AssetWatcher;
+
+impl AssetWatcher {
+    pub fn new() -> Result<Self, String> {
+        Err("hot reload isn't available in the browser".to_string())
+    }
+
+    pub fn set_assets(&mut self, _: &[(LoadTarget, Option<(&Path, &[PathBuf])>)]) {}
+
+    pub fn next_change(&mut self) -> Option<(LoadTarget, PathBuf)> {
+        None
+    }
+}